@@ -0,0 +1,220 @@
+// auth.rs
+use actix_web::{
+    dev::Payload,
+    error::InternalError,
+    http::header::{self, HeaderMap},
+    web, FromRequest, HttpRequest, HttpResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+use mongodb::{bson::doc, Client, Collection};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::env_list;
+use crate::db::uuid_bson;
+use crate::models::user::User;
+
+const SESSION_TTL_DAYS: i64 = 30;
+
+/// A login. Only the SHA-256 of the token is stored, so a leaked
+/// `sessions` collection cannot be replayed.
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    token_hash: String,
+    user_id: Uuid,
+    created_at: mongodb::bson::DateTime,
+    /// Expired sessions are deleted by a TTL index.
+    expires_at: mongodb::bson::DateTime,
+}
+
+fn sessions(client: &Client) -> Collection<Session> {
+    client.database("cucura-ccdb").collection("sessions")
+}
+
+/// SHA-256 of a bearer or single-use token, the only form it is stored in.
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// 32 random bytes, hex encoded.
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Creates a session for `user` and returns its bearer token.
+pub async fn start_session(
+    client: &Client,
+    user: &User,
+) -> mongodb::error::Result<(String, DateTime<Utc>)> {
+    let token = new_token();
+    let now = Utc::now();
+    let expires_at = now + Duration::days(SESSION_TTL_DAYS);
+    sessions(client)
+        .insert_one(Session {
+            token_hash: token_hash(&token),
+            user_id: user.user_id,
+            created_at: mongodb::bson::DateTime::from_millis(now.timestamp_millis()),
+            expires_at: mongodb::bson::DateTime::from_millis(expires_at.timestamp_millis()),
+        })
+        .await?;
+    Ok((token, expires_at))
+}
+
+/// The user whose unexpired session `token` is.
+pub async fn session_user_id(client: &Client, token: &str) -> mongodb::error::Result<Option<Uuid>> {
+    let filter = doc! {
+        "token_hash": token_hash(token),
+        "expires_at": { "$gt": mongodb::bson::DateTime::now() },
+    };
+    Ok(sessions(client)
+        .find_one(filter)
+        .await?
+        .map(|session| session.user_id))
+}
+
+pub async fn end_session(
+    client: &Client,
+    user: &AuthenticatedUser,
+    token: &str,
+) -> mongodb::error::Result<()> {
+    sessions(client)
+        .delete_one(doc! { "token_hash": token_hash(token), "user_id": uuid_bson(user.user_id) })
+        .await?;
+    Ok(())
+}
+
+/// The caller, from an `Authorization: Bearer` session token. Handlers that
+/// take one answer 401 to anonymous or expired callers.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub email: String,
+    admin: bool,
+}
+
+impl AuthenticatedUser {
+    pub fn is_admin(&self) -> bool {
+        self.admin
+    }
+
+    /// Whether the caller is `user_id`, or an admin acting for them.
+    pub fn may_act_for(&self, user_id: Uuid) -> bool {
+        self.user_id == user_id || self.admin
+    }
+}
+
+fn unauthorized(message: &'static str) -> actix_web::Error {
+    InternalError::from_response(message, HttpResponse::Unauthorized().json(message)).into()
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req.headers()).map(str::to_string);
+        let client = req.app_data::<web::Data<Client>>().cloned();
+        Box::pin(async move {
+            let (Some(token), Some(client)) = (token, client) else {
+                return Err(unauthorized("Authentication required"));
+            };
+            let lookup = async {
+                let Some(user_id) = session_user_id(&client, &token).await? else {
+                    return Ok(None);
+                };
+                client
+                    .database("cucura-ccdb")
+                    .collection::<User>("users")
+                    .find_one(doc! { "user_id": uuid_bson(user_id) })
+                    .await
+            };
+            match lookup.await {
+                Ok(Some(user)) => Ok(AuthenticatedUser {
+                    user_id: user.user_id,
+                    email: user.email,
+                    admin: user.admin,
+                }),
+                Ok(None) => Err(unauthorized("Invalid or expired session")),
+                Err(e) => {
+                    error!(error = %e, "Failed to find session");
+                    let message = "Failed to authenticate";
+                    Err(InternalError::from_response(
+                        message,
+                        HttpResponse::InternalServerError().json(message),
+                    )
+                    .into())
+                }
+            }
+        })
+    }
+}
+
+/// Gives admin access to the users in `ADMIN_USER_IDS`, a comma-separated
+/// list. Otherwise only an admin can make another, so this is how a
+/// deployment gets its first. Ids rather than emails, since emails are not
+/// verified and anyone could register with an admin's.
+pub async fn grant_admins_from_env(client: &Client) -> mongodb::error::Result<()> {
+    let mut user_ids = Vec::new();
+    for value in env_list("ADMIN_USER_IDS").unwrap_or_default() {
+        match Uuid::parse_str(&value) {
+            Ok(user_id) => user_ids.push(uuid_bson(user_id)),
+            Err(_) => warn!(value = %value, "Ignoring invalid user id in ADMIN_USER_IDS"),
+        }
+    }
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+    let users = client.database("cucura-ccdb").collection::<User>("users");
+    let filter = doc! { "user_id": { "$in": user_ids }, "admin": { "$ne": true } };
+    let mut granted = users.find(filter.clone()).await?;
+    while let Some(user) = granted.next().await {
+        let user = user?;
+        info!(user_id = %user.user_id, username = %user.username, "Granting admin access from ADMIN_USER_IDS");
+    }
+    users
+        .update_many(filter, doc! { "$set": { "admin": true } })
+        .await?;
+    Ok(())
+}
+
+/// Like [`AuthenticatedUser`] but for admins only: 403 for everyone else.
+#[derive(Debug, Clone)]
+pub struct Admin(pub AuthenticatedUser);
+
+impl FromRequest for Admin {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = AuthenticatedUser::from_request(req, payload);
+        Box::pin(async move {
+            let user = user.await?;
+            if !user.is_admin() {
+                let message = "Admin access required";
+                return Err(InternalError::from_response(
+                    message,
+                    HttpResponse::Forbidden().json(message),
+                )
+                .into());
+            }
+            Ok(Admin(user))
+        })
+    }
+}
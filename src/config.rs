@@ -0,0 +1,37 @@
+// config.rs
+use std::env;
+
+/// Deployment environment from `APP_ENV`; decides the defaults for settings
+/// that should be permissive locally and strict in production.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Staging,
+    Production,
+}
+
+impl Environment {
+    pub fn from_env() -> Environment {
+        match env::var("APP_ENV").as_deref() {
+            Ok("production") => Environment::Production,
+            Ok("staging") => Environment::Staging,
+            Ok("development") | Err(_) => Environment::Development,
+            Ok(other) => panic!(
+                "APP_ENV must be development, staging or production, got {}",
+                other
+            ),
+        }
+    }
+}
+
+/// Comma-separated env var as a list, ignoring blanks.
+pub fn env_list(key: &str) -> Option<Vec<String>> {
+    env::var(key).ok().map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
@@ -0,0 +1,89 @@
+// db.rs
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use mongodb::bson::{spec::BinarySubtype, Binary, Bson};
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+use mongodb::event::{command::CommandEvent, EventHandler};
+use tracing::{debug, debug_span, warn, Span};
+use uuid::Uuid;
+
+use crate::metrics::MONGO_OPERATION_DURATION;
+
+/// Uuid fields are written by the driver as generic binary, so filters on them
+/// need the same representation rather than the string form.
+pub fn uuid_bson(id: Uuid) -> Bson {
+    Bson::Binary(Binary {
+        subtype: BinarySubtype::Generic,
+        bytes: id.as_bytes().to_vec(),
+    })
+}
+
+/// Whether a unique index rejected the write.
+pub fn is_duplicate_key(e: &Error) -> bool {
+    matches!(&*e.kind, ErrorKind::Write(WriteFailure::WriteError(error)) if error.code == 11000)
+}
+
+struct InFlightCommand {
+    collection: String,
+    span: Span,
+}
+
+/// Driver command monitoring: a `mongo.command` span and a latency sample per
+/// command. Started events carry the collection name, the matching
+/// succeeded/failed events carry the duration, so the two are joined on the
+/// (process-wide unique) request id. Started events fire on the calling task,
+/// so the span nests under the request span; the outcome is logged inside it.
+pub fn command_monitor() -> EventHandler<CommandEvent> {
+    let in_flight: Mutex<HashMap<i32, InFlightCommand>> = Mutex::new(HashMap::new());
+    EventHandler::callback(move |event| match event {
+        CommandEvent::Started(event) => {
+            let collection = event
+                .command
+                .get_str(&event.command_name)
+                .or_else(|_| event.command.get_str("collection"))
+                .unwrap_or("-")
+                .to_string();
+            let span = debug_span!(
+                "mongo.command",
+                db = %event.db,
+                collection = %collection,
+                command = %event.command_name,
+                duration_ms = tracing::field::Empty,
+            );
+            in_flight
+                .lock()
+                .unwrap()
+                .insert(event.request_id, InFlightCommand { collection, span });
+        }
+        CommandEvent::Succeeded(event) => {
+            let command = in_flight.lock().unwrap().remove(&event.request_id);
+            let collection = command.as_ref().map_or("-", |c| c.collection.as_str());
+            MONGO_OPERATION_DURATION
+                .with_label_values(&[collection, &event.command_name, "ok"])
+                .observe(event.duration.as_secs_f64());
+            if let Some(command) = &command {
+                command
+                    .span
+                    .record("duration_ms", event.duration.as_millis() as u64);
+                command.span.in_scope(|| debug!("Mongo command succeeded"));
+            }
+        }
+        CommandEvent::Failed(event) => {
+            let command = in_flight.lock().unwrap().remove(&event.request_id);
+            let collection = command.as_ref().map_or("-", |c| c.collection.as_str());
+            MONGO_OPERATION_DURATION
+                .with_label_values(&[collection, &event.command_name, "error"])
+                .observe(event.duration.as_secs_f64());
+            if let Some(command) = &command {
+                command
+                    .span
+                    .record("duration_ms", event.duration.as_millis() as u64);
+                command.span.in_scope(|| {
+                    warn!(error = %event.failure, "Mongo command failed");
+                });
+            }
+        }
+        _ => {}
+    })
+}
@@ -0,0 +1,37 @@
+// mailer.rs
+use std::env;
+
+use futures::future::BoxFuture;
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing email. Handlers take it as `web::Data<dyn Mailer>` so a
+/// provider can be swapped in without touching them.
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// Writes emails to the log instead of sending them; the default until a
+/// provider is configured, and what development uses.
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            info!(to = %email.to, subject = %email.subject, body = %email.body, "Email");
+            Ok(())
+        })
+    }
+}
+
+/// Absolute link into the web app (`APP_URL`) for use in emails.
+pub fn app_link(path: &str) -> String {
+    let base = env::var("APP_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    format!("{}{}", base.trim_end_matches('/'), path)
+}
@@ -1,24 +1,50 @@
+mod auth;
+mod config;
+mod db;
+mod mailer;
+mod media;
+mod metrics;
+mod middleware;
+mod migrations;
 mod models;
+mod moderation;
+mod purchases;
+mod ratings;
 mod routes;
-use actix_cors::Cors;
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+mod schedule;
+mod search;
+mod taxonomy;
+mod telemetry;
+mod tls;
+use actix_web::{middleware::from_fn, web, App, HttpResponse, HttpServer, Responder};
+use config::Environment;
 use dotenv::dotenv;
+use mailer::{LogMailer, Mailer};
+use middleware::cors::CorsConfig;
+use middleware::deprecation::deprecated;
+use middleware::https_redirect::HttpsRedirect;
+use middleware::rate_limit::RateLimiter;
+use moderation::filter::ContentFilter;
 use mongodb::{
     options::{ClientOptions, ServerApi, ServerApiVersion},
     Client,
 };
-use routes::{
-    auth_routes, business_review_routes, business_routes, message_routes, payment_routes,
-    profile_routes, training_routes, user_routes,
-};
+use routes::health_routes::{self, HealthState};
+use routes::metrics_routes;
 
 use std::env;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tls::TlsConfig;
+use tracing::{error, info};
+
+const PORT: u16 = 5001;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();
     dotenv().ok();
+    let _telemetry = telemetry::init();
 
     let uri = env::var("MONGODB_URI").expect("MONGODB_URI must be set");
 
@@ -26,36 +52,132 @@ async fn main() -> std::io::Result<()> {
 
     let server_api = ServerApi::builder().version(ServerApiVersion::V1).build();
     client_options.server_api = Some(server_api);
+    client_options.command_event_handler = Some(db::command_monitor());
 
     let client = Client::with_options(client_options).unwrap();
 
-    println!("Starting web server...");
+    // Set RUN_MIGRATIONS=false when migrations are applied by a separate job;
+    // /health/ready reports them as pending until then.
+    if env::var("RUN_MIGRATIONS").map_or(true, |value| value != "false") {
+        if let Err(e) = migrations::run_pending(&client).await {
+            error!(error = %e, "Failed to apply migrations");
+        }
+    }
+    if let Err(e) = auth::grant_admins_from_env(&client).await {
+        error!(error = %e, "Failed to grant admin access");
+    }
+
+    let shutdown_timeout = env::var("SHUTDOWN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(30);
+    let cors_config = CorsConfig::from_env(Environment::from_env());
+    let rate_limiter = web::Data::new(RateLimiter::from_env(&client));
+    let search = search::from_env(&client);
+    let search_index = web::Data::from(search.index);
+    let mailer: web::Data<dyn Mailer> = web::Data::from(Arc::new(LogMailer) as Arc<dyn Mailer>);
+    let storage = media::from_env();
+    media::spawn_sweep(
+        client.clone(),
+        storage.clone(),
+        Duration::from_secs(
+            env::var("MEDIA_SWEEP_SECS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(900),
+        ),
+    );
+    let storage = web::Data::from(storage);
+    let content_filter: web::Data<dyn ContentFilter> =
+        web::Data::from(moderation::filter::from_env());
+    let suggest_index = web::Data::from(search.suggest);
+    let health_state = web::Data::new(HealthState::default());
+    let server_health_state = health_state.clone();
+    let tls_config = TlsConfig::from_env();
+    let https_redirect = tls_config
+        .as_ref()
+        .filter(|tls| tls.redirect_port.is_some())
+        .map(|tls| {
+            web::Data::new(HttpsRedirect {
+                https_port: tls.public_port.unwrap_or(PORT),
+            })
+        });
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header()
-            .max_age(3600);
+    info!("Starting web server...");
 
-        App::new()
+    let server = HttpServer::new(move || {
+        let mut app = App::new()
             .app_data(web::Data::new(client.clone()))
-            .wrap(cors)
-            .configure(user_routes::user_routes)
-            .configure(profile_routes::profile_routes)
-            .configure(business_review_routes::review_routes)
-            .configure(training_routes::training_routes)
-            .configure(business_routes::business_routes)
-            .configure(payment_routes::payment_routes)
-            .configure(message_routes::message_routes)
-            .configure(auth_routes::auth_routes)
-      
+            .app_data(server_health_state.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(search_index.clone())
+            .app_data(suggest_index.clone())
+            .app_data(mailer.clone())
+            .app_data(storage.clone())
+            .app_data(content_filter.clone());
+        if let Some(https_redirect) = &https_redirect {
+            app = app.app_data(https_redirect.clone());
+        }
+        app.wrap(from_fn(middleware::https_redirect::https_redirect))
+            .wrap(from_fn(middleware::rate_limit::rate_limit))
+            .wrap(cors_config.build())
+            .wrap(from_fn(middleware::security_headers::security_headers))
+            .wrap(from_fn(middleware::metrics::record_metrics))
+            .wrap(from_fn(middleware::request_id::request_id))
+            .configure(health_routes::health_routes)
+            .configure(metrics_routes::metrics_routes)
+            .service(
+                web::scope("/v1")
+                    .wrap(deprecated("/v2"))
+                    .configure(routes::v1),
+            )
+            .service(web::scope("/v2").configure(routes::v2))
+            // Unversioned paths used by the first mobile releases; must stay last.
+            .service(web::scope("").wrap(deprecated("/v1")).configure(routes::v1))
     })
-    .bind(("0.0.0.0", 5001))?
-    .run()
-    .await
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout);
 
-    //println!("Server running at http://localhost:5001/");
+    let server = match &tls_config {
+        Some(tls) => {
+            let (server_config, cert_resolver) = tls.server_config()?;
+            actix_web::rt::spawn(cert_resolver.watch());
+            info!(port = PORT, "Serving HTTPS (HTTP/1.1 and HTTP/2)");
+            let server = server.bind_rustls_0_23(("0.0.0.0", PORT), server_config)?;
+            match tls.redirect_port {
+                Some(redirect_port) => {
+                    info!(port = redirect_port, "Redirecting plain HTTP to HTTPS");
+                    server.bind(("0.0.0.0", redirect_port))?
+                }
+                None => server,
+            }
+        }
+        None => server.bind(("0.0.0.0", PORT))?,
+    }
+    .run();
+
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received, draining in-flight requests...");
+        health_state.draining.store(true, Ordering::Relaxed);
+        handle.stop(true).await;
+    });
+
+    server.await
+}
 
-    //server.await
+/// Resolves on SIGTERM (orchestrator stop) or Ctrl-C.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler");
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
 }
@@ -0,0 +1,68 @@
+// media/local.rs
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use futures::future::LocalBoxFuture;
+
+use super::Storage;
+
+/// Files under a directory on this host, keys as relative paths. Suits a
+/// single instance or a shared volume.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> LocalStorage {
+        LocalStorage { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        _content_type: &'a str,
+        bytes: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let path = self.path(key);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            // Written aside and renamed, so readers never see a partial file.
+            let partial = path.with_extension("partial");
+            tokio::fs::write(&partial, bytes)
+                .await
+                .map_err(|e| e.to_string())?;
+            tokio::fs::rename(&partial, &path)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<Vec<u8>>, String>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.path(key)).await {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.to_string()),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.path(key)).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
@@ -0,0 +1,518 @@
+// media/mod.rs
+//! Uploaded images: business logos and pictures, and profile avatars.
+//! Uploads are sniffed by their magic bytes, decoded (rotated upright per
+//! their EXIF orientation), downscaled and re-encoded, which drops EXIF and
+//! any other metadata, then written with a thumbnail to a [`Storage`]: the
+//! local filesystem by default, or an S3-compatible bucket with
+//! `MEDIA_STORAGE=s3`. A background sweep deletes files whose business or
+//! profile is gone or no longer refers to them.
+use std::env;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Limits};
+use mongodb::{
+    bson::{self, doc, Document},
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::uuid_bson;
+
+pub mod local;
+pub mod s3;
+
+use local::LocalStorage;
+use s3::S3Storage;
+
+/// Larger sources are rejected before decoding, so a small file claiming
+/// huge dimensions cannot exhaust memory.
+const MAX_SOURCE_DIMENSION: u32 = 12_000;
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+const THUMBNAIL_SIZE: u32 = 256;
+const JPEG_QUALITY: u8 = 85;
+
+/// Uploads are only swept once this old, so a file is never deleted between
+/// being stored and its owner being updated to refer to it.
+const SWEEP_GRACE_MINUTES: i64 = 10;
+
+/// Where uploaded files live. Futures are local because the S3 client runs
+/// on the worker's own runtime.
+pub trait Storage: Send + Sync {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        content_type: &'a str,
+        bytes: Vec<u8>,
+    ) -> LocalBoxFuture<'a, Result<(), String>>;
+
+    fn get<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<Option<Vec<u8>>, String>>;
+
+    /// Deleting a key that does not exist succeeds.
+    fn delete<'a>(&'a self, key: &'a str) -> LocalBoxFuture<'a, Result<(), String>>;
+}
+
+/// `MEDIA_STORAGE=s3` stores in a bucket (see [`S3Storage::from_env`]);
+/// otherwise files go under `MEDIA_DIR` (default `media`).
+pub fn from_env() -> Arc<dyn Storage> {
+    match env::var("MEDIA_STORAGE").as_deref() {
+        Ok("s3") => Arc::new(S3Storage::from_env()),
+        _ => Arc::new(LocalStorage::new(
+            env::var("MEDIA_DIR").unwrap_or_else(|_| "media".to_string()),
+        )),
+    }
+}
+
+/// URL clients load a stored file from: `MEDIA_PUBLIC_URL` (a CDN or public
+/// bucket) or, by default, this API's `/v2/media` route.
+pub fn public_url(key: &str) -> String {
+    let base = env::var("MEDIA_PUBLIC_URL").unwrap_or_else(|_| "/v2/media".to_string());
+    format!("{}/{}", base.trim_end_matches('/'), key)
+}
+
+/// Keys are generated here, so anything else asked of the media route is
+/// rejected rather than passed to the storage.
+pub fn valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('/')
+        && !key.contains("..")
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_./".contains(c))
+}
+
+pub fn content_type(key: &str) -> &'static str {
+    if key.ends_with(".png") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Purpose {
+    Logo,
+    Picture,
+    Avatar,
+}
+
+impl Purpose {
+    /// Largest accepted upload, in bytes.
+    pub fn max_bytes(self) -> usize {
+        match self {
+            Purpose::Picture => 8 * 1024 * 1024,
+            Purpose::Logo | Purpose::Avatar => 2 * 1024 * 1024,
+        }
+    }
+
+    /// Stored images are downscaled to fit this square.
+    fn max_dimension(self) -> u32 {
+        match self {
+            Purpose::Picture => 2048,
+            Purpose::Logo | Purpose::Avatar => 512,
+        }
+    }
+
+    fn directory(self) -> &'static str {
+        match self {
+            Purpose::Logo => "logos",
+            Purpose::Picture => "pictures",
+            Purpose::Avatar => "avatars",
+        }
+    }
+
+    /// The owner's collection and id field, and a `$expr` that is true when
+    /// the owner refers to the upload's `$$url`.
+    fn owner(self) -> (&'static str, &'static str, Document) {
+        match self {
+            Purpose::Logo => (
+                "businesses",
+                "business_id",
+                doc! { "$eq": ["$logo", "$$url"] },
+            ),
+            Purpose::Picture => (
+                "businesses",
+                "business_id",
+                doc! { "$in": ["$$url", { "$ifNull": ["$pictures", []] }] },
+            ),
+            Purpose::Avatar => (
+                "profiles",
+                "profile_id",
+                doc! { "$eq": ["$avatar", "$$url"] },
+            ),
+        }
+    }
+}
+
+/// A stored upload, kept in the `media` collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Media {
+    pub media_id: Uuid,
+    pub purpose: Purpose,
+    /// The business (logos and pictures) or profile (avatars) it belongs to.
+    pub owner_id: Uuid,
+    pub uploaded_by: Uuid,
+    pub content_type: String,
+    pub width: u32,
+    pub height: u32,
+    /// Stored size in bytes, after processing.
+    pub size: u64,
+    pub url: String,
+    pub thumbnail_url: String,
+    pub key: String,
+    pub thumbnail_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub fn media(client: &Client) -> Collection<Media> {
+    client.database("cucura-ccdb").collection("media")
+}
+
+#[derive(Debug)]
+pub enum ProcessError {
+    /// Not a JPEG, PNG, GIF or WebP image.
+    Unsupported,
+    /// Sniffed as an image but could not be decoded, or too large to.
+    Invalid(ImageError),
+}
+
+/// An upload ready to store: JPEG, or PNG when it has transparency.
+pub struct Processed {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub image: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+}
+
+/// Decodes, orients, downscales and re-encodes an upload. CPU-bound; run it
+/// off the async workers.
+pub fn process(bytes: &[u8], purpose: Purpose) -> Result<Processed, ProcessError> {
+    let format = image::guess_format(bytes).map_err(|_| ProcessError::Unsupported)?;
+    if !matches!(
+        format,
+        ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err(ProcessError::Unsupported);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder().map_err(ProcessError::Invalid)?;
+    let orientation = decoder.orientation().map_err(ProcessError::Invalid)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(ProcessError::Invalid)?;
+    image.apply_orientation(orientation);
+
+    let max = purpose.max_dimension();
+    if image.width() > max || image.height() > max {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+    let format = if image.color().has_alpha() {
+        ImageFormat::Png
+    } else {
+        ImageFormat::Jpeg
+    };
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    Ok(Processed {
+        format,
+        width: image.width(),
+        height: image.height(),
+        image: encode(&image, format).map_err(ProcessError::Invalid)?,
+        thumbnail: encode(&thumbnail, format).map_err(ProcessError::Invalid)?,
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))?,
+        _ => image.write_to(&mut Cursor::new(&mut bytes), format)?,
+    }
+    Ok(bytes)
+}
+
+/// Writes a processed upload and its record. Nothing refers to it yet; the
+/// caller points the owner at `url`, or calls [`release`] if it cannot.
+pub async fn store(
+    client: &Client,
+    storage: &dyn Storage,
+    purpose: Purpose,
+    owner_id: Uuid,
+    uploaded_by: Uuid,
+    processed: Processed,
+) -> Result<Media, String> {
+    let media_id = Uuid::new_v4();
+    let extension = match processed.format {
+        ImageFormat::Png => "png",
+        _ => "jpg",
+    };
+    let directory = format!("{}/{}", purpose.directory(), owner_id);
+    let key = format!("{}/{}.{}", directory, media_id, extension);
+    let thumbnail_key = format!("{}/{}_thumb.{}", directory, media_id, extension);
+    let record = Media {
+        media_id,
+        purpose,
+        owner_id,
+        uploaded_by,
+        content_type: content_type(&key).to_string(),
+        width: processed.width,
+        height: processed.height,
+        size: processed.image.len() as u64,
+        url: public_url(&key),
+        thumbnail_url: public_url(&thumbnail_key),
+        key,
+        thumbnail_key,
+        created_at: Utc::now(),
+    };
+
+    storage
+        .put(&record.key, &record.content_type, processed.image)
+        .await?;
+    storage
+        .put(
+            &record.thumbnail_key,
+            &record.content_type,
+            processed.thumbnail,
+        )
+        .await?;
+    if let Err(e) = media(client).insert_one(&record).await {
+        // Unrecorded files would never be swept.
+        remove_files(storage, &record).await;
+        return Err(e.to_string());
+    }
+    Ok(record)
+}
+
+async fn remove_files(storage: &dyn Storage, record: &Media) -> bool {
+    let mut removed = true;
+    for key in [&record.key, &record.thumbnail_key] {
+        if let Err(e) = storage.delete(key).await {
+            warn!(error = %e, key = %key, "Failed to delete media file");
+            removed = false;
+        }
+    }
+    removed
+}
+
+/// Deletes the uploads matching `filter` that their owner no longer refers
+/// to. Failures are logged and left for the sweep to retry.
+pub async fn release(client: &Client, storage: &dyn Storage, filter: Document) {
+    let found = match media(client).find(filter).await {
+        Ok(cursor) => cursor.collect::<Vec<_>>().await,
+        Err(e) => {
+            warn!(error = %e, "Failed to find media");
+            return;
+        }
+    };
+    for record in found.into_iter().flatten() {
+        if remove_files(storage, &record).await {
+            if let Err(e) = media(client)
+                .delete_one(doc! { "media_id": uuid_bson(record.media_id) })
+                .await
+            {
+                warn!(error = %e, "Failed to delete media record");
+            }
+        }
+    }
+}
+
+/// Uploads of `purpose` whose owner is deleted or no longer refers to them.
+async fn orphans(client: &Client, purpose: Purpose) -> mongodb::error::Result<Vec<Document>> {
+    let (collection, id_field, refers) = purpose.owner();
+    let cutoff = (Utc::now() - chrono::Duration::minutes(SWEEP_GRACE_MINUTES))
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    let cursor = client
+        .database("cucura-ccdb")
+        .collection::<Document>("media")
+        .aggregate([
+            doc! { "$match": { "purpose": bson::to_bson(&purpose).unwrap(), "created_at": { "$lt": cutoff } } },
+            doc! { "$lookup": {
+                "from": collection,
+                "localField": "owner_id",
+                "foreignField": id_field,
+                "let": { "url": "$url" },
+                "pipeline": [
+                    { "$match": { "$expr": refers } },
+                    { "$project": { "_id": 1 } },
+                ],
+                "as": "owner",
+            } },
+            doc! { "$match": { "owner": { "$size": 0 } } },
+            doc! { "$project": { "media_id": 1 } },
+        ])
+        .await?;
+    cursor.collect::<Vec<_>>().await.into_iter().collect()
+}
+
+/// Periodically deletes orphaned uploads, e.g. after a business or profile
+/// is deleted or its logo replaced.
+pub fn spawn_sweep(client: Client, storage: Arc<dyn Storage>, interval: Duration) {
+    actix_web::rt::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            for purpose in [Purpose::Logo, Purpose::Picture, Purpose::Avatar] {
+                let ids: Vec<_> = match orphans(&client, purpose).await {
+                    Ok(found) => found
+                        .iter()
+                        .filter_map(|document| document.get("media_id").cloned())
+                        .collect(),
+                    Err(e) => {
+                        warn!(error = %e, "Failed to find orphaned media");
+                        break;
+                    }
+                };
+                if ids.is_empty() {
+                    continue;
+                }
+                info!(count = ids.len(), purpose = ?purpose, "Deleting orphaned media");
+                release(
+                    &client,
+                    storage.as_ref(),
+                    doc! { "media_id": { "$in": ids } },
+                )
+                .await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgb, Rgba};
+
+    use super::*;
+
+    fn encoded(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    fn photo(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        }))
+    }
+
+    /// A JPEG with an EXIF segment saying to rotate it 90° clockwise.
+    fn rotated_jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+        let jpeg = encoded(photo(width, height), ImageFormat::Jpeg);
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&[0, 1]); // one entry
+        tiff.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]); // orientation 6
+        tiff.extend_from_slice(&[0, 0, 0, 0]); // no next IFD
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend_from_slice(&tiff);
+        let length = (app1.len() + 2) as u16;
+
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xE1]);
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(&app1);
+        bytes.extend_from_slice(&jpeg[2..]);
+        bytes
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_supported_image() {
+        assert!(matches!(
+            process(b"just some text", Purpose::Picture),
+            Err(ProcessError::Unsupported)
+        ));
+        // Sniffed as a BMP, which is not accepted.
+        let bmp = [b"BM".as_slice(), &[0; 64]].concat();
+        assert!(matches!(
+            process(&bmp, Purpose::Picture),
+            Err(ProcessError::Unsupported)
+        ));
+        let mut truncated = encoded(photo(64, 64), ImageFormat::Png);
+        truncated.truncate(40);
+        assert!(matches!(
+            process(&truncated, Purpose::Picture),
+            Err(ProcessError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn downscales_to_the_purpose_s_size() {
+        let png = encoded(photo(1200, 600), ImageFormat::Png);
+        let processed = process(&png, Purpose::Logo).unwrap();
+        assert_eq!((processed.width, processed.height), (512, 256));
+        assert_eq!(processed.format, ImageFormat::Jpeg);
+        let stored = image::load_from_memory(&processed.image).unwrap();
+        assert_eq!((stored.width(), stored.height()), (512, 256));
+        let thumbnail = image::load_from_memory(&processed.thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+
+        let small = process(&png, Purpose::Picture).unwrap();
+        assert_eq!((small.width, small.height), (1200, 600));
+    }
+
+    #[test]
+    fn keeps_transparency_as_png() {
+        let image = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(32, 32, Rgba([0, 0, 0, 0])));
+        let processed = process(&encoded(image, ImageFormat::Png), Purpose::Avatar).unwrap();
+        assert_eq!(processed.format, ImageFormat::Png);
+        assert_eq!(
+            image::guess_format(&processed.image).unwrap(),
+            ImageFormat::Png
+        );
+    }
+
+    #[test]
+    fn applies_exif_orientation_then_drops_exif() {
+        let upload = rotated_jpeg_with_exif(40, 20);
+        assert!(contains(&upload, b"Exif\0\0"));
+        let processed = process(&upload, Purpose::Picture).unwrap();
+        assert_eq!((processed.width, processed.height), (20, 40));
+        assert!(!contains(&processed.image, b"Exif"));
+        assert!(!contains(&processed.thumbnail, b"Exif"));
+    }
+
+    #[test]
+    fn valid_key_only_accepts_generated_keys() {
+        for key in [
+            "logos/0b6f1c9e-4e0a-4a7e-9d55-2f7c1c2d3e4f/1a2b.jpg",
+            "avatars/abc/def_thumb.png",
+        ] {
+            assert!(valid_key(key), "{} was rejected", key);
+        }
+        for key in [
+            "",
+            "/etc/passwd",
+            "../secret",
+            "logos/../../etc/passwd",
+            "Logos/A.jpg",
+            "logos/a b.jpg",
+            "logos/a%2f.jpg",
+            "logos\\a.jpg",
+        ] {
+            assert!(!valid_key(key), "{} was accepted", key);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// metrics.rs
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use prometheus::{
+    core::Collector, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry,
+};
+
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<C: Collector + Clone + 'static>(collector: C) -> C {
+    REGISTRY
+        .register(Box::new(collector.clone()))
+        .expect("metric registered twice");
+    collector
+}
+
+pub static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "http_requests_total",
+                "HTTP requests by route template and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route template and status",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static MONGO_OPERATION_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "mongo_operation_duration_seconds",
+                "MongoDB command latency by collection and command",
+            )
+            .buckets(vec![
+                0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+            ]),
+            &["collection", "command", "outcome"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static BCRYPT_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "bcrypt_duration_seconds",
+                "Time spent hashing and verifying passwords",
+            )
+            .buckets(vec![0.05, 0.1, 0.2, 0.3, 0.5, 0.75, 1.0, 2.0]),
+            &["operation"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static USER_REGISTRATIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("cucura_user_registrations_total", "Users registered").unwrap())
+});
+
+pub static BUSINESS_REGISTRATIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(
+        IntCounter::new(
+            "cucura_business_registrations_total",
+            "Businesses registered",
+        )
+        .unwrap(),
+    )
+});
+
+pub static PAYMENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "cucura_payments_total",
+                "Payments recorded, labelled by the status they were created with or moved to",
+            ),
+            &["status"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static MESSAGES_SENT: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("cucura_messages_sent_total", "Messages sent").unwrap())
+});
+
+/// Runs a bcrypt `hash` or `verify` call and records how long it took.
+pub fn time_bcrypt<T>(operation: &str, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    BCRYPT_DURATION
+        .with_label_values(&[operation])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/// Counts a payment under its status. Statuses are free text from clients,
+/// so anything outside the known ones is counted as `other` to keep the
+/// label set bounded.
+pub fn record_payment(status: &str) {
+    let status = status.to_lowercase();
+    let label = match status.as_str() {
+        "pending" | "completed" | "failed" | "refunded" => status.as_str(),
+        _ => "other",
+    };
+    PAYMENTS.with_label_values(&[label]).inc();
+}
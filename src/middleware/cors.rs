@@ -0,0 +1,90 @@
+// middleware/cors.rs
+use std::env;
+
+use actix_cors::Cors;
+
+use crate::config::{env_list, Environment};
+
+const DEFAULT_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+const DEFAULT_HEADERS: &[&str] = &[
+    "authorization",
+    "content-type",
+    "accept",
+    "x-request-id",
+    "x-api-key",
+];
+/// Response headers browsers may read: pagination/versioning/limits/tracing.
+const EXPOSED_HEADERS: &[&str] = &[
+    "location",
+    "x-request-id",
+    "deprecation",
+    "sunset",
+    "link",
+    "retry-after",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+    "ratelimit-policy",
+];
+
+/// CORS settings read once at startup. `Cors` itself is not `Clone`, so each
+/// worker builds its own from this.
+///
+/// `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS` and `CORS_ALLOWED_HEADERS` are
+/// comma-separated; `*` origins are only accepted without credentials. With no
+/// origins configured, development allows any origin and other environments
+/// allow none.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    origins: Option<Vec<String>>,
+    methods: Vec<String>,
+    headers: Vec<String>,
+    credentials: bool,
+    max_age: usize,
+}
+
+impl CorsConfig {
+    pub fn from_env(environment: Environment) -> CorsConfig {
+        let origins = match env_list("CORS_ALLOWED_ORIGINS") {
+            Some(origins) if origins.iter().any(|origin| origin == "*") => None,
+            Some(origins) => Some(origins),
+            None if environment == Environment::Development => None,
+            None => Some(Vec::new()),
+        };
+        let credentials = env::var("CORS_ALLOW_CREDENTIALS").is_ok_and(|value| value == "true");
+        if credentials && origins.is_none() {
+            panic!("CORS_ALLOW_CREDENTIALS=true needs an explicit CORS_ALLOWED_ORIGINS list");
+        }
+
+        CorsConfig {
+            origins,
+            methods: env_list("CORS_ALLOWED_METHODS")
+                .unwrap_or_else(|| DEFAULT_METHODS.iter().map(|m| m.to_string()).collect()),
+            headers: env_list("CORS_ALLOWED_HEADERS")
+                .unwrap_or_else(|| DEFAULT_HEADERS.iter().map(|h| h.to_string()).collect()),
+            credentials,
+            max_age: env::var("CORS_MAX_AGE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(3600),
+        }
+    }
+
+    pub fn build(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.methods.iter().map(String::as_str))
+            .allowed_headers(self.headers.iter().map(String::as_str))
+            .expose_headers(EXPOSED_HEADERS.iter().copied())
+            .max_age(self.max_age);
+        cors = match &self.origins {
+            None => cors.allow_any_origin(),
+            Some(origins) => origins
+                .iter()
+                .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+        };
+        if self.credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
@@ -0,0 +1,54 @@
+// middleware/deprecation.rs
+use std::env;
+use std::sync::OnceLock;
+
+use actix_web::middleware::DefaultHeaders;
+use chrono::{NaiveDate, NaiveTime};
+
+const DEFAULT_DEPRECATED_AT: &str = "2026-11-01";
+const DEFAULT_SUNSET: &str = "2027-11-01";
+
+struct Schedule {
+    deprecation: String,
+    sunset: String,
+}
+
+fn schedule() -> &'static Schedule {
+    static SCHEDULE: OnceLock<Schedule> = OnceLock::new();
+    SCHEDULE.get_or_init(|| {
+        let deprecated_at = date_from_env("API_DEPRECATED_AT", DEFAULT_DEPRECATED_AT);
+        let sunset = date_from_env("API_SUNSET", DEFAULT_SUNSET);
+        Schedule {
+            // RFC 9745 structured date, e.g. `@1793491200`
+            deprecation: format!(
+                "@{}",
+                deprecated_at.and_time(NaiveTime::MIN).and_utc().timestamp()
+            ),
+            // RFC 8594 wants an HTTP-date
+            sunset: sunset
+                .and_time(NaiveTime::MIN)
+                .and_utc()
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        }
+    })
+}
+
+fn date_from_env(key: &str, default: &str) -> NaiveDate {
+    let value = env::var(key).unwrap_or_else(|_| default.to_string());
+    NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .unwrap_or_else(|_| panic!("{} must be a YYYY-MM-DD date", key))
+}
+
+/// Adds `Deprecation`, `Sunset` and a `successor-version` link to every response
+/// of the wrapped scope or resource.
+pub fn deprecated(successor: &str) -> DefaultHeaders {
+    let schedule = schedule();
+    DefaultHeaders::new()
+        .add(("Deprecation", schedule.deprecation.as_str()))
+        .add(("Sunset", schedule.sunset.as_str()))
+        .add((
+            "Link",
+            format!("<{}>; rel=\"successor-version\"", successor),
+        ))
+}
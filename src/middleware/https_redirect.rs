@@ -0,0 +1,62 @@
+// middleware/https_redirect.rs
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+
+/// Registered as app data when the plain HTTP redirect listener is enabled.
+/// `https_port` is the public HTTPS port, left out of the URL when it is 443.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpsRedirect {
+    pub https_port: u16,
+}
+
+/// Answers every request that arrived over plain HTTP with a 308 to the same
+/// path on HTTPS; 308 rather than 301 so clients replay POST bodies.
+pub async fn https_redirect(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let redirect = req
+        .app_data::<web::Data<HttpsRedirect>>()
+        .map(|redirect| *redirect.get_ref());
+    let redirect = match redirect {
+        Some(redirect) if !req.app_config().secure() => redirect,
+        _ => return Ok(next.call(req).await?.map_into_left_body()),
+    };
+
+    let host = strip_port(req.connection_info().host()).to_string();
+    let authority = match redirect.https_port {
+        443 => host,
+        port => format!("{}:{}", host, port),
+    };
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+
+    let response = HttpResponse::PermanentRedirect()
+        .insert_header((
+            header::LOCATION,
+            format!("https://{}{}", authority, path_and_query),
+        ))
+        .finish();
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+/// Drops the plain listener's port from a Host value, leaving bare IPv6
+/// literals (`[::1]`) intact.
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if port.bytes().all(|b| b.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
@@ -0,0 +1,7 @@
+pub mod cors;
+pub mod deprecation;
+pub mod https_redirect;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
+pub mod security_headers;
@@ -0,0 +1,374 @@
+// middleware/rate_limit/mod.rs
+pub mod store;
+
+use std::env;
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use mongodb::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::auth::{bearer_token, session_user_id};
+use store::{Decision, MemoryStore, MongoStore, RateLimitStore};
+
+/// Header carrying an API key for [`KeyBy::ApiKey`] policies.
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// What identifies a client for a policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyBy {
+    /// The signed-in user, then the IP. Only sessions the server has
+    /// checked count: a made-up token would otherwise get a fresh bucket on
+    /// every request.
+    #[default]
+    User,
+    /// The `X-API-Key` header, then the IP. Keys are not checked here, so
+    /// only use this in front of routes that reject unknown keys.
+    ApiKey,
+    Ip,
+}
+
+/// A token bucket applied to a set of routes: `capacity` requests in a burst,
+/// refilled at `refill_per_minute`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Policy {
+    pub name: String,
+    /// `"METHOD /path/template"` without the version prefix, e.g.
+    /// `"POST /businesses/{business_id}/reviews"`; `*` matches any method or path.
+    pub routes: Vec<String>,
+    pub capacity: u32,
+    pub refill_per_minute: f64,
+    #[serde(default)]
+    pub key: KeyBy,
+}
+
+impl Policy {
+    pub fn refill_per_second(&self) -> f64 {
+        self.refill_per_minute / 60.0
+    }
+
+    fn matches(&self, method: &str, route: &str) -> bool {
+        self.routes.iter().any(|spec| {
+            let (spec_method, spec_route) = spec.split_once(' ').unwrap_or(("*", spec));
+            (spec_method == "*" || spec_method.eq_ignore_ascii_case(method))
+                && (spec_route == "*" || spec_route == route)
+        })
+    }
+}
+
+fn default_policies() -> Vec<Policy> {
+    let policy = |name: &str, routes: &[&str], capacity, refill_per_minute, key| Policy {
+        name: name.to_string(),
+        routes: routes.iter().map(|route| route.to_string()).collect(),
+        capacity,
+        refill_per_minute,
+        key,
+    };
+    vec![
+        policy(
+            "registration",
+            &["POST /users/register", "POST /users"],
+            5,
+            5.0 / 60.0,
+            KeyBy::Ip,
+        ),
+        policy(
+            "login",
+            &["POST /auth/login", "POST /sessions"],
+            10,
+            5.0,
+            KeyBy::Ip,
+        ),
+        policy(
+            "messages",
+            &["POST /messages/send", "POST /messages"],
+            20,
+            10.0,
+            KeyBy::User,
+        ),
+        policy(
+            "reviews",
+            &[
+                "POST /reviews/add",
+                "POST /businesses/{business_id}/reviews",
+            ],
+            5,
+            0.5,
+            KeyBy::User,
+        ),
+    ]
+}
+
+pub struct RateLimiter {
+    policies: Vec<Policy>,
+    store: Box<dyn RateLimitStore>,
+    trust_proxy: bool,
+    /// For looking up sessions.
+    client: Client,
+}
+
+impl RateLimiter {
+    /// `RATE_LIMIT_POLICIES` (a JSON array of policies) replaces the defaults,
+    /// `RATE_LIMIT_STORE=mongo` shares buckets between instances, and
+    /// `RATE_LIMIT_TRUST_PROXY=true` takes the client IP from `Forwarded` /
+    /// `X-Forwarded-For`.
+    pub fn from_env(client: &Client) -> RateLimiter {
+        let policies = match env::var("RATE_LIMIT_POLICIES") {
+            Ok(json) => serde_json::from_str(&json)
+                .expect("RATE_LIMIT_POLICIES must be a JSON array of policies"),
+            Err(_) => default_policies(),
+        };
+        let store: Box<dyn RateLimitStore> = match env::var("RATE_LIMIT_STORE").as_deref() {
+            Ok("mongo") => Box::new(MongoStore::new(client)),
+            _ => Box::new(MemoryStore::default()),
+        };
+        RateLimiter {
+            policies,
+            store,
+            trust_proxy: env::var("RATE_LIMIT_TRUST_PROXY").is_ok_and(|value| value == "true"),
+            client: client.clone(),
+        }
+    }
+
+    fn policy_for(&self, method: &str, route: &str) -> Option<&Policy> {
+        self.policies
+            .iter()
+            .find(|policy| policy.matches(method, route))
+    }
+
+    async fn client_key(&self, req: &ServiceRequest, key_by: KeyBy) -> String {
+        match key_by {
+            KeyBy::User => {
+                if let Some(token) = bearer_token(req.headers()) {
+                    match session_user_id(&self.client, token).await {
+                        Ok(Some(user_id)) => return format!("user:{}", user_id),
+                        Ok(None) => (),
+                        Err(e) => warn!(error = %e, "Failed to find session, limiting by IP"),
+                    }
+                }
+            }
+            KeyBy::ApiKey => {
+                if let Some(key) = api_key(req.headers()) {
+                    // Hashed so keys never sit in the store in the clear.
+                    return format!("key:{:x}", Sha256::digest(key.as_bytes()));
+                }
+            }
+            KeyBy::Ip => (),
+        }
+        let info = req.connection_info();
+        let ip = if self.trust_proxy {
+            info.realip_remote_addr()
+        } else {
+            info.peer_addr()
+        };
+        format!("ip:{}", ip.unwrap_or("unknown"))
+    }
+}
+
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(API_KEY_HEADER)?
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// Route template without the `/v1` / `/v2` prefix, so one policy covers every
+/// version of an endpoint.
+fn unversioned(route: &str) -> &str {
+    ["/v1", "/v2"]
+        .iter()
+        .find_map(|prefix| route.strip_prefix(prefix))
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(route)
+}
+
+fn set_headers(headers: &mut HeaderMap, policy: &Policy, decision: &Decision) {
+    let window = (policy.capacity as f64 * 60.0 / policy.refill_per_minute).round() as u64;
+    let values = [
+        ("ratelimit-limit", policy.capacity.to_string()),
+        ("ratelimit-remaining", decision.remaining.to_string()),
+        (
+            "ratelimit-reset",
+            decision.reset_after.as_secs().to_string(),
+        ),
+        (
+            "ratelimit-policy",
+            format!("{};w={};name=\"{}\"", policy.capacity, window, policy.name),
+        ),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(HeaderName::from_static(name), value);
+        }
+    }
+    if let Some(retry_after) = decision.retry_after {
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(retry_after.as_secs()),
+        );
+    }
+}
+
+/// Applies the first matching policy. Store failures fail open: an outage of
+/// the limiter backend should not take the API down with it.
+pub async fn rate_limit(
+    limiter: web::Data<RateLimiter>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let route = req.match_pattern().unwrap_or_default();
+    let Some(policy) = limiter.policy_for(req.method().as_str(), unversioned(&route)) else {
+        return next.call(req).await.map(|res| res.map_into_left_body());
+    };
+
+    let key = format!(
+        "{}:{}",
+        policy.name,
+        limiter.client_key(&req, policy.key).await
+    );
+    let decision = match limiter.store.acquire(&key, policy).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            warn!(error = %e, policy = %policy.name, "Rate limit store unavailable, allowing request");
+            return next.call(req).await.map(|res| res.map_into_left_body());
+        }
+    };
+
+    if !decision.allowed {
+        let mut res = HttpResponse::TooManyRequests().json("Too many requests, slow down");
+        set_headers(res.headers_mut(), policy, &decision);
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+
+    let mut res = next.call(req).await?;
+    set_headers(res.headers_mut(), policy, &decision);
+    Ok(res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn policies_match_method_and_unversioned_route() {
+        let reviews = default_policies()
+            .into_iter()
+            .find(|policy| policy.name == "reviews")
+            .unwrap();
+        assert!(reviews.matches("POST", "/businesses/{business_id}/reviews"));
+        assert!(reviews.matches("post", "/reviews/add"));
+        assert!(!reviews.matches("GET", "/businesses/{business_id}/reviews"));
+
+        let any = Policy {
+            name: "any".to_string(),
+            routes: vec!["* /messages".to_string(), "/users".to_string()],
+            capacity: 1,
+            refill_per_minute: 1.0,
+            key: KeyBy::User,
+        };
+        assert!(any.matches("DELETE", "/messages"));
+        assert!(any.matches("GET", "/users"));
+        assert!(!any.matches("GET", "/users/{username}"));
+    }
+
+    #[test]
+    fn unversioned_strips_only_a_version_prefix() {
+        assert_eq!(unversioned("/v2/reviews/add"), "/reviews/add");
+        assert_eq!(unversioned("/v1/users"), "/users");
+        assert_eq!(unversioned("/v10/users"), "/v10/users");
+        assert_eq!(unversioned("/metrics"), "/metrics");
+    }
+
+    #[test]
+    fn policies_read_every_key() {
+        let policies: Vec<Policy> = serde_json::from_str(
+            r#"[
+                {"name": "a", "routes": ["*"], "capacity": 1, "refill_per_minute": 1, "key": "api_key"},
+                {"name": "b", "routes": ["*"], "capacity": 1, "refill_per_minute": 1, "key": "ip"},
+                {"name": "c", "routes": ["*"], "capacity": 1, "refill_per_minute": 1}
+            ]"#,
+        )
+        .unwrap();
+        let keys: Vec<KeyBy> = policies.iter().map(|policy| policy.key).collect();
+        assert_eq!(keys, [KeyBy::ApiKey, KeyBy::Ip, KeyBy::User]);
+        assert!(serde_json::from_str::<KeyBy>(r#""token""#).is_err());
+    }
+
+    /// A limiter whose session lookups never reach a database.
+    async fn limiter() -> RateLimiter {
+        let client = Client::with_uri_str("mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100")
+            .await
+            .unwrap();
+        RateLimiter {
+            policies: default_policies(),
+            store: Box::new(MemoryStore::default()),
+            trust_proxy: false,
+            client,
+        }
+    }
+
+    fn request(api_key: Option<&str>) -> ServiceRequest {
+        let mut request = TestRequest::default().peer_addr("203.0.113.7:4000".parse().unwrap());
+        if let Some(api_key) = api_key {
+            request = request.insert_header((API_KEY_HEADER, api_key));
+        }
+        request.to_srv_request()
+    }
+
+    #[actix_web::test]
+    async fn api_key_policies_are_keyed_by_a_hash_of_the_key() {
+        let limiter = limiter().await;
+        let first = limiter
+            .client_key(&request(Some("secret-one")), KeyBy::ApiKey)
+            .await;
+        assert!(first.starts_with("key:"));
+        assert!(!first.contains("secret-one"));
+        assert_eq!(
+            limiter
+                .client_key(&request(Some(" secret-one ")), KeyBy::ApiKey)
+                .await,
+            first
+        );
+        assert_ne!(
+            limiter
+                .client_key(&request(Some("secret-two")), KeyBy::ApiKey)
+                .await,
+            first
+        );
+    }
+
+    #[actix_web::test]
+    async fn requests_without_a_credential_are_keyed_by_ip() {
+        let limiter = limiter().await;
+        for key_by in [KeyBy::ApiKey, KeyBy::User, KeyBy::Ip] {
+            assert_eq!(
+                limiter.client_key(&request(None), key_by).await,
+                "ip:203.0.113.7"
+            );
+        }
+        assert_eq!(
+            limiter
+                .client_key(&request(Some("   ")), KeyBy::ApiKey)
+                .await,
+            "ip:203.0.113.7"
+        );
+        assert_eq!(
+            limiter
+                .client_key(&request(Some("secret-one")), KeyBy::Ip)
+                .await,
+            "ip:203.0.113.7"
+        );
+    }
+}
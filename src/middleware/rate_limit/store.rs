@@ -0,0 +1,257 @@
+// middleware/rate_limit/store.rs
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use mongodb::{
+    bson::{doc, Document},
+    options::ReturnDocument,
+    Client, Collection,
+};
+
+use super::Policy;
+
+/// Outcome of taking one token from a bucket.
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub allowed: bool,
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset_after: Duration,
+    /// Until the next token is available; only set when the request was refused.
+    pub retry_after: Option<Duration>,
+}
+
+/// Whole seconds, rounded up, ignoring float noise such as `3600.0000000000005`.
+fn whole_seconds(seconds: f64) -> Duration {
+    Duration::from_secs((seconds - 1e-6).ceil().max(0.0) as u64)
+}
+
+impl Decision {
+    fn from_tokens(policy: &Policy, allowed: bool, tokens: f64) -> Decision {
+        let rate = policy.refill_per_second();
+        Decision {
+            allowed,
+            remaining: tokens.max(0.0).floor() as u32,
+            reset_after: whole_seconds((policy.capacity as f64 - tokens).max(0.0) / rate),
+            retry_after: (!allowed)
+                .then(|| whole_seconds((1.0 - tokens) / rate).max(Duration::from_secs(1))),
+        }
+    }
+}
+
+pub trait RateLimitStore: Send + Sync {
+    /// Refills the bucket for `key` and tries to take one token from it.
+    fn acquire<'a>(
+        &'a self,
+        key: &'a str,
+        policy: &'a Policy,
+    ) -> BoxFuture<'a, mongodb::error::Result<Decision>>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Per-process buckets. Fine for a single instance; counts are not shared
+/// between replicas.
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+const MEMORY_STORE_SWEEP_THRESHOLD: usize = 10_000;
+
+impl RateLimitStore for MemoryStore {
+    fn acquire<'a>(
+        &'a self,
+        key: &'a str,
+        policy: &'a Policy,
+    ) -> BoxFuture<'a, mongodb::error::Result<Decision>> {
+        Box::pin(async move {
+            let now = Instant::now();
+            let capacity = policy.capacity as f64;
+            let rate = policy.refill_per_second();
+            let mut buckets = self.buckets.lock().unwrap();
+
+            // Buckets that have refilled completely carry no state worth keeping.
+            if buckets.len() > MEMORY_STORE_SWEEP_THRESHOLD {
+                buckets.retain(|_, bucket| {
+                    bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * rate
+                        < capacity
+                });
+            }
+
+            let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+                tokens: capacity,
+                updated_at: now,
+            });
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+            bucket.updated_at = now;
+
+            let allowed = bucket.tokens >= 1.0;
+            if allowed {
+                bucket.tokens -= 1.0;
+            }
+            Ok(Decision::from_tokens(policy, allowed, bucket.tokens))
+        })
+    }
+}
+
+/// Buckets shared by every instance through the `rate_limits` collection. The
+/// refill and take happen in one pipeline update, using the server clock, so
+/// concurrent requests on different replicas cannot both spend the last token.
+pub struct MongoStore {
+    collection: Collection<Document>,
+}
+
+impl MongoStore {
+    pub fn new(client: &Client) -> MongoStore {
+        MongoStore {
+            collection: client.database("cucura-ccdb").collection("rate_limits"),
+        }
+    }
+}
+
+impl RateLimitStore for MongoStore {
+    fn acquire<'a>(
+        &'a self,
+        key: &'a str,
+        policy: &'a Policy,
+    ) -> BoxFuture<'a, mongodb::error::Result<Decision>> {
+        Box::pin(async move {
+            let capacity = policy.capacity as f64;
+            let rate = policy.refill_per_second();
+            let full_after_ms = (capacity / rate * 1000.0).ceil() as i64;
+            let pipeline = vec![
+                doc! { "$set": {
+                    "tokens": { "$min": [
+                        capacity,
+                        { "$add": [
+                            { "$ifNull": ["$tokens", capacity] },
+                            { "$multiply": [
+                                { "$divide": [
+                                    { "$subtract": ["$$NOW", { "$ifNull": ["$updated_at", "$$NOW"] }] },
+                                    1000,
+                                ] },
+                                rate,
+                            ] },
+                        ] },
+                    ] },
+                    "updated_at": "$$NOW",
+                    "expires_at": { "$add": ["$$NOW", full_after_ms] },
+                } },
+                doc! { "$set": { "allowed": { "$gte": ["$tokens", 1] } } },
+                doc! { "$set": { "tokens": {
+                    "$cond": ["$allowed", { "$subtract": ["$tokens", 1] }, "$tokens"],
+                } } },
+            ];
+
+            let bucket = self
+                .collection
+                .find_one_and_update(doc! { "_id": key }, pipeline)
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .await?
+                .unwrap_or_default();
+            let allowed = bucket.get_bool("allowed").unwrap_or(true);
+            let tokens = bucket.get_f64("tokens").unwrap_or(capacity);
+            Ok(Decision::from_tokens(policy, allowed, tokens))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::super::KeyBy;
+    use super::*;
+
+    fn policy(capacity: u32, refill_per_minute: f64) -> Policy {
+        Policy {
+            name: "test".to_string(),
+            routes: vec!["*".to_string()],
+            capacity,
+            refill_per_minute,
+            key: KeyBy::Ip,
+        }
+    }
+
+    /// Moves the bucket's last refill `seconds` into the past.
+    fn rewind(store: &MemoryStore, key: &str, seconds: f64) {
+        let mut buckets = store.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(key).unwrap();
+        bucket.updated_at = bucket
+            .updated_at
+            .checked_sub(Duration::from_secs_f64(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn allows_a_burst_of_capacity_then_refuses() {
+        let store = MemoryStore::default();
+        let policy = policy(3, 1.0);
+        for remaining in [2, 1, 0] {
+            let decision = block_on(store.acquire("a", &policy)).unwrap();
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+            assert_eq!(decision.retry_after, None);
+        }
+        let refused = block_on(store.acquire("a", &policy)).unwrap();
+        assert!(!refused.allowed);
+        assert_eq!(refused.remaining, 0);
+        assert_eq!(refused.retry_after, Some(Duration::from_secs(60)));
+        assert_eq!(refused.reset_after, Duration::from_secs(180));
+
+        // Other clients have their own bucket.
+        assert!(block_on(store.acquire("b", &policy)).unwrap().allowed);
+    }
+
+    #[test]
+    fn refills_at_the_policy_rate() {
+        let store = MemoryStore::default();
+        let policy = policy(5, 60.0);
+        for _ in 0..5 {
+            block_on(store.acquire("a", &policy)).unwrap();
+        }
+        assert!(!block_on(store.acquire("a", &policy)).unwrap().allowed);
+
+        rewind(&store, "a", 2.5);
+        let decision = block_on(store.acquire("a", &policy)).unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+        assert!(block_on(store.acquire("a", &policy)).unwrap().allowed);
+        assert!(!block_on(store.acquire("a", &policy)).unwrap().allowed);
+    }
+
+    #[test]
+    fn refill_stops_at_capacity() {
+        let store = MemoryStore::default();
+        let policy = policy(5, 60.0);
+        block_on(store.acquire("a", &policy)).unwrap();
+        rewind(&store, "a", 3600.0);
+        let decision = block_on(store.acquire("a", &policy)).unwrap();
+        assert_eq!(decision.remaining, 4);
+    }
+
+    #[test]
+    fn decision_times_round_up_to_whole_seconds() {
+        // 0.5 a minute: a token every two minutes.
+        let policy = policy(5, 0.5);
+        let refused = Decision::from_tokens(&policy, false, 0.25);
+        assert_eq!(refused.retry_after, Some(Duration::from_secs(90)));
+        assert_eq!(refused.reset_after, Duration::from_secs(570));
+
+        let full = Decision::from_tokens(&policy, true, 5.0);
+        assert_eq!(full.remaining, 5);
+        assert_eq!(full.reset_after, Duration::ZERO);
+
+        assert_eq!(whole_seconds(3600.0000000000005), Duration::from_secs(3600));
+        assert_eq!(whole_seconds(0.2), Duration::from_secs(1));
+        assert_eq!(whole_seconds(-3.0), Duration::ZERO);
+    }
+}
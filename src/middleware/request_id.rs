@@ -0,0 +1,65 @@
+// middleware/request_id.rs
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use tracing::{error, info, info_span, Instrument};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Ids from upstream proxies are kept if they look sane, otherwise we mint one.
+fn incoming_request_id(req: &ServiceRequest) -> Option<String> {
+    let value = req.headers().get(&REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !value.is_empty()
+        && value.len() <= 128
+        && value.bytes().all(|byte| byte.is_ascii_graphic());
+    valid.then(|| value.to_string())
+}
+
+/// Propagates or generates `X-Request-Id`, runs the rest of the stack inside an
+/// `http.request` span carrying it (so every log line below has it), echoes it
+/// on the response and writes one access log line per request.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = incoming_request_id(&req).unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let route = req.match_pattern().unwrap_or_default();
+    let span = info_span!(
+        "http.request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+        route = %route,
+        status = tracing::field::Empty,
+    );
+
+    let started = Instant::now();
+    let mut res = match next.call(req).instrument(span.clone()).await {
+        Ok(res) => res,
+        Err(e) => {
+            span.in_scope(|| error!(error = %e, "request failed"));
+            return Err(e);
+        }
+    };
+
+    span.record("status", res.status().as_u16());
+    span.in_scope(|| {
+        info!(
+            status = res.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "request completed"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(res)
+}
@@ -0,0 +1,786 @@
+// migrations.rs
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::IndexOptions,
+    Client, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::uuid_bson;
+use crate::models::business::{owners, Business};
+use crate::models::category::{bundled_taxonomy, Category};
+use crate::models::user::User;
+use crate::purchases;
+use crate::ratings;
+use crate::search::{mongo::text_index_keys, SearchKind};
+use crate::taxonomy;
+
+type MigrationFn = for<'a> fn(&'a Database) -> BoxFuture<'a, mongodb::error::Result<()>>;
+
+pub struct Migration {
+    pub id: &'static str,
+    pub run: MigrationFn,
+}
+
+/// Applied in order; ids are recorded in the `migrations` collection and must never change.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        id: "0001_lookup_indexes",
+        run: lookup_indexes,
+    },
+    Migration {
+        id: "0002_rate_limit_expiry",
+        run: rate_limit_expiry,
+    },
+    Migration {
+        id: "0003_search_text_indexes",
+        run: search_text_indexes,
+    },
+    Migration {
+        id: "0004_profile_views",
+        run: profile_views,
+    },
+    Migration {
+        id: "0005_business_locations",
+        run: business_locations,
+    },
+    Migration {
+        id: "0006_directory_indexes",
+        run: directory_indexes,
+    },
+    Migration {
+        id: "0007_sessions",
+        run: sessions,
+    },
+    Migration {
+        id: "0008_industry_taxonomy",
+        run: industry_taxonomy,
+    },
+    Migration {
+        id: "0009_business_members",
+        run: business_members,
+    },
+    Migration {
+        id: "0010_verification_requests",
+        run: verification_requests,
+    },
+    Migration {
+        id: "0011_media",
+        run: media,
+    },
+    Migration {
+        id: "0012_catalogue",
+        run: catalogue,
+    },
+    Migration {
+        id: "0013_analytics",
+        run: analytics,
+    },
+    Migration {
+        id: "0014_rating_summaries",
+        run: rating_summaries,
+    },
+    Migration {
+        id: "0015_one_review_per_business",
+        run: one_review_per_business,
+    },
+    Migration {
+        id: "0016_moderation",
+        run: moderation,
+    },
+    Migration {
+        id: "0017_review_votes",
+        run: review_votes,
+    },
+    Migration {
+        id: "0018_verified_purchases",
+        run: verified_purchases,
+    },
+    Migration {
+        id: "0019_admin_flag",
+        run: admin_flag,
+    },
+    Migration {
+        id: "0020_seller_completed_purchases",
+        run: seller_completed_purchases,
+    },
+    Migration {
+        id: "0021_unique_review_ids",
+        run: unique_review_ids,
+    },
+    Migration {
+        id: "0022_catalogue_purchases",
+        run: catalogue_purchases,
+    },
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedMigration {
+    id: String,
+    applied_at: String,
+}
+
+async fn applied_ids(db: &Database) -> mongodb::error::Result<Vec<String>> {
+    let mut cursor = db
+        .collection::<AppliedMigration>("migrations")
+        .find(doc! {})
+        .await?;
+    let mut ids = Vec::new();
+    while let Some(applied) = cursor.next().await {
+        ids.push(applied?.id);
+    }
+    Ok(ids)
+}
+
+/// Ids of migrations that have not been applied to this database yet.
+pub async fn pending(client: &Client) -> mongodb::error::Result<Vec<&'static str>> {
+    let applied = applied_ids(&client.database("cucura-ccdb")).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| migration.id)
+        .filter(|id| !applied.iter().any(|applied| applied == id))
+        .collect())
+}
+
+pub async fn run_pending(client: &Client) -> mongodb::error::Result<()> {
+    let db = client.database("cucura-ccdb");
+    let pending = pending(client).await?;
+    for migration in MIGRATIONS.iter().filter(|m| pending.contains(&m.id)) {
+        info!(migration = migration.id, "Applying migration");
+        (migration.run)(&db).await?;
+        db.collection::<AppliedMigration>("migrations")
+            .update_one(
+                doc! { "id": migration.id },
+                doc! { "$setOnInsert": { "id": migration.id, "applied_at": Utc::now().to_rfc3339() } },
+            )
+            .upsert(true)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn create_index(
+    db: &Database,
+    collection: &str,
+    keys: Document,
+    unique: bool,
+) -> mongodb::error::Result<()> {
+    let index = IndexModel::builder()
+        .keys(keys)
+        .options(IndexOptions::builder().unique(unique).build())
+        .build();
+    db.collection::<Document>(collection)
+        .create_index(index)
+        .await?;
+    Ok(())
+}
+
+fn lookup_indexes(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "migrations", doc! { "id": 1 }, true).await?;
+        create_index(db, "users", doc! { "user_id": 1 }, false).await?;
+        create_index(db, "users", doc! { "username": 1 }, false).await?;
+        create_index(db, "profiles", doc! { "profile_id": 1 }, false).await?;
+        create_index(db, "profiles", doc! { "user_id": 1 }, false).await?;
+        create_index(db, "businesses", doc! { "business_id": 1 }, false).await?;
+        create_index(db, "businesses", doc! { "user_ids": 1 }, false).await?;
+        create_index(db, "reviews", doc! { "business_review_id": 1 }, false).await?;
+        create_index(
+            db,
+            "reviews",
+            doc! { "business_id": 1, "created_at": -1 },
+            false,
+        )
+        .await?;
+        create_index(db, "payments", doc! { "payment_id": 1 }, false).await?;
+        create_index(db, "payments", doc! { "seller_id": 1 }, false).await?;
+        create_index(db, "payments", doc! { "purchaser_id": 1 }, false).await?;
+        create_index(db, "messages", doc! { "sender": 1, "receiver": 1 }, false).await?;
+        create_index(db, "notifications", doc! { "user_id": 1 }, false).await?;
+        create_index(db, "trainings", doc! { "training_id": 1 }, false).await?;
+        Ok(())
+    })
+}
+
+/// Buckets are deleted once they would have refilled completely.
+fn rate_limit_expiry(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let index = IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(0))
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("rate_limits")
+            .create_index(index)
+            .await?;
+        Ok(())
+    })
+}
+
+/// One weighted text index per searched collection (Mongo allows only one).
+fn search_text_indexes(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        for kind in SearchKind::ALL {
+            let (keys, weights) = text_index_keys(kind);
+            let index = IndexModel::builder()
+                .keys(keys)
+                .options(
+                    IndexOptions::builder()
+                        .name(format!("{}_search", kind.collection()))
+                        .weights(weights)
+                        .build(),
+                )
+                .build();
+            db.collection::<Document>(kind.collection())
+                .create_index(index)
+                .await?;
+        }
+        Ok(())
+    })
+}
+
+fn profile_views(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move { create_index(db, "profile_views", doc! { "profile_id": 1 }, true).await })
+}
+
+/// 2dsphere index for the geo queries, and locations geocoded from the city
+/// for businesses created before they had one.
+fn business_locations(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "businesses", doc! { "location": "2dsphere" }, false).await?;
+
+        let collection = db.collection::<Document>("businesses");
+        let mut cursor = collection
+            .find(doc! { "location": { "$exists": false }, "city": { "$type": "string" } })
+            .await?;
+        let mut located = 0;
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let Ok(id) = document.get_object_id("_id") else {
+                continue;
+            };
+            let Ok(mut business) = mongodb::bson::from_document::<Business>(document) else {
+                continue;
+            };
+            business.locate();
+            if let Some(location) = business.location {
+                collection
+                    .update_one(
+                        doc! { "_id": id },
+                        doc! { "$set": {
+                            "location": mongodb::bson::to_bson(&location)?,
+                            "region": &business.region,
+                        } },
+                    )
+                    .await?;
+                located += 1;
+            }
+        }
+        info!(located, "Geocoded existing businesses");
+        Ok(())
+    })
+}
+
+/// Directory filters; the facet counts scan whatever these leave.
+fn directory_indexes(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "businesses", doc! { "industry": 1 }, false).await?;
+        create_index(
+            db,
+            "businesses",
+            doc! { "country": 1, "region": 1, "city": 1 },
+            false,
+        )
+        .await?;
+        create_index(db, "businesses", doc! { "founded_on": 1 }, false).await?;
+        Ok(())
+    })
+}
+
+/// Token lookup, and deletion of sessions once they expire.
+fn sessions(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "sessions", doc! { "token_hash": 1 }, true).await?;
+        let index = IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(
+                IndexOptions::builder()
+                    .expire_after(Duration::from_secs(0))
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("sessions")
+            .create_index(index)
+            .await?;
+        Ok(())
+    })
+}
+
+/// Seeds the bundled taxonomy into an empty `categories` collection and
+/// maps each uncategorised business's free-text industry onto it. Texts
+/// with no confident match stay uncategorised for an admin to assign.
+fn industry_taxonomy(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "categories", doc! { "code": 1 }, true).await?;
+        create_index(db, "categories", doc! { "parent_code": 1 }, false).await?;
+        create_index(db, "businesses", doc! { "category_codes": 1 }, false).await?;
+
+        let categories = db.collection::<Category>("categories");
+        if categories.count_documents(doc! {}).await? == 0 {
+            categories.insert_many(bundled_taxonomy()).await?;
+        }
+        let taxonomy = taxonomy::load(db).await?;
+
+        let collection = db.collection::<Document>("businesses");
+        let mut cursor = collection
+            .find(doc! { "category_codes.0": { "$exists": false } })
+            .projection(doc! { "_id": 1, "industry": 1 })
+            .await?;
+        let (mut mapped, mut unmapped) = (0, 0);
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let Ok(id) = document.get_object_id("_id") else {
+                continue;
+            };
+            let category = document
+                .get_str("industry")
+                .ok()
+                .and_then(|industry| taxonomy::best_match(industry, &taxonomy));
+            let category_codes: Vec<&str> = category.map(|c| c.code.as_str()).into_iter().collect();
+            collection
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "category_codes": &category_codes } },
+                )
+                .await?;
+            if category.is_some() {
+                mapped += 1;
+            } else {
+                unmapped += 1;
+            }
+        }
+        info!(mapped, unmapped, "Mapped business industries to categories");
+        Ok(())
+    })
+}
+
+/// Every user already listed on a business becomes one of its owners.
+fn business_members(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "businesses", doc! { "members.user_id": 1 }, false).await?;
+        create_index(
+            db,
+            "business_invitations",
+            doc! { "invitation_id": 1 },
+            true,
+        )
+        .await?;
+        create_index(
+            db,
+            "business_invitations",
+            doc! { "email": 1, "status": 1 },
+            false,
+        )
+        .await?;
+        create_index(
+            db,
+            "business_invitations",
+            doc! { "business_id": 1, "status": 1 },
+            false,
+        )
+        .await?;
+
+        let collection = db.collection::<Document>("businesses");
+        let mut cursor = collection
+            .find(doc! { "members.0": { "$exists": false } })
+            .await?;
+        let mut migrated = 0;
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let Ok(id) = document.get_object_id("_id") else {
+                continue;
+            };
+            let Ok(business) = mongodb::bson::from_document::<Business>(document) else {
+                continue;
+            };
+            let members = owners(business.user_ids.as_deref().unwrap_or_default());
+            collection
+                .update_one(
+                    doc! { "_id": id },
+                    doc! { "$set": { "members": mongodb::bson::to_bson(&members)? } },
+                )
+                .await?;
+            migrated += 1;
+        }
+        info!(migrated, "Added owner memberships to existing businesses");
+        Ok(())
+    })
+}
+
+/// The review queue, per-business request history, and an explicit status on
+/// businesses registered before verification existed.
+fn verification_requests(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "verification_requests", doc! { "request_id": 1 }, true).await?;
+        create_index(
+            db,
+            "verification_requests",
+            doc! { "status": 1, "created_at": 1 },
+            false,
+        )
+        .await?;
+        create_index(
+            db,
+            "verification_requests",
+            doc! { "business_id": 1, "status": 1 },
+            false,
+        )
+        .await?;
+        create_index(db, "businesses", doc! { "verification_status": 1 }, false).await?;
+        db.collection::<Document>("businesses")
+            .update_many(
+                doc! { "verification_status": { "$exists": false } },
+                doc! { "$set": { "verification_status": "unverified" } },
+            )
+            .await?;
+        Ok(())
+    })
+}
+
+/// Upload lookup by id and owner, and the orphan sweep's age filter.
+fn media(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "media", doc! { "media_id": 1 }, true).await?;
+        create_index(db, "media", doc! { "owner_id": 1, "purpose": 1 }, false).await?;
+        create_index(db, "media", doc! { "purpose": 1, "created_at": 1 }, false).await?;
+        Ok(())
+    })
+}
+
+/// Catalogue browsing per business, and payments by the items they include.
+fn catalogue(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "catalogue_items", doc! { "item_id": 1 }, true).await?;
+        create_index(
+            db,
+            "catalogue_items",
+            doc! { "business_id": 1, "name": 1 },
+            false,
+        )
+        .await?;
+        create_index(
+            db,
+            "catalogue_items",
+            doc! { "business_id": 1, "categories": 1 },
+            false,
+        )
+        .await?;
+        create_index(db, "payments", doc! { "items.item_id": 1 }, false).await?;
+        create_index(db, "payments", doc! { "business_id": 1 }, false).await?;
+        Ok(())
+    })
+}
+
+/// Daily business views, training enrolments, and the per-business time
+/// ranges the analytics aggregate over (reviews already have one).
+fn analytics(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(
+            db,
+            "business_views",
+            doc! { "business_id": 1, "date": 1 },
+            true,
+        )
+        .await?;
+        create_index(
+            db,
+            "training_enrolments",
+            doc! { "training_id": 1, "user_id": 1 },
+            true,
+        )
+        .await?;
+        create_index(
+            db,
+            "training_enrolments",
+            doc! { "training_id": 1, "created_at": 1 },
+            false,
+        )
+        .await?;
+        create_index(db, "trainings", doc! { "trainer_id": 1 }, false).await?;
+        create_index(
+            db,
+            "messages",
+            doc! { "receiver": 1, "created_at": 1 },
+            false,
+        )
+        .await?;
+        create_index(
+            db,
+            "payments",
+            doc! { "seller_id": 1, "created_at": 1 },
+            false,
+        )
+        .await?;
+        Ok(())
+    })
+}
+
+/// Summaries for businesses reviewed before they were maintained, and the
+/// directory's `min_rating` filter, which now reads them.
+fn rating_summaries(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let summarized = ratings::recount(db).await?;
+        info!(summarized, "Summarized business ratings");
+        create_index(
+            db,
+            "businesses",
+            doc! { "rating_summary.average": 1 },
+            false,
+        )
+        .await
+    })
+}
+
+/// Reviewers' older duplicate reviews of a business become revisions of
+/// their newest one, which the unique index then keeps the only one.
+fn one_review_per_business(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let reviews = db.collection::<Document>("reviews");
+        let mut duplicates = reviews
+            .aggregate([
+                doc! { "$sort": { "created_at": 1 } },
+                doc! { "$group": {
+                    "_id": { "business_id": "$business_id", "reviewer_id": "$reviewer_id" },
+                    "reviews": { "$push": "$$ROOT" },
+                } },
+                doc! { "$match": { "reviews.1": { "$exists": true } } },
+            ])
+            .allow_disk_use(true)
+            .await?;
+        let field = |review: &Document, key: &str| review.get(key).cloned().unwrap_or(Bson::Null);
+        let mut folded = 0;
+        while let Some(group) = duplicates.next().await {
+            let group = group?;
+            let versions: Vec<&Document> = group
+                .get_array("reviews")
+                .map(|reviews| reviews.iter().filter_map(Bson::as_document).collect())
+                .unwrap_or_default();
+            let Some(kept) = versions.last() else {
+                continue;
+            };
+            for pair in versions.windows(2) {
+                let (older, newer) = (pair[0], pair[1]);
+                db.collection::<Document>("review_revisions")
+                    .insert_one(doc! {
+                        "revision_id": uuid_bson(Uuid::new_v4()),
+                        "business_review_id": field(kept, "business_review_id"),
+                        "rating": field(older, "rating"),
+                        "comment": field(older, "comment"),
+                        "review_link": field(older, "review_link"),
+                        "written_at": field(older, "updated_at"),
+                        "replaced_at": field(newer, "created_at"),
+                        "replaced_by": Bson::Null,
+                    })
+                    .await?;
+                reviews
+                    .delete_one(doc! { "_id": field(older, "_id") })
+                    .await?;
+                folded += 1;
+            }
+        }
+        info!(folded, "Folded duplicate reviews into revisions");
+        if folded > 0 {
+            ratings::recount(db).await?;
+        }
+        create_index(
+            db,
+            "reviews",
+            doc! { "business_id": 1, "reviewer_id": 1 },
+            true,
+        )
+        .await?;
+        create_index(
+            db,
+            "review_revisions",
+            doc! { "business_review_id": 1, "replaced_at": 1 },
+            false,
+        )
+        .await
+    })
+}
+
+fn moderation(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(db, "moderation_cases", doc! { "case_id": 1 }, true).await?;
+        // One open case per item; closed ones are kept alongside it.
+        let open_case = IndexModel::builder()
+            .keys(doc! { "target_kind": 1, "target_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "status": "open" })
+                    .build(),
+            )
+            .build();
+        db.collection::<Document>("moderation_cases")
+            .create_index(open_case)
+            .await?;
+        create_index(
+            db,
+            "moderation_cases",
+            doc! { "status": 1, "report_count": -1, "created_at": 1 },
+            false,
+        )
+        .await
+    })
+}
+
+fn review_votes(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(
+            db,
+            "review_votes",
+            doc! { "business_review_id": 1, "user_id": 1 },
+            true,
+        )
+        .await?;
+        create_index(
+            db,
+            "reviews",
+            doc! { "business_id": 1, "helpful_count": -1, "created_at": -1 },
+            false,
+        )
+        .await
+    })
+}
+
+fn verified_purchases(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        create_index(
+            db,
+            "payments",
+            doc! { "purchaser_id": 1, "business_id": 1 },
+            false,
+        )
+        .await?;
+        create_index(
+            db,
+            "reviews",
+            doc! { "business_id": 1, "verified_purchase": 1, "created_at": -1 },
+            false,
+        )
+        .await?;
+        let refreshed = purchases::refresh_all(db).await?;
+        info!(refreshed, "Checked reviews for verified purchases");
+        Ok(())
+    })
+}
+
+/// Admin access used to follow `user_type == "Admin"`, which clients could
+/// set themselves, so nobody is promoted here: the accounts are only logged,
+/// for an operator to check and grant through `ADMIN_USER_IDS`.
+fn admin_flag(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let mut candidates = db
+            .collection::<User>("users")
+            .find(doc! { "user_type": "Admin", "admin": { "$ne": true } })
+            .await?;
+        while let Some(candidate) = candidates.next().await {
+            let candidate = candidate?;
+            warn!(
+                user_id = %candidate.user_id,
+                username = %candidate.username,
+                "Account has user_type Admin but no admin access; add it to ADMIN_USER_IDS if it should"
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Purchases now need a payment the seller marked completed, which no
+/// existing payment has; badges earned under the old check are rechecked.
+fn seller_completed_purchases(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let reset = db
+            .collection::<Document>("reviews")
+            .update_many(
+                doc! { "verified_purchase": true },
+                doc! { "$set": { "verified_purchase": false } },
+            )
+            .await?;
+        let refreshed = purchases::refresh_all(db).await?;
+        info!(
+            reset = reset.modified_count,
+            refreshed, "Rechecked reviews for seller-completed purchases"
+        );
+        Ok(())
+    })
+}
+
+/// v1 clients chose their own review ids, so two reviews could share one.
+/// The oldest keeps it and the others get fresh ids before the index is
+/// made unique.
+fn unique_review_ids(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let reviews = db.collection::<Document>("reviews");
+        let mut shared = reviews
+            .aggregate([
+                doc! { "$sort": { "created_at": 1 } },
+                doc! { "$group": {
+                    "_id": "$business_review_id",
+                    "ids": { "$push": "$_id" },
+                } },
+                doc! { "$match": { "ids.1": { "$exists": true } } },
+            ])
+            .allow_disk_use(true)
+            .await?;
+        let mut renamed = 0;
+        while let Some(group) = shared.next().await {
+            let group = group?;
+            let ids = group.get_array("ids").cloned().unwrap_or_default();
+            for id in ids.into_iter().skip(1) {
+                reviews
+                    .update_one(
+                        doc! { "_id": id },
+                        doc! { "$set": { "business_review_id": uuid_bson(Uuid::new_v4()) } },
+                    )
+                    .await?;
+                renamed += 1;
+            }
+        }
+        info!(renamed, "Gave reviews sharing an id fresh ones");
+        reviews.drop_index("business_review_id_1").await?;
+        create_index(db, "reviews", doc! { "business_review_id": 1 }, true).await
+    })
+}
+
+/// Only catalogue orders count as purchases now. Payments written before
+/// the server recorded where `business_id` came from cannot be told apart
+/// from ones that named it themselves, so none of them count.
+fn catalogue_purchases(db: &Database) -> BoxFuture<'_, mongodb::error::Result<()>> {
+    Box::pin(async move {
+        let reset = db
+            .collection::<Document>("reviews")
+            .update_many(
+                doc! { "verified_purchase": true },
+                doc! { "$set": { "verified_purchase": false } },
+            )
+            .await?;
+        let refreshed = purchases::refresh_all(db).await?;
+        info!(
+            reset = reset.modified_count,
+            refreshed, "Rechecked reviews for catalogue purchases"
+        );
+        Ok(())
+    })
+}
@@ -1,17 +1,86 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::geo::{geocode, GeoPoint};
+use super::opening_hours::OpeningHours;
+
+/// What a member may do with a business, from most to least privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BusinessRole {
+    Owner,
+    Admin,
+    Editor,
+    Viewer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Membership {
+    pub user_id: Uuid,
+    pub role: BusinessRole,
+    pub joined_at: String,
+}
+
+/// Verification badge state; changed only through the verification workflow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationStatus {
+    #[default]
+    Unverified,
+    Pending,
+    Verified,
+    Rejected,
+}
+
+/// Aggregate of a business's review scores, maintained as reviews change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RatingSummary {
+    pub count: i64,
+    /// Sum of all scores.
+    pub total: i64,
+    /// Reviews per score, from 1 to 5.
+    pub histogram: [i64; 5],
+    /// `None` until the business is reviewed.
+    pub average: Option<f64>,
+    /// The average pulled toward a neutral prior while there are few
+    /// reviews; the one to rank by.
+    pub weighted_score: Option<f64>,
+}
+
+/// Memberships making each of `user_ids` an owner.
+pub fn owners(user_ids: &[Uuid]) -> Vec<Membership> {
+    let mut members: Vec<Membership> = Vec::new();
+    for user_id in user_ids {
+        if !members.iter().any(|member| member.user_id == *user_id) {
+            members.push(Membership {
+                user_id: *user_id,
+                role: BusinessRole::Owner,
+                joined_at: Utc::now().to_string(),
+            });
+        }
+    }
+    members
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Business {
     pub business_id: Uuid,
     pub user_ids: Option<Vec<Uuid>>, // This is the user_id of the user who created the business
+    /// Roles of the users in `user_ids`, which is kept as the plain list for
+    /// older clients and queries. Always holds at least one owner.
+    #[serde(default)]
+    pub members: Vec<Membership>,
     pub name: String,
     pub description: String,
     pub logo: Option<String>,
     pub pictures: Option<Vec<String>>,
     pub founder: String,
+    /// Free-text label shown in listings; filtering uses `category_codes`.
     pub industry: String,
+    /// Taxonomy categories, most relevant first.
+    #[serde(default)]
+    pub category_codes: Vec<String>,
     pub phone: String,
     pub address: Option<String>,
     pub city: Option<String>,
@@ -19,6 +88,21 @@ pub struct Business {
     pub country: String,
     pub website: Option<String>,
     pub contact_email: Option<String>,
+    pub founded_on: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    #[serde(default)]
+    pub opening_hours: Option<OpeningHours>,
+    #[serde(default)]
+    pub rating_summary: RatingSummary,
+    #[serde(default)]
+    pub verification_status: VerificationStatus,
+    #[serde(default)]
+    pub verified_at: Option<String>,
+    /// Set by moderation; hidden businesses are left out of the directory
+    /// and search.
+    #[serde(default)]
+    pub hidden: bool,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -35,6 +119,7 @@ impl Business {
     ) -> Business {
         Business {
             business_id: Uuid::new_v4(),
+            members: owners(user_ids.as_deref().unwrap_or_default()),
             user_ids,
             name,
             description,
@@ -42,6 +127,7 @@ impl Business {
             pictures: None,
             founder,
             industry,
+            category_codes: Vec::new(),
             phone,
             address: None,
             city: None,
@@ -49,8 +135,112 @@ impl Business {
             country,
             website: None,
             contact_email: None,
+            founded_on: None,
+            location: None,
+            opening_hours: None,
+            rating_summary: RatingSummary::default(),
+            verification_status: VerificationStatus::Unverified,
+            verified_at: None,
+            hidden: false,
             created_at: Utc::now().to_string(),
             updated_at: Utc::now().to_string(),
         }
     }
+
+    /// Sets `location` (and `region`, when missing) from the bundled city
+    /// dataset; `location` is `None` when the city is missing or unknown.
+    pub fn locate(&mut self) {
+        let city = self
+            .city
+            .as_deref()
+            .and_then(|city| geocode(city, Some(&self.country)));
+        self.location = city.map(|city| city.location);
+        if let (None, Some(city)) = (&self.region, city) {
+            self.region = Some(city.region.clone());
+        }
+    }
+
+    /// Whether the city or country differ from `previous`, so the stored
+    /// location no longer describes the address.
+    pub fn moved_from(&self, previous: &Business) -> bool {
+        self.city != previous.city || self.country != previous.country
+    }
+
+    /// Re-geocodes after a move. A city missing from the dataset keeps the
+    /// location the business already had; `true` when `location` changed.
+    pub fn relocate(&mut self) -> bool {
+        let previous = self.location.take();
+        self.locate();
+        if self.location.is_none() {
+            self.location = previous;
+        }
+        self.location != previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn business(city: Option<&str>, country: &str) -> Business {
+        let mut business = Business::new(
+            None,
+            "Sunrise Bakery".to_string(),
+            String::new(),
+            String::new(),
+            "Bakery".to_string(),
+            String::new(),
+            country.to_string(),
+        );
+        business.city = city.map(str::to_string);
+        business
+    }
+
+    #[test]
+    fn only_a_new_city_or_country_is_a_move() {
+        let before = business(Some("Nairobi"), "Kenya");
+        let mut after = business(Some("Nairobi"), "Kenya");
+        after.name = "Sunset Bakery".to_string();
+        after.address = Some("2 Moi Avenue".to_string());
+        assert!(!after.moved_from(&before));
+        assert!(business(Some("Mombasa"), "Kenya").moved_from(&before));
+        assert!(business(Some("Nairobi"), "KE").moved_from(&before));
+        assert!(business(None, "Kenya").moved_from(&before));
+    }
+
+    #[test]
+    fn relocate_geocodes_the_new_city() {
+        let mut business = business(Some("Mombasa"), "Kenya");
+        business.location = Some(GeoPoint::new(36.8172, -1.2864));
+        assert!(business.relocate());
+        assert_eq!(business.location, Some(GeoPoint::new(39.6682, -4.0435)));
+        assert_eq!(business.region.as_deref(), Some("Mombasa"));
+    }
+
+    #[test]
+    fn relocate_keeps_the_old_location_when_the_city_is_unknown() {
+        let nairobi = GeoPoint::new(36.8172, -1.2864);
+        let mut business = business(Some("Atlantis"), "Kenya");
+        business.location = Some(nairobi);
+        assert!(!business.relocate());
+        assert_eq!(business.location, Some(nairobi));
+
+        business.city = None;
+        assert!(!business.relocate());
+        assert_eq!(business.location, Some(nairobi));
+    }
+
+    #[test]
+    fn relocate_leaves_an_unlocated_business_unlocated_on_a_miss() {
+        let mut business = business(Some("Atlantis"), "Kenya");
+        assert!(!business.relocate());
+        assert_eq!(business.location, None);
+    }
+
+    #[test]
+    fn relocate_to_the_same_point_is_no_change() {
+        let mut business = business(Some("Paris"), "FR");
+        business.location = Some(GeoPoint::new(2.3522, 48.8566));
+        assert!(!business.relocate());
+    }
 }
@@ -4,6 +4,16 @@ use uuid::Uuid;
 
 use super::rating::Rating;
 
+/// The business's public reply to a review.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewResponse {
+    /// The member who last wrote it.
+    pub responder_id: Uuid,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BusinessReview {
     pub business_review_id: Uuid,
@@ -12,6 +22,21 @@ pub struct BusinessReview {
     pub rating: Rating,
     pub comment: Option<String>,
     pub review_link: Option<String>,
+    /// At most one per review.
+    #[serde(default)]
+    pub response: Option<ReviewResponse>,
+    /// Set by moderation; hidden reviews are left out of listings and
+    /// rating summaries.
+    #[serde(default)]
+    pub hidden: bool,
+    /// How many users found the review helpful or not; one vote each.
+    #[serde(default)]
+    pub helpful_count: i64,
+    #[serde(default)]
+    pub unhelpful_count: i64,
+    /// The reviewer has a completed payment to the business.
+    #[serde(default)]
+    pub verified_purchase: bool,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -31,6 +56,11 @@ impl BusinessReview {
             rating,
             comment,
             review_link,
+            response: None,
+            hidden: false,
+            helpful_count: 0,
+            unhelpful_count: 0,
+            verified_purchase: false,
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
         }
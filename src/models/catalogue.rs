@@ -0,0 +1,47 @@
+// models/catalogue.rs
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Product,
+    Service,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    OutOfStock,
+    /// Still listed so past payments can be traced, but no longer sold.
+    Discontinued,
+}
+
+/// An amount in the currency's minor unit (cents for USD, shillings for
+/// UGX), so prices add up exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Price {
+    pub amount: i64,
+    /// ISO 4217 code, upper case.
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogueItem {
+    pub item_id: Uuid,
+    pub business_id: Uuid,
+    pub kind: ItemKind,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: Price,
+    /// Image URLs, the first shown in listings.
+    #[serde(default)]
+    pub images: Vec<String>,
+    pub availability: Availability,
+    /// The business's own groupings, e.g. "Drinks" or "Repairs".
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
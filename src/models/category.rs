@@ -0,0 +1,59 @@
+// models/category.rs
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// A node of the industry taxonomy. A child's code extends its parent's
+/// (`A` > `A01`), so a prefix match on a code selects its whole subtree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    pub code: String,
+    pub name: String,
+    pub parent_code: Option<String>,
+    /// Extra words matched when mapping free-text industries.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Businesses in this category or below only take reviews from
+    /// customers with a completed payment.
+    #[serde(default)]
+    pub requires_verified_purchase: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Category {
+    pub fn new(code: String, name: String, parent_code: Option<String>) -> Category {
+        Category {
+            code,
+            name,
+            parent_code,
+            keywords: Vec::new(),
+            requires_verified_purchase: false,
+            created_at: Utc::now().to_string(),
+            updated_at: Utc::now().to_string(),
+        }
+    }
+}
+
+/// The ISIC-style starting taxonomy bundled in `data/industries.csv`.
+pub fn bundled_taxonomy() -> Vec<Category> {
+    include_str!("../../data/industries.csv")
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            // The name is last so it may contain commas.
+            let columns: Vec<&str> = line.splitn(4, ',').collect();
+            let mut category = Category::new(
+                columns[0].to_string(),
+                columns[3].to_string(),
+                Some(columns[1].to_string()).filter(|parent| !parent.is_empty()),
+            );
+            category.keywords = columns[2]
+                .split(';')
+                .filter(|keyword| !keyword.is_empty())
+                .map(str::to_string)
+                .collect();
+            category
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+// models/geo.rs
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+/// GeoJSON point, stored as-is so Mongo can index it with `2dsphere`.
+/// Coordinates are `[longitude, latitude]`, GeoJSON order.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    #[serde(rename = "type")]
+    pub kind: PointType,
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointType {
+    Point,
+}
+
+impl GeoPoint {
+    pub fn new(longitude: f64, latitude: f64) -> GeoPoint {
+        GeoPoint {
+            kind: PointType::Point,
+            coordinates: [longitude, latitude],
+        }
+    }
+}
+
+/// A row of the bundled `data/cities.csv`.
+#[derive(Debug, Clone)]
+pub struct City {
+    pub name: String,
+    pub region: String,
+    pub country: String,
+    pub country_code: String,
+    pub location: GeoPoint,
+}
+
+/// Bundled at compile time so geocoding needs no network access. Rows are
+/// ordered by prominence: for an ambiguous name without a country, the
+/// first row wins.
+static CITIES: LazyLock<Vec<City>> = LazyLock::new(|| {
+    include_str!("../../data/cities.csv")
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let columns: Vec<&str> = line.split(',').collect();
+            let coordinate = |index: usize| -> f64 {
+                columns[index]
+                    .parse()
+                    .unwrap_or_else(|_| panic!("data/cities.csv: bad coordinate in {:?}", line))
+            };
+            City {
+                name: columns[0].to_string(),
+                region: columns[1].to_string(),
+                country: columns[2].to_string(),
+                country_code: columns[3].to_string(),
+                location: GeoPoint::new(coordinate(5), coordinate(4)),
+            }
+        })
+        .collect()
+});
+
+pub fn cities() -> &'static [City] {
+    &CITIES
+}
+
+/// Offline lookup of a city, optionally narrowed by country name or ISO
+/// 3166 alpha-2 code. Case, accents and punctuation are ignored.
+pub fn geocode(city: &str, country: Option<&str>) -> Option<&'static City> {
+    let city = normalize(city);
+    let country = country.map(normalize).filter(|country| !country.is_empty());
+    let mut candidates = cities().iter().filter(|row| normalize(&row.name) == city);
+    match country {
+        Some(country) => candidates.find(|row| {
+            normalize(&row.country) == country || row.country_code.eq_ignore_ascii_case(&country)
+        }),
+        None => candidates.next(),
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c.to_lowercase().next().unwrap_or(c) {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => Some('a'),
+            'ç' => Some('c'),
+            'è' | 'é' | 'ê' | 'ë' => Some('e'),
+            'ì' | 'í' | 'î' | 'ï' => Some('i'),
+            'ñ' => Some('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => Some('o'),
+            'ù' | 'ú' | 'û' | 'ü' => Some('u'),
+            'ý' | 'ÿ' => Some('y'),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
@@ -7,6 +7,9 @@ pub struct Message {
     pub sender: Uuid,
     pub receiver: Uuid,
     pub content: String,
+    /// Set by moderation; hidden messages are left out of conversations.
+    #[serde(default)]
+    pub hidden: bool,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -18,6 +21,7 @@ impl Message {
             sender,
             receiver,
             content,
+            hidden: false,
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
         }
@@ -1,7 +1,11 @@
 pub mod business;
 pub mod business_review;
+pub mod catalogue;
+pub mod category;
+pub mod geo;
 pub mod message;
 pub mod notification;
+pub mod opening_hours;
 pub mod payment;
 pub mod payment_type;
 pub mod profile;
@@ -8,6 +8,11 @@ pub enum NotificationType {
     SomeoneSentMessage,
     SomeoneLikedPost,
     SomeoneViewedProfile,
+    SomeoneInvitedToBusiness,
+    SomeoneDisputedBusiness,
+    SomeoneReviewedVerification,
+    SomeoneRespondedToReview,
+    ModeratorWarnedYou,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
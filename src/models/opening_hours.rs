@@ -0,0 +1,51 @@
+// models/opening_hours.rs
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Day {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Local wall-clock times as `HH:MM`, which compare correctly as strings.
+/// A period that closes at or before it opens runs past midnight; `24:00`
+/// closes at the end of the day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub opens: String,
+    pub closes: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpeningPeriod {
+    pub day: Day,
+    #[serde(flatten)]
+    pub hours: TimeRange,
+}
+
+/// A date with other hours than usual, such as a public holiday.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpecialDay {
+    pub date: NaiveDate,
+    /// Replaces that weekday's periods; empty when closed all day.
+    #[serde(default)]
+    pub periods: Vec<TimeRange>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpeningHours {
+    /// IANA name, e.g. `Africa/Nairobi`; times are local to it.
+    pub timezone: String,
+    /// Days without periods are closed; a day may have several.
+    pub weekly: Vec<OpeningPeriod>,
+    #[serde(default)]
+    pub special_days: Vec<SpecialDay>,
+}
@@ -2,7 +2,19 @@ use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::catalogue::Price;
 use super::payment_type::PaymentType;
+
+/// A catalogue item as it was when paid for; later edits to the item do not
+/// change it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentItem {
+    pub item_id: Uuid,
+    pub name: String,
+    pub quantity: u32,
+    pub unit_price: Price,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Payment {
     pub payment_id: Uuid,
@@ -13,6 +25,21 @@ pub struct Payment {
     pub amount: f64,
     pub currency: String,
     pub status: String,
+    /// The business whose catalogue `items` come from.
+    #[serde(default)]
+    pub business_id: Option<Uuid>,
+    #[serde(default)]
+    pub items: Vec<PaymentItem>,
+    /// Whether `business_id` and `items` were set by the server from
+    /// pricing an order against the catalogue. Only such payments count
+    /// as purchases.
+    #[serde(default)]
+    pub from_catalogue: bool,
+    /// Who marked the payment completed: a member of the business for a
+    /// catalogue order, otherwise the seller. Set by the server; only
+    /// payments with one count as purchases.
+    #[serde(default)]
+    pub completed_by: Option<Uuid>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -36,6 +63,10 @@ impl Payment {
             amount,
             currency,
             status,
+            business_id: None,
+            items: Vec::new(),
+            from_catalogue: false,
+            completed_by: None,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
         }
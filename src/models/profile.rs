@@ -12,6 +12,9 @@ pub struct Profile {
     pub profile_type: Option<String>,
     pub business: Option<String>,
     pub interests: Option<Vec<String>>,
+    /// URL of the uploaded avatar image.
+    #[serde(default)]
+    pub avatar: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -27,6 +30,7 @@ impl Profile {
             profile_type: None,
             business: None,
             interests: None,
+            avatar: None,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
         }
@@ -50,6 +54,7 @@ impl Profile {
             profile_type: Some(profile_type),
             business: Some(business),
             interests: Some(interests),
+            avatar: None,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
         }
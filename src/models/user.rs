@@ -8,6 +8,10 @@ pub struct User {
     pub email: String,
     pub password: String,
     pub user_type: String,
+    /// Grants the admin endpoints. Never read from a request body; only an
+    /// admin can change it.
+    #[serde(default)]
+    pub admin: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -20,6 +24,7 @@ impl User {
             email,
             password,
             user_type,
+            admin: false,
             created_at: Utc::now().naive_utc(),
             updated_at: Utc::now().naive_utc(),
         }
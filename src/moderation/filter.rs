@@ -0,0 +1,274 @@
+// moderation/filter.rs
+//! Checks run on user-written text before it is stored. Handlers take the
+//! filter as `web::Data<dyn ContentFilter>`, so checks can be added or
+//! swapped without touching them.
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+/// Link-only comments shorter than this many words are held as spam.
+const MIN_WORDS_WITH_LINK: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    /// Stored, but hidden until a moderator looks at it; the note says why.
+    Hold(String),
+    /// Refused outright; the message is shown to the writer.
+    Reject(String),
+}
+
+pub trait ContentFilter: Send + Sync {
+    fn check(&self, text: &str) -> Verdict;
+}
+
+/// Refuses text containing any listed word. Words match whole, after
+/// undoing the usual digit and symbol substitutions.
+pub struct WordList {
+    words: HashSet<String>,
+}
+
+impl WordList {
+    /// One word per line; blank lines and lines starting with `#` are
+    /// skipped.
+    pub fn parse(list: &str) -> WordList {
+        WordList {
+            words: list
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_lowercase)
+                .collect(),
+        }
+    }
+
+    /// The list at `MODERATION_WORD_LIST`, or the one bundled in
+    /// `data/profanity.txt`.
+    pub fn from_env() -> WordList {
+        if let Ok(path) = env::var("MODERATION_WORD_LIST") {
+            match fs::read_to_string(&path) {
+                Ok(list) => return WordList::parse(&list),
+                Err(e) => {
+                    warn!(error = %e, path = %path, "Failed to read word list; using the bundled one")
+                }
+            }
+        }
+        WordList::parse(include_str!("../../data/profanity.txt"))
+    }
+}
+
+fn unmask(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+impl ContentFilter for WordList {
+    fn check(&self, text: &str) -> Verdict {
+        let text: String = text.to_lowercase().chars().map(unmask).collect();
+        if text
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| self.words.contains(word))
+        {
+            Verdict::Reject("Comment contains language that is not allowed".to_string())
+        } else {
+            Verdict::Allow
+        }
+    }
+}
+
+/// Holds text that looks like link spam: more than `max_links` links, the
+/// same link twice, or a link with hardly any text around it.
+pub struct LinkSpam {
+    pub max_links: usize,
+}
+
+impl LinkSpam {
+    /// `MODERATION_MAX_LINKS`, 2 by default.
+    pub fn from_env() -> LinkSpam {
+        LinkSpam {
+            max_links: env::var("MODERATION_MAX_LINKS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(2),
+        }
+    }
+}
+
+fn is_link(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.starts_with("http://") || word.starts_with("https://") || word.starts_with("www.")
+}
+
+impl ContentFilter for LinkSpam {
+    fn check(&self, text: &str) -> Verdict {
+        let (links, words): (Vec<&str>, Vec<&str>) = text
+            .split_whitespace()
+            .partition(|word| is_link(word.trim_start_matches(|c: char| !c.is_alphanumeric())));
+        if links.is_empty() {
+            return Verdict::Allow;
+        }
+        let distinct: HashSet<String> = links
+            .iter()
+            .map(|link| {
+                link.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .collect();
+        if links.len() > self.max_links {
+            Verdict::Hold(format!("{} links", links.len()))
+        } else if distinct.len() < links.len() {
+            Verdict::Hold("Repeated link".to_string())
+        } else if words.len() < MIN_WORDS_WITH_LINK {
+            Verdict::Hold("Link with little text".to_string())
+        } else {
+            Verdict::Allow
+        }
+    }
+}
+
+/// Runs every filter; a rejection wins over a hold.
+pub struct Filters(pub Vec<Box<dyn ContentFilter>>);
+
+impl ContentFilter for Filters {
+    fn check(&self, text: &str) -> Verdict {
+        let mut verdict = Verdict::Allow;
+        for filter in &self.0 {
+            match filter.check(text) {
+                Verdict::Allow => (),
+                Verdict::Reject(message) => return Verdict::Reject(message),
+                hold => {
+                    if verdict == Verdict::Allow {
+                        verdict = hold;
+                    }
+                }
+            }
+        }
+        verdict
+    }
+}
+
+/// The word list and the link spam heuristics; `MODERATION_FILTERS=off`
+/// lets everything through, e.g. for load tests.
+pub fn from_env() -> Arc<dyn ContentFilter> {
+    if env::var("MODERATION_FILTERS").as_deref() == Ok("off") {
+        info!("Content filters are off");
+        return Arc::new(Filters(Vec::new()));
+    }
+    Arc::new(Filters(vec![
+        Box::new(WordList::from_env()),
+        Box::new(LinkSpam::from_env()),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words() -> WordList {
+        WordList::parse("# Bundled list\n\nscam\n  Toast  \n#spam\n")
+    }
+
+    fn links() -> LinkSpam {
+        LinkSpam { max_links: 2 }
+    }
+
+    /// Holds everything, to show a rejection still wins.
+    struct HoldAll;
+
+    impl ContentFilter for HoldAll {
+        fn check(&self, _text: &str) -> Verdict {
+            Verdict::Hold("Held".to_string())
+        }
+    }
+
+    fn rejected(verdict: Verdict) -> bool {
+        matches!(verdict, Verdict::Reject(_))
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let list = words();
+        assert_eq!(list.words.len(), 2);
+        assert!(list.words.contains("scam"));
+        assert!(list.words.contains("toast"));
+        assert!(!list.words.contains("#spam"));
+        assert_eq!(list.check("Not spam at all"), Verdict::Allow);
+    }
+
+    #[test]
+    fn listed_words_are_rejected_whole_and_in_any_case() {
+        let list = words();
+        assert!(rejected(list.check("What a SCAM.")));
+        assert!(rejected(list.check("burnt toast, again")));
+        assert_eq!(list.check("Scampi and toaster waffles"), Verdict::Allow);
+        assert_eq!(list.check(""), Verdict::Allow);
+    }
+
+    #[test]
+    fn digit_and_symbol_substitutions_are_unmasked() {
+        let list = words();
+        assert!(rejected(list.check("total $c4m")));
+        assert!(rejected(list.check("5c@m")));
+        assert!(rejected(list.check("t04$7 for breakfast")));
+        assert!(rejected(list.check("T0A5T")));
+        assert_eq!(list.check("5c@mpi"), Verdict::Allow);
+    }
+
+    #[test]
+    fn text_without_links_is_allowed() {
+        assert_eq!(links().check("Lovely staff"), Verdict::Allow);
+    }
+
+    #[test]
+    fn too_many_links_are_held() {
+        let verdict = links().check(
+            "See https://a.example and https://b.example and www.c.example for the menus we liked",
+        );
+        assert_eq!(verdict, Verdict::Hold("3 links".to_string()));
+    }
+
+    #[test]
+    fn a_repeated_link_is_held() {
+        let verdict = links().check(
+            "Order at https://shop.example today, honestly: (HTTPS://shop.example) is great",
+        );
+        assert_eq!(verdict, Verdict::Hold("Repeated link".to_string()));
+    }
+
+    #[test]
+    fn a_link_with_little_text_is_held() {
+        assert_eq!(
+            links().check("Cheap deals https://deals.example"),
+            Verdict::Hold("Link with little text".to_string())
+        );
+        assert_eq!(
+            links().check("Their menu is at www.menu.example if you want a look"),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn a_rejection_wins_over_a_hold() {
+        let filters = Filters(vec![Box::new(HoldAll), Box::new(words())]);
+        assert!(rejected(filters.check("scam")));
+        assert_eq!(filters.check("fine"), Verdict::Hold("Held".to_string()));
+    }
+
+    #[test]
+    fn no_filters_allow_everything() {
+        assert_eq!(
+            Filters(Vec::new()).check("scam https://a.example"),
+            Verdict::Allow
+        );
+    }
+}
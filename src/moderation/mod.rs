@@ -0,0 +1,273 @@
+// moderation/mod.rs
+//! Reports on reviews, messages and businesses, gathered into one open
+//! [`ModerationCase`] per reported item for admins to act on. An item is
+//! hidden as soon as `MODERATION_AUTO_HIDE_REPORTS` people (3 by default)
+//! have reported it, or when a [`filter`] holds it on submission, and stays
+//! hidden until a moderator dismisses the case.
+use std::env;
+
+use chrono::{NaiveDateTime, Utc};
+use mongodb::{
+    bson::{self, doc, Document},
+    options::ReturnDocument,
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{is_duplicate_key, uuid_bson};
+use crate::models::business_review::BusinessReview;
+use crate::ratings;
+
+pub mod filter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    Review,
+    Message,
+    Business,
+}
+
+impl TargetKind {
+    pub fn collection(self) -> &'static str {
+        match self {
+            TargetKind::Review => "reviews",
+            TargetKind::Message => "messages",
+            TargetKind::Business => "businesses",
+        }
+    }
+
+    pub fn id_field(self) -> &'static str {
+        match self {
+            TargetKind::Review => "business_review_id",
+            TargetKind::Message => "message_id",
+            TargetKind::Business => "business_id",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TargetKind::Review => "review",
+            TargetKind::Message => "message",
+            TargetKind::Business => "business",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Spam,
+    Offensive,
+    Harassment,
+    Misleading,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStatus {
+    Open,
+    /// Hidden, removed or warned about.
+    Actioned,
+    Dismissed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Hide,
+    Remove,
+    Dismiss,
+    /// Notifies the item's authors; the item itself is left as it is.
+    Warn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// `None` for reports filed by a content filter.
+    pub reporter_id: Option<Uuid>,
+    pub reason: ReportReason,
+    pub note: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionTaken {
+    pub action: Action,
+    pub moderator_id: Uuid,
+    pub note: Option<String>,
+    pub taken_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationCase {
+    pub case_id: Uuid,
+    pub target_kind: TargetKind,
+    pub target_id: Uuid,
+    /// Who a warning goes to: the reviewer, the sender, or the owners.
+    pub author_ids: Vec<Uuid>,
+    pub status: CaseStatus,
+    /// Every reason given so far, for filtering the queue.
+    pub reasons: Vec<ReportReason>,
+    pub report_count: i64,
+    pub reports: Vec<Report>,
+    /// Whether the case hid the item rather than a moderator, so a
+    /// dismissal knows to show it again.
+    pub auto_hidden: bool,
+    pub actions: Vec<ActionTaken>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+pub fn cases(client: &Client) -> Collection<ModerationCase> {
+    client
+        .database("cucura-ccdb")
+        .collection("moderation_cases")
+}
+
+fn auto_hide_reports() -> i64 {
+    env::var("MODERATION_AUTO_HIDE_REPORTS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(3)
+}
+
+/// Adds `report` to the item's open case, opening one if needed, and hides
+/// the item when `hide` is set or enough people have reported it. `None`
+/// when the reporter already reported it in the open case.
+pub async fn file_report(
+    client: &Client,
+    target_kind: TargetKind,
+    target_id: Uuid,
+    author_ids: &[Uuid],
+    report: Report,
+    hide: bool,
+) -> mongodb::error::Result<Option<ModerationCase>> {
+    let mut filter = doc! {
+        "target_kind": bson::to_bson(&target_kind)?,
+        "target_id": uuid_bson(target_id),
+        "status": "open",
+    };
+    if let Some(reporter_id) = report.reporter_id {
+        filter.insert(
+            "reports.reporter_id",
+            doc! { "$ne": uuid_bson(reporter_id) },
+        );
+    }
+    let now = bson::to_bson(&Utc::now().naive_utc())?;
+    let update = doc! {
+        "$push": { "reports": bson::to_bson(&report)? },
+        "$addToSet": { "reasons": bson::to_bson(&report.reason)? },
+        "$inc": { "report_count": 1 },
+        "$set": { "updated_at": now.clone() },
+        "$setOnInsert": {
+            "case_id": uuid_bson(Uuid::new_v4()),
+            "author_ids": author_ids.iter().copied().map(uuid_bson).collect::<Vec<_>>(),
+            "auto_hidden": false,
+            "actions": [],
+            "created_at": now,
+        },
+    };
+    // A reporter already in the open case matches nothing, so the upsert
+    // runs into the one-open-case index instead.
+    let case = match cases(client)
+        .find_one_and_update(filter, update)
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(case)) => case,
+        Ok(None) => return Ok(None),
+        Err(e) if is_duplicate_key(&e) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    if case.auto_hidden || !(hide || case.report_count >= auto_hide_reports()) {
+        return Ok(Some(case));
+    }
+    set_hidden(client, target_kind, target_id, true).await?;
+    let case = cases(client)
+        .find_one_and_update(
+            doc! { "case_id": uuid_bson(case.case_id) },
+            doc! { "$set": { "auto_hidden": true } },
+        )
+        .return_document(ReturnDocument::After)
+        .await?
+        .unwrap_or(case);
+    Ok(Some(case))
+}
+
+/// Hides or shows the item, keeping its business's rating summary in step
+/// for reviews. `false` when the item does not exist.
+pub async fn set_hidden(
+    client: &Client,
+    target_kind: TargetKind,
+    target_id: Uuid,
+    hidden: bool,
+) -> mongodb::error::Result<bool> {
+    let db = client.database("cucura-ccdb");
+    let filter = doc! { target_kind.id_field(): uuid_bson(target_id) };
+    if target_kind != TargetKind::Review {
+        let result = db
+            .collection::<Document>(target_kind.collection())
+            .update_one(filter, doc! { "$set": { "hidden": hidden } })
+            .await?;
+        return Ok(result.matched_count > 0);
+    }
+
+    let reviews: Collection<BusinessReview> = db.collection("reviews");
+    let mut changing = filter.clone();
+    changing.insert("hidden", doc! { "$ne": hidden });
+    match reviews
+        .find_one_and_update(changing, doc! { "$set": { "hidden": hidden } })
+        .await?
+    {
+        Some(review) => {
+            let score = Some(review.rating.score);
+            if hidden {
+                ratings::record(client, review.business_id, score, None).await;
+            } else {
+                ratings::record(client, review.business_id, None, score).await;
+            }
+            Ok(true)
+        }
+        None => Ok(reviews.count_documents(filter).await? > 0),
+    }
+}
+
+/// Deletes the item, and a review's votes with it. `false` when it was
+/// already gone.
+pub async fn remove(
+    client: &Client,
+    target_kind: TargetKind,
+    target_id: Uuid,
+) -> mongodb::error::Result<bool> {
+    let db = client.database("cucura-ccdb");
+    let filter = doc! { target_kind.id_field(): uuid_bson(target_id) };
+    if target_kind != TargetKind::Review {
+        let result = db
+            .collection::<Document>(target_kind.collection())
+            .delete_one(filter)
+            .await?;
+        return Ok(result.deleted_count > 0);
+    }
+
+    match db
+        .collection::<BusinessReview>("reviews")
+        .find_one_and_delete(filter)
+        .await?
+    {
+        Some(review) => {
+            if !review.hidden {
+                ratings::record(client, review.business_id, Some(review.rating.score), None).await;
+            }
+            db.collection::<Document>("review_votes")
+                .delete_many(doc! { "business_review_id": uuid_bson(target_id) })
+                .await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
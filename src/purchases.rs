@@ -0,0 +1,147 @@
+// purchases.rs
+//! Verified purchases. A review is marked `verified_purchase` when its
+//! reviewer has a payment for an order from the business's catalogue that
+//! the seller marked completed. Payments made without the catalogue name
+//! their seller and business themselves, so they never count. The flag is
+//! set when the review is
+//! written and kept in step by [`refresh`] whenever a payment is added,
+//! changes status or is removed.
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    Database,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::uuid_bson;
+use crate::models::business::Business;
+
+/// Catalogue orders their seller marked completed. The status alone is
+/// not enough: older clients could set it themselves.
+fn completed() -> Document {
+    doc! {
+        "status": { "$regex": "^completed$", "$options": "i" },
+        "completed_by": { "$ne": null },
+        "from_catalogue": true,
+    }
+}
+
+/// Whether `purchaser_id` has a completed catalogue order from the business.
+pub async fn has_purchased(
+    db: &Database,
+    purchaser_id: Uuid,
+    business: &Business,
+) -> mongodb::error::Result<bool> {
+    let mut filter = completed();
+    filter.extend(doc! {
+        "purchaser_id": uuid_bson(purchaser_id),
+        "business_id": uuid_bson(business.business_id),
+    });
+    let found = db
+        .collection::<Document>("payments")
+        .count_documents(filter)
+        .limit(1)
+        .await?;
+    Ok(found > 0)
+}
+
+/// Whether one of the business's categories, or a category above them,
+/// only takes reviews with a verified purchase.
+pub async fn required_for(db: &Database, business: &Business) -> mongodb::error::Result<bool> {
+    // A child's code extends its parent's, so the prefixes of a code are
+    // its ancestors.
+    let codes: Vec<String> = business
+        .category_codes
+        .iter()
+        .flat_map(|code| {
+            code.char_indices()
+                .map(move |(start, c)| code[..start + c.len_utf8()].to_string())
+        })
+        .collect();
+    if codes.is_empty() {
+        return Ok(false);
+    }
+    let found = db
+        .collection::<Document>("categories")
+        .count_documents(doc! { "code": { "$in": codes }, "requires_verified_purchase": true })
+        .limit(1)
+        .await?;
+    Ok(found > 0)
+}
+
+/// Re-checks the purchaser's reviews of every business a payment from
+/// `purchaser_id` to `seller_id` (for `business_id`, when from the
+/// catalogue) could verify. Failures are logged: the payment itself is
+/// already written.
+pub async fn refresh(
+    db: &Database,
+    purchaser_id: Uuid,
+    seller_id: Uuid,
+    business_id: Option<Uuid>,
+) {
+    if let Err(e) = try_refresh(db, purchaser_id, seller_id, business_id).await {
+        error!(error = %e, purchaser_id = %purchaser_id, "Failed to refresh verified purchases");
+    }
+}
+
+async fn try_refresh(
+    db: &Database,
+    purchaser_id: Uuid,
+    seller_id: Uuid,
+    business_id: Option<Uuid>,
+) -> mongodb::error::Result<()> {
+    let filter = match business_id {
+        Some(business_id) => doc! { "business_id": uuid_bson(business_id) },
+        None => doc! { "members.user_id": uuid_bson(seller_id) },
+    };
+    let mut businesses = db.collection::<Business>("businesses").find(filter).await?;
+    while let Some(business) = businesses.next().await {
+        let business = business?;
+        let verified = has_purchased(db, purchaser_id, &business).await?;
+        db.collection::<Document>("reviews")
+            .update_many(
+                doc! {
+                    "business_id": uuid_bson(business.business_id),
+                    "reviewer_id": uuid_bson(purchaser_id),
+                },
+                doc! { "$set": { "verified_purchase": verified } },
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Refreshes every purchaser, seller and business with a completed
+/// payment, for reviews written before purchases were checked.
+pub async fn refresh_all(db: &Database) -> mongodb::error::Result<u64> {
+    let mut pairs = db
+        .collection::<Document>("payments")
+        .aggregate([
+            doc! { "$match": completed() },
+            doc! { "$group": { "_id": {
+                "purchaser_id": "$purchaser_id",
+                "seller_id": "$seller_id",
+                "business_id": "$business_id",
+            } } },
+        ])
+        .await?;
+    let mut refreshed = 0;
+    while let Some(pair) = pairs.next().await {
+        let pair = pair?;
+        let Ok(key) = pair.get_document("_id") else {
+            continue;
+        };
+        let uuid = |field: &str| match key.get(field) {
+            Some(Bson::Binary(binary)) => Uuid::from_slice(&binary.bytes).ok(),
+            _ => None,
+        };
+        let (Some(purchaser_id), Some(seller_id)) = (uuid("purchaser_id"), uuid("seller_id"))
+        else {
+            continue;
+        };
+        try_refresh(db, purchaser_id, seller_id, uuid("business_id")).await?;
+        refreshed += 1;
+    }
+    Ok(refreshed)
+}
@@ -0,0 +1,244 @@
+// ratings.rs
+//! Upkeep of [`RatingSummary`] on businesses. Review writes move it by the
+//! score they add or remove rather than re-reading every review, so each
+//! change to a review's score must go through [`record`].
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    Client, Database,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::uuid_bson;
+
+/// The weighted score treats every business as having `PRIOR_WEIGHT` extra
+/// reviews of `PRIOR_MEAN`, so a single 5 does not outrank a hundred 4s.
+const PRIOR_MEAN: f64 = 3.0;
+const PRIOR_WEIGHT: f64 = 5.0;
+
+/// Recomputes `average` and `weighted_score` from the counts.
+fn derived() -> Document {
+    let count = "$rating_summary.count";
+    let total = "$rating_summary.total";
+    doc! { "$set": {
+        "rating_summary.average": { "$cond": [
+            { "$gt": [count, 0] },
+            { "$divide": [total, count] },
+            Bson::Null,
+        ] },
+        "rating_summary.weighted_score": { "$cond": [
+            { "$gt": [count, 0] },
+            { "$divide": [
+                { "$add": [PRIOR_MEAN * PRIOR_WEIGHT, total] },
+                { "$add": [PRIOR_WEIGHT, count] },
+            ] },
+            Bson::Null,
+        ] },
+    } }
+}
+
+/// How a summary's count, total and histogram move when a review scored
+/// `removed` becomes one scored `added`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Delta {
+    count: i32,
+    total: i32,
+    histogram: [i32; 5],
+}
+
+/// `None` when the summary does not change. Scores outside 1 to 5, which
+/// only older clients could write, are not counted.
+fn delta(removed: Option<i32>, added: Option<i32>) -> Option<Delta> {
+    let counted = |score: &i32| (1..=5).contains(score);
+    let (removed, added) = (removed.filter(counted), added.filter(counted));
+    if removed == added {
+        return None;
+    }
+    let mut delta = Delta::default();
+    if let Some(score) = removed {
+        delta.count -= 1;
+        delta.total -= score;
+        delta.histogram[score as usize - 1] -= 1;
+    }
+    if let Some(score) = added {
+        delta.count += 1;
+        delta.total += score;
+        delta.histogram[score as usize - 1] += 1;
+    }
+    Some(delta)
+}
+
+/// Moves the business's summary from a review scored `removed` to one
+/// scored `added`; `None` for a review created or deleted. Failures are
+/// logged: the review itself is already written.
+pub async fn record(client: &Client, business_id: Uuid, removed: Option<i32>, added: Option<i32>) {
+    let Some(Delta {
+        count,
+        total,
+        histogram,
+    }) = delta(removed, added)
+    else {
+        return;
+    };
+
+    let summary = |field: &str| format!("$rating_summary.{}", field);
+    let update = vec![
+        doc! { "$set": {
+            "rating_summary.count": { "$add": [{ "$ifNull": [summary("count"), 0] }, count] },
+            "rating_summary.total": { "$add": [{ "$ifNull": [summary("total"), 0] }, total] },
+            "rating_summary.histogram": { "$map": {
+                "input": { "$range": [0, 5] },
+                "as": "score",
+                "in": { "$add": [
+                    { "$ifNull": [
+                        { "$arrayElemAt": [summary("histogram"), "$$score"] },
+                        0,
+                    ] },
+                    { "$arrayElemAt": [histogram.to_vec(), "$$score"] },
+                ] },
+            } },
+        } },
+        derived(),
+    ];
+    if let Err(e) = client
+        .database("cucura-ccdb")
+        .collection::<Document>("businesses")
+        .update_one(doc! { "business_id": uuid_bson(business_id) }, update)
+        .await
+    {
+        error!(error = %e, business_id = %business_id, "Failed to update rating summary");
+    }
+}
+
+/// Rebuilds every reviewed business's summary from its visible reviews.
+pub async fn recount(db: &Database) -> mongodb::error::Result<u64> {
+    let mut group = doc! {
+        "_id": "$business_id",
+        "count": { "$sum": 1 },
+        "total": { "$sum": "$rating.score" },
+    };
+    for score in 1..=5 {
+        group.insert(
+            format!("score_{}", score),
+            doc! { "$sum": { "$cond": [{ "$eq": ["$rating.score", score] }, 1, 0] } },
+        );
+    }
+    let mut summaries = db
+        .collection::<Document>("reviews")
+        .aggregate([
+            doc! { "$match": {
+                "rating.score": { "$gte": 1, "$lte": 5 },
+                "hidden": { "$ne": true },
+            } },
+            doc! { "$group": group },
+            doc! { "$project": {
+                "count": 1,
+                "total": 1,
+                "histogram": ["$score_1", "$score_2", "$score_3", "$score_4", "$score_5"],
+            } },
+        ])
+        .await?;
+    let mut updated = 0;
+    while let Some(summary) = summaries.next().await {
+        let mut summary = summary?;
+        let Some(business_id) = summary.remove("_id") else {
+            continue;
+        };
+        db.collection::<Document>("businesses")
+            .update_one(
+                doc! { "business_id": business_id },
+                vec![doc! { "$set": { "rating_summary": summary } }, derived()],
+            )
+            .await?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies each change in turn, as the stored summary would see them.
+    fn summary(changes: &[(Option<i32>, Option<i32>)]) -> Delta {
+        let mut summary = Delta::default();
+        for (removed, added) in changes {
+            if let Some(delta) = delta(*removed, *added) {
+                summary.count += delta.count;
+                summary.total += delta.total;
+                for (bucket, change) in summary.histogram.iter_mut().zip(delta.histogram) {
+                    *bucket += change;
+                }
+            }
+        }
+        summary
+    }
+
+    #[test]
+    fn a_new_review_adds_its_score() {
+        assert_eq!(
+            delta(None, Some(4)),
+            Some(Delta {
+                count: 1,
+                total: 4,
+                histogram: [0, 0, 0, 1, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn a_deleted_review_takes_its_score_away() {
+        assert_eq!(
+            delta(Some(1), None),
+            Some(Delta {
+                count: -1,
+                total: -1,
+                histogram: [-1, 0, 0, 0, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn an_edit_moves_the_score_between_buckets() {
+        assert_eq!(
+            delta(Some(2), Some(5)),
+            Some(Delta {
+                count: 0,
+                total: 3,
+                histogram: [0, -1, 0, 0, 1],
+            })
+        );
+        assert_eq!(delta(Some(3), Some(3)), None);
+        assert_eq!(delta(None, None), None);
+    }
+
+    #[test]
+    fn scores_outside_one_to_five_are_not_counted() {
+        assert_eq!(delta(None, Some(0)), None);
+        assert_eq!(delta(Some(9), Some(-1)), None);
+        assert_eq!(delta(Some(7), Some(5)), delta(None, Some(5)));
+        assert_eq!(delta(Some(5), Some(6)), delta(Some(5), None));
+    }
+
+    #[test]
+    fn changes_add_up_to_the_remaining_reviews() {
+        // Reviews of 5, 3 and 4; the 3 is edited to 1 and the 5 deleted.
+        let summary = summary(&[
+            (None, Some(5)),
+            (None, Some(3)),
+            (None, Some(4)),
+            (Some(3), Some(1)),
+            (Some(5), None),
+        ]);
+        assert_eq!(
+            summary,
+            Delta {
+                count: 2,
+                total: 5,
+                histogram: [1, 0, 0, 1, 0],
+            }
+        );
+        assert_eq!(summary.histogram.iter().sum::<i32>(), summary.count);
+    }
+}
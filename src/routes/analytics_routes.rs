@@ -0,0 +1,434 @@
+// routes/analytics_routes.rs
+//! Per-period activity for a business's members. Timestamps are bucketed by
+//! their UTC date: every collection stores them as strings that start with
+//! it (`YYYY-MM-DD`), so periods are cut with `$dateTrunc` on that prefix.
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    Client, Collection,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+use super::membership_routes::role_of;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::models::business::{Business, BusinessRole};
+
+const DEFAULT_DAYS: u64 = 30;
+const MAX_DAYS: i64 = 731;
+const MIN_YEAR: i32 = 2000;
+const MAX_YEAR: i32 = 9999;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
+}
+
+impl Interval {
+    fn unit(self) -> &'static str {
+        match self {
+            Interval::Day => "day",
+            Interval::Week => "week",
+            Interval::Month => "month",
+        }
+    }
+
+    /// The first day of the period holding `date`, as `$dateTrunc` has it.
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Day => date,
+            Interval::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Interval::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Interval::Day => start + Days::new(1),
+            Interval::Week => start + Days::new(7),
+            Interval::Month => start + Months::new(1),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    pub interval: Option<Interval>,
+    /// Defaults to 30 days before `to`.
+    pub from: Option<NaiveDate>,
+    /// Inclusive; defaults to today (UTC).
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewsPoint {
+    pub period: String,
+    pub views: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewsPoint {
+    pub period: String,
+    pub count: i64,
+    /// Of the reviews posted in the period.
+    pub average_rating: Option<f64>,
+    /// Of all reviews posted up to the end of the period.
+    pub cumulative_average: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaymentsPoint {
+    pub period: String,
+    pub status: String,
+    pub currency: String,
+    pub count: i64,
+    pub total: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessagesPoint {
+    pub period: String,
+    pub messages: i64,
+    /// Distinct non-members who wrote in the period.
+    pub prospects: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnrolmentsPoint {
+    pub period: String,
+    pub enrolments: i64,
+}
+
+/// Every period from `from` to `to` is listed, with zeros when there was no
+/// activity, except in `payments`, which has one entry per period, status
+/// and currency that had any.
+#[derive(Debug, Serialize)]
+pub struct Analytics {
+    pub business_id: Uuid,
+    pub interval: Interval,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub views: Vec<ViewsPoint>,
+    pub reviews: Vec<ReviewsPoint>,
+    pub payments: Vec<PaymentsPoint>,
+    pub messages: Vec<MessagesPoint>,
+    pub enrolments: Vec<EnrolmentsPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bucket {
+    #[serde(default)]
+    period: String,
+    count: i64,
+    #[serde(default)]
+    sum: f64,
+}
+
+pub fn analytics_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/analytics").route(web::get().to(get_analytics)),
+    );
+}
+
+fn collection(client: &Client, name: &str) -> Collection<Document> {
+    client.database("cucura-ccdb").collection(name)
+}
+
+/// The start of `field`'s period as `YYYY-MM-DD`.
+fn period(field: &str, interval: Interval) -> Document {
+    let mut trunc = doc! {
+        "date": { "$dateFromString": {
+            "dateString": { "$substrCP": [field, 0, 10] },
+            "format": "%Y-%m-%d",
+        } },
+        "unit": interval.unit(),
+    };
+    if interval == Interval::Week {
+        trunc.insert("startOfWeek", "monday");
+    }
+    doc! { "$dateToString": { "date": { "$dateTrunc": trunc }, "format": "%Y-%m-%d" } }
+}
+
+/// Matches timestamps on the days `from` to `to`.
+fn within(from: NaiveDate, to: NaiveDate) -> Document {
+    doc! { "$gte": from.to_string(), "$lt": (to + Days::new(1)).to_string() }
+}
+
+/// `count` and `sum` of `value` per period of `field`.
+fn buckets(field: &str, interval: Interval, value: Bson) -> Vec<Document> {
+    vec![
+        doc! { "$group": {
+            "_id": period(field, interval),
+            "count": { "$sum": 1 },
+            "sum": { "$sum": value },
+        } },
+        doc! { "$project": { "_id": 0, "period": "$_id", "count": 1, "sum": 1 } },
+    ]
+}
+
+async fn run<T: DeserializeOwned + Send + Sync>(
+    collection: Collection<Document>,
+    pipeline: Vec<Document>,
+) -> mongodb::error::Result<Vec<T>> {
+    let mut cursor = collection.aggregate(pipeline).with_type::<T>().await?;
+    let mut found = Vec::new();
+    while let Some(result) = cursor.next().await {
+        found.push(result?);
+    }
+    Ok(found)
+}
+
+/// Views, reviews, payments, prospect messages and training enrolments per
+/// day, week or month. Open to the business's members.
+pub async fn get_analytics(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    query: web::Query<AnalyticsQuery>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let interval = query.interval.unwrap_or_default();
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    if !(MIN_YEAR..=MAX_YEAR).contains(&to.year())
+        || query
+            .from
+            .is_some_and(|from| !(MIN_YEAR..=MAX_YEAR).contains(&from.year()))
+    {
+        return HttpResponse::BadRequest().json("Dates must be between the years 2000 and 9999");
+    }
+    let from = query.from.unwrap_or(to - Days::new(DEFAULT_DAYS));
+    if from > to {
+        return HttpResponse::BadRequest().json("from must not be after to");
+    }
+    if (to - from).num_days() > MAX_DAYS {
+        return HttpResponse::BadRequest().json("At most two years can be requested at once");
+    }
+
+    let business = match collection(&client, "businesses")
+        .clone_with_type::<Business>()
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => business,
+        Ok(None) => return HttpResponse::NotFound().json("Business not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to compute analytics");
+        }
+    };
+    if role_of(&business, user.user_id).is_none() && !user.is_admin() {
+        return HttpResponse::Forbidden().json("Only members can see a business's analytics");
+    }
+    let members: Vec<Bson> = business
+        .members
+        .iter()
+        .map(|member| uuid_bson(member.user_id))
+        .collect();
+    let owners: Vec<Bson> = business
+        .members
+        .iter()
+        .filter(|member| member.role == BusinessRole::Owner)
+        .map(|member| uuid_bson(member.user_id))
+        .collect();
+
+    let trainings = match collection(&client, "trainings")
+        .distinct("training_id", doc! { "trainer_id": { "$in": &members } })
+        .await
+    {
+        Ok(trainings) => trainings,
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to compute analytics");
+        }
+    };
+
+    let views = run::<Bucket>(collection(&client, "business_views"), {
+        let mut pipeline = vec![doc! { "$match": {
+            "business_id": uuid_bson(business_id),
+            "date": { "$gte": from.to_string(), "$lte": to.to_string() },
+        } }];
+        pipeline.extend(buckets("$date", interval, "$views".into()));
+        pipeline
+    });
+    let earlier_reviews = run::<Bucket>(
+        collection(&client, "reviews"),
+        vec![
+            doc! { "$match": {
+                "business_id": uuid_bson(business_id),
+                "created_at": { "$lt": from.to_string() },
+            } },
+            doc! { "$group": {
+                "_id": Bson::Null,
+                "count": { "$sum": 1 },
+                "sum": { "$sum": "$rating.score" },
+            } },
+            doc! { "$project": { "_id": 0, "count": 1, "sum": 1 } },
+        ],
+    );
+    let reviews = run::<Bucket>(collection(&client, "reviews"), {
+        let mut pipeline = vec![doc! { "$match": {
+            "business_id": uuid_bson(business_id),
+            "created_at": within(from, to),
+        } }];
+        pipeline.extend(buckets("$created_at", interval, "$rating.score".into()));
+        pipeline
+    });
+    // Payments made before the catalogue carry no business, so an owner's
+    // are counted as the business's.
+    let payments = run::<PaymentsPoint>(
+        collection(&client, "payments"),
+        vec![
+            doc! { "$match": {
+                "created_at": within(from, to),
+                "$or": [
+                    { "business_id": uuid_bson(business_id) },
+                    { "business_id": Bson::Null, "seller_id": { "$in": &owners } },
+                ],
+            } },
+            doc! { "$group": {
+                "_id": {
+                    "period": period("$created_at", interval),
+                    "status": "$status",
+                    "currency": "$currency",
+                },
+                "count": { "$sum": 1 },
+                "total": { "$sum": "$amount" },
+            } },
+            doc! { "$project": {
+                "_id": 0,
+                "period": "$_id.period",
+                "status": "$_id.status",
+                "currency": "$_id.currency",
+                "count": 1,
+                "total": 1,
+            } },
+            doc! { "$sort": { "period": 1, "status": 1, "currency": 1 } },
+        ],
+    );
+    let messages = run::<MessagesPoint>(
+        collection(&client, "messages"),
+        vec![
+            doc! { "$match": {
+                "receiver": { "$in": &members },
+                "sender": { "$nin": &members },
+                "created_at": within(from, to),
+            } },
+            doc! { "$group": {
+                "_id": period("$created_at", interval),
+                "messages": { "$sum": 1 },
+                "prospects": { "$addToSet": "$sender" },
+            } },
+            doc! { "$project": {
+                "_id": 0,
+                "period": "$_id",
+                "messages": 1,
+                "prospects": { "$size": "$prospects" },
+            } },
+        ],
+    );
+    let enrolments = run::<Bucket>(collection(&client, "training_enrolments"), {
+        let mut pipeline = vec![doc! { "$match": {
+            "training_id": { "$in": trainings },
+            "created_at": within(from, to),
+        } }];
+        pipeline.extend(buckets("$created_at", interval, 0.into()));
+        pipeline
+    });
+
+    let (views, earlier_reviews, reviews, payments, messages, enrolments) = match futures::try_join!(
+        views,
+        earlier_reviews,
+        reviews,
+        payments,
+        messages,
+        enrolments
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to compute analytics");
+        }
+    };
+
+    let mut periods = Vec::new();
+    let mut start = interval.start(from);
+    while start <= to {
+        periods.push(start.to_string());
+        start = interval.next(start);
+    }
+    let by_period = |buckets: Vec<Bucket>| -> HashMap<String, Bucket> {
+        buckets
+            .into_iter()
+            .map(|bucket| (bucket.period.clone(), bucket))
+            .collect()
+    };
+    let (views, reviews, enrolments) =
+        (by_period(views), by_period(reviews), by_period(enrolments));
+    let mut messages: HashMap<String, MessagesPoint> = messages
+        .into_iter()
+        .map(|point| (point.period.clone(), point))
+        .collect();
+    let (mut review_count, mut review_sum) = earlier_reviews
+        .first()
+        .map_or((0, 0.0), |earlier| (earlier.count, earlier.sum));
+    let average = |sum: f64, count: i64| (count > 0).then(|| sum / count as f64);
+
+    HttpResponse::Ok().json(Analytics {
+        business_id,
+        interval,
+        from,
+        to,
+        views: periods
+            .iter()
+            .map(|period| ViewsPoint {
+                period: period.clone(),
+                views: views.get(period).map_or(0, |bucket| bucket.sum as i64),
+            })
+            .collect(),
+        reviews: periods
+            .iter()
+            .map(|period| {
+                let (count, sum) = reviews
+                    .get(period)
+                    .map_or((0, 0.0), |bucket| (bucket.count, bucket.sum));
+                review_count += count;
+                review_sum += sum;
+                ReviewsPoint {
+                    period: period.clone(),
+                    count,
+                    average_rating: average(sum, count),
+                    cumulative_average: average(review_sum, review_count),
+                }
+            })
+            .collect(),
+        payments,
+        messages: periods
+            .iter()
+            .map(|period| {
+                messages.remove(period).unwrap_or(MessagesPoint {
+                    period: period.clone(),
+                    messages: 0,
+                    prospects: 0,
+                })
+            })
+            .collect(),
+        enrolments: periods
+            .iter()
+            .map(|period| EnrolmentsPoint {
+                period: period.clone(),
+                enrolments: enrolments.get(period).map_or(0, |bucket| bucket.count),
+            })
+            .collect(),
+    })
+}
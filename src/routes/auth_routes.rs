@@ -1,5 +1,5 @@
 // routes/auth_routes.rs
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Responder};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use futures::StreamExt;
 use models::user::User;
@@ -8,7 +8,10 @@ use mongodb::{
     Client, Collection,
 };
 use serde::{Deserialize, Serialize};
+use tracing::error;
 
+use crate::auth::{self, AuthenticatedUser};
+use crate::metrics;
 use crate::models;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,12 +27,27 @@ pub struct PasswordChangeRequest {
     pub new_password: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub token: String,
+    pub token_type: &'static str,
+    pub user_id: uuid::Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
 pub fn auth_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/auth/login").route(web::post().to(login_user)))
         .service(web::resource("/auth/set_password").route(web::post().to(set_password)))
         .service(web::resource("/auth/change_password").route(web::put().to(change_password)));
 }
 
+/// Token-based login: `POST /sessions` returns a bearer token for the
+/// `Authorization` header, `DELETE /sessions/current` revokes it.
+pub fn session_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/sessions").route(web::post().to(create_session)))
+        .service(web::resource("/sessions/current").route(web::delete().to(delete_session)));
+}
+
 pub async fn login_user(
     client: web::Data<Client>,
     login_request: web::Json<LoginRequest>,
@@ -39,7 +57,9 @@ pub async fn login_user(
 
     match collection.find_one(filter).await {
         Ok(Some(user)) => {
-            if verify(&login_request.password, &user.password).unwrap() {
+            if metrics::time_bcrypt("verify", || verify(&login_request.password, &user.password))
+                .unwrap()
+            {
                 HttpResponse::Ok().json("Login successful")
             } else {
                 HttpResponse::Unauthorized().json("Invalid username or password")
@@ -47,7 +67,7 @@ pub async fn login_user(
         }
         Ok(None) => HttpResponse::Unauthorized().json("Invalid username or password"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to login")
         }
     }
@@ -56,13 +76,14 @@ pub async fn login_user(
 pub async fn set_password(client: web::Data<Client>, user: web::Json<User>) -> impl Responder {
     let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
     let filter = doc! { "email": &user.email };
-    let hashed_password = hash(&user.password, DEFAULT_COST).unwrap();
+    let hashed_password =
+        metrics::time_bcrypt("hash", || hash(&user.password, DEFAULT_COST)).unwrap();
     let update = doc! { "$set": { "password": hashed_password } };
 
     match collection.update_one(filter, update).await {
         Ok(_) => HttpResponse::Ok().json("Password set successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to set password")
         }
     }
@@ -77,15 +98,21 @@ pub async fn change_password(
 
     match collection.find_one(filter.clone()).await {
         Ok(Some(user)) => {
-            if verify(&password_change_request.old_password, &user.password).unwrap() {
-                let hashed_password =
-                    hash(&password_change_request.new_password, DEFAULT_COST).unwrap();
+            if metrics::time_bcrypt("verify", || {
+                verify(&password_change_request.old_password, &user.password)
+            })
+            .unwrap()
+            {
+                let hashed_password = metrics::time_bcrypt("hash", || {
+                    hash(&password_change_request.new_password, DEFAULT_COST)
+                })
+                .unwrap();
                 let update = doc! { "$set": { "password": hashed_password } };
 
                 match collection.update_one(filter, update).await {
                     Ok(_) => HttpResponse::Ok().json("Password changed successfully"),
                     Err(e) => {
-                        eprintln!("Failed to update document: {}", e);
+                        error!(error = %e, "Failed to update document");
                         HttpResponse::InternalServerError().json("Failed to change password")
                     }
                 }
@@ -95,8 +122,63 @@ pub async fn change_password(
         }
         Ok(None) => HttpResponse::Unauthorized().json("Invalid username"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to change password")
         }
     }
 }
+
+pub async fn create_session(
+    client: web::Data<Client>,
+    login_request: web::Json<LoginRequest>,
+) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let user = match collection
+        .find_one(doc! { "username": &login_request.username })
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return HttpResponse::Unauthorized().json("Invalid username or password"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to login");
+        }
+    };
+    if !metrics::time_bcrypt("verify", || verify(&login_request.password, &user.password))
+        .unwrap_or(false)
+    {
+        return HttpResponse::Unauthorized().json("Invalid username or password");
+    }
+
+    match auth::start_session(&client, &user).await {
+        Ok((token, expires_at)) => HttpResponse::Created()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(SessionResponse {
+                token,
+                token_type: "Bearer",
+                user_id: user.user_id,
+                expires_at,
+            }),
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to login")
+        }
+    }
+}
+
+pub async fn delete_session(
+    client: web::Data<Client>,
+    req: HttpRequest,
+    user: AuthenticatedUser,
+) -> impl Responder {
+    let Some(token) = auth::bearer_token(req.headers()) else {
+        return HttpResponse::Unauthorized().json("Authentication required");
+    };
+    match auth::end_session(&client, &user, token).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to logout")
+        }
+    }
+}
@@ -1,10 +1,115 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::{NaiveDateTime, Utc};
 use futures::StreamExt;
 use mongodb::bson;
 use mongodb::bson::oid::ObjectId;
-use mongodb::{bson::doc, Client, Collection};
+use mongodb::options::ReturnDocument;
+use mongodb::{
+    bson::{doc, Document},
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use uuid::Uuid;
 
-use crate::models::business_review::BusinessReview;
+use super::membership_routes::role_of;
+use super::PaginationParams;
+use crate::auth::{Admin, AuthenticatedUser};
+use crate::db::{is_duplicate_key, uuid_bson};
+use crate::models::business::{Business, BusinessRole};
+use crate::models::business_review::{BusinessReview, ReviewResponse};
+use crate::models::notification::{Notification, NotificationType};
+use crate::models::rating::Rating;
+use crate::moderation::filter::{ContentFilter, Verdict};
+use crate::moderation::{self, Report, ReportReason, TargetKind};
+use crate::purchases;
+use crate::ratings;
+
+const MAX_RESPONSE_LENGTH: usize = 2000;
+
+/// The default order ranks a review by its net helpful votes plus one,
+/// divided by its age in weeks plus two raised to this power: well-voted
+/// reviews stay near the top for months, and new ones still surface.
+const RELEVANCE_GRAVITY: f64 = 1.5;
+
+#[derive(Debug, Deserialize)]
+pub struct NewReview {
+    /// The signed-in user; older clients still send it.
+    pub reviewer_id: Option<Uuid>,
+    pub score: i32,
+    pub description: Option<String>,
+    pub comment: Option<String>,
+    pub review_link: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewPatch {
+    pub score: Option<i32>,
+    pub description: Option<String>,
+    pub comment: Option<String>,
+    pub review_link: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewSort {
+    /// Helpfulness and recency combined.
+    #[default]
+    Relevant,
+    Helpful,
+    #[serde(alias = "recent")]
+    Newest,
+    Oldest,
+    Highest,
+    Lowest,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQuery {
+    pub sort: Option<ReviewSort>,
+    /// Only reviews with (or without) a verified purchase.
+    pub verified: Option<bool>,
+}
+
+/// A review as it read before an edit, kept for moderators.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewRevision {
+    pub revision_id: Uuid,
+    pub business_review_id: Uuid,
+    pub rating: Rating,
+    pub comment: Option<String>,
+    pub review_link: Option<String>,
+    /// When this version was written.
+    pub written_at: NaiveDateTime,
+    pub replaced_at: NaiveDateTime,
+    /// Who made the edit; `None` for duplicate reviews folded into one
+    /// when reviews became one per business.
+    pub replaced_by: Option<Uuid>,
+}
+
+/// A user's helpful or unhelpful vote on a review; one per user.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReviewVote {
+    pub business_review_id: Uuid,
+    pub user_id: Uuid,
+    pub helpful: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewVote {
+    pub helpful: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewResponse {
+    pub body: String,
+}
+
+fn valid_score(score: i32) -> bool {
+    (1..=5).contains(&score)
+}
 
 pub fn review_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/reviews/add").route(web::post().to(add_review)))
@@ -14,63 +119,393 @@ pub fn review_routes(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/reviews/{id}").route(web::get().to(find_review)));
 }
 
+pub fn review_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/reviews")
+            .route(web::get().to(list_business_reviews))
+            .route(web::post().to(create_business_review)),
+    )
+    .service(web::resource("/reviews").route(web::get().to(find_all_reviews)))
+    .service(
+        web::resource("/reviews/{business_review_id}")
+            .route(web::get().to(get_review))
+            .route(web::patch().to(patch_review))
+            .route(web::delete().to(remove_review)),
+    )
+    .service(
+        web::resource("/reviews/{business_review_id}/revisions")
+            .route(web::get().to(list_review_revisions)),
+    )
+    .service(
+        web::resource("/reviews/{business_review_id}/response")
+            .route(web::post().to(create_response))
+            .route(web::patch().to(edit_response))
+            .route(web::delete().to(remove_response)),
+    )
+    .service(
+        web::resource("/reviews/{business_review_id}/vote")
+            .route(web::get().to(get_vote))
+            .route(web::put().to(put_vote))
+            .route(web::delete().to(remove_vote)),
+    );
+}
+
+fn reviews(client: &Client) -> Collection<BusinessReview> {
+    client.database("cucura-ccdb").collection("reviews")
+}
+
+fn revisions(client: &Client) -> Collection<ReviewRevision> {
+    client
+        .database("cucura-ccdb")
+        .collection("review_revisions")
+}
+
+fn votes(client: &Client) -> Collection<ReviewVote> {
+    client.database("cucura-ccdb").collection("review_votes")
+}
+
+/// The business, if `reviewer_id` may review it: it exists, they are not
+/// one of its members, and they have not reviewed it before.
+async fn check_new_review(
+    client: &Client,
+    business_id: Uuid,
+    reviewer_id: Uuid,
+) -> Result<Business, HttpResponse> {
+    let businesses: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let business = match businesses
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) if role_of(&business, reviewer_id).is_some() => {
+            return Err(HttpResponse::Forbidden().json("Members cannot review their own business"))
+        }
+        Ok(Some(business)) => business,
+        Ok(None) => return Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return Err(HttpResponse::InternalServerError().json("Failed to add review"));
+        }
+    };
+
+    let filter =
+        doc! { "business_id": uuid_bson(business_id), "reviewer_id": uuid_bson(reviewer_id) };
+    match reviews(client).find_one(filter).await {
+        Ok(Some(existing)) => Err(already_reviewed(existing.business_review_id)),
+        Ok(None) => Ok(business),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to add review"))
+        }
+    }
+}
+
+/// Runs the comment through the content filter: refused comments fail with
+/// 400, held ones come back as the note to hold the review with.
+fn screen_comment(
+    filter: &dyn ContentFilter,
+    comment: Option<&str>,
+) -> Result<Option<String>, HttpResponse> {
+    match comment.map(|comment| filter.check(comment)) {
+        Some(Verdict::Reject(message)) => Err(HttpResponse::BadRequest().json(message)),
+        Some(Verdict::Hold(note)) => Ok(Some(note)),
+        _ => Ok(None),
+    }
+}
+
+/// Hides a review the content filter held and queues it for moderators.
+async fn hold_review(client: &Client, review: &BusinessReview, note: String) {
+    let report = Report {
+        reporter_id: None,
+        reason: ReportReason::Spam,
+        note: Some(note),
+        created_at: Utc::now().naive_utc(),
+    };
+    if let Err(e) = moderation::file_report(
+        client,
+        TargetKind::Review,
+        review.business_review_id,
+        &[review.reviewer_id],
+        report,
+        true,
+    )
+    .await
+    {
+        error!(error = %e, business_review_id = %review.business_review_id, "Failed to hold review");
+    }
+}
+
+/// Whether the reviewer has bought from the business; the review is
+/// refused if one of its categories requires a purchase and they have not.
+async fn check_purchase(
+    client: &Client,
+    business: &Business,
+    reviewer_id: Uuid,
+) -> Result<bool, HttpResponse> {
+    let db = client.database("cucura-ccdb");
+    let checked = async {
+        let verified = purchases::has_purchased(&db, reviewer_id, business).await?;
+        let required = !verified && purchases::required_for(&db, business).await?;
+        Ok::<_, mongodb::error::Error>((verified, required))
+    };
+    match checked.await {
+        Ok((_, true)) => Err(HttpResponse::Forbidden()
+            .json("Only customers with a completed purchase can review this business")),
+        Ok((verified, false)) => Ok(verified),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            Err(HttpResponse::InternalServerError().json("Failed to add review"))
+        }
+    }
+}
+
+fn already_reviewed(business_review_id: Uuid) -> HttpResponse {
+    HttpResponse::Conflict()
+        .insert_header((
+            header::LOCATION,
+            format!("/v2/reviews/{}", business_review_id),
+        ))
+        .json("You have already reviewed this business; edit that review instead")
+}
+
+/// Inserts a review that passed [`check_new_review`]. A concurrent second
+/// review of the same business is refused by the unique index. Reviews
+/// held by the content filter are inserted hidden and left out of the
+/// rating summary.
+async fn insert_review(client: &Client, review: &BusinessReview) -> Result<(), HttpResponse> {
+    match reviews(client).insert_one(review).await {
+        Ok(_) => {
+            if !review.hidden {
+                ratings::record(client, review.business_id, None, Some(review.rating.score)).await;
+            }
+            Ok(())
+        }
+        Err(e) if is_duplicate_key(&e) => {
+            let filter = doc! {
+                "business_id": uuid_bson(review.business_id),
+                "reviewer_id": uuid_bson(review.reviewer_id),
+            };
+            match reviews(client).find_one(filter).await {
+                Ok(Some(existing)) => Err(already_reviewed(existing.business_review_id)),
+                _ => Err(HttpResponse::Conflict().json("Review already exists")),
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            Err(HttpResponse::InternalServerError().json("Failed to add review"))
+        }
+    }
+}
+
+/// The review, if `user` wrote it or is an admin.
+async fn own_review(
+    client: &Client,
+    business_review_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<BusinessReview, HttpResponse> {
+    match reviews(client)
+        .find_one(doc! { "business_review_id": uuid_bson(business_review_id) })
+        .await
+    {
+        Ok(Some(review)) if review.reviewer_id == user.user_id || user.is_admin() => Ok(review),
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json("Not your review")),
+        Ok(None) => Err(HttpResponse::NotFound().json("review not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find review"))
+        }
+    }
+}
+
+/// Applies `set` to the review and keeps the version it replaces as a
+/// revision; `score` is the new score, when `set` changes it.
+async fn edit_review(
+    client: &Client,
+    business_review_id: Uuid,
+    user: &AuthenticatedUser,
+    set: Document,
+    score: Option<i32>,
+) -> Result<BusinessReview, HttpResponse> {
+    let filter = doc! { "business_review_id": uuid_bson(business_review_id) };
+    let before = match reviews(client)
+        .find_one_and_update(filter.clone(), doc! { "$set": set })
+        .return_document(ReturnDocument::Before)
+        .await
+    {
+        Ok(Some(before)) => before,
+        Ok(None) => return Err(HttpResponse::NotFound().json("review not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            return Err(HttpResponse::InternalServerError().json("Failed to update review"));
+        }
+    };
+    if let Some(score) = score.filter(|_| !before.hidden) {
+        ratings::record(
+            client,
+            before.business_id,
+            Some(before.rating.score),
+            Some(score),
+        )
+        .await;
+    }
+    let revision = ReviewRevision {
+        revision_id: Uuid::new_v4(),
+        business_review_id,
+        rating: before.rating,
+        comment: before.comment,
+        review_link: before.review_link,
+        written_at: before.updated_at,
+        replaced_at: Utc::now().naive_utc(),
+        replaced_by: Some(user.user_id),
+    };
+    if let Err(e) = revisions(client).insert_one(revision).await {
+        error!(error = %e, business_review_id = %business_review_id, "Failed to keep review revision");
+    }
+
+    match reviews(client).find_one(filter).await {
+        Ok(Some(review)) => Ok(review),
+        Ok(None) => Err(HttpResponse::NotFound().json("review not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find review"))
+        }
+    }
+}
+
+/// Deletes the review and its votes if `user` wrote it or is an admin. Its
+/// revisions are kept for moderators.
+async fn delete_own_review(
+    client: &Client,
+    business_review_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<(), HttpResponse> {
+    let review = own_review(client, business_review_id, user).await?;
+    match reviews(client)
+        .find_one_and_delete(doc! { "business_review_id": uuid_bson(business_review_id) })
+        .await
+    {
+        Ok(Some(review)) => {
+            if !review.hidden {
+                ratings::record(client, review.business_id, Some(review.rating.score), None).await;
+            }
+            if let Err(e) = votes(client)
+                .delete_many(doc! { "business_review_id": uuid_bson(business_review_id) })
+                .await
+            {
+                warn!(error = %e, business_review_id = %business_review_id, "Failed to delete review votes");
+            }
+            Ok(())
+        }
+        Ok(None) => Err(HttpResponse::NotFound().json("review not found")),
+        Err(e) => {
+            error!(error = %e, review = %review.business_review_id, "Failed to delete document");
+            Err(HttpResponse::InternalServerError().json("Failed to delete review"))
+        }
+    }
+}
+
 pub async fn add_review(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
+    filter: web::Data<dyn ContentFilter>,
     review: web::Json<BusinessReview>,
 ) -> impl Responder {
-    let collection = client.database("cucura-ccdb").collection("reviews");
-    let new_review = review.into_inner();
-    let insert_result = collection.insert_one(new_review).await;
+    let review = review.into_inner();
+    if review.reviewer_id != user.user_id {
+        return HttpResponse::Forbidden().json("Reviews can only be posted as yourself");
+    }
+    // Only what the reviewer writes is taken; the id and timestamps are
+    // the server's, like in v2.
+    let mut new_review = BusinessReview::new(
+        Uuid::new_v4(),
+        user.user_id,
+        review.business_id,
+        review.rating,
+        review.comment,
+        review.review_link,
+    );
+    if !valid_score(new_review.rating.score) {
+        return HttpResponse::BadRequest().json("Score must be between 1 and 5");
+    }
+    let held = match screen_comment(filter.get_ref(), new_review.comment.as_deref()) {
+        Ok(held) => held,
+        Err(response) => return response,
+    };
+    new_review.hidden = held.is_some();
+    let business = match check_new_review(&client, new_review.business_id, user.user_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    new_review.verified_purchase = match check_purchase(&client, &business, user.user_id).await {
+        Ok(verified) => verified,
+        Err(response) => return response,
+    };
 
-    match insert_result {
-        Ok(_) => HttpResponse::Ok().json("review added successfully"),
-        Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
-            HttpResponse::InternalServerError().json("Failed to add review")
+    match insert_review(&client, &new_review).await {
+        Ok(()) => {
+            if let Some(note) = held {
+                hold_review(&client, &new_review, note).await;
+            }
+            HttpResponse::Ok().json("review added successfully")
         }
+        Err(response) => response,
     }
 }
 
 pub async fn update_review(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
+    filter: web::Data<dyn ContentFilter>,
     business_review: web::Json<BusinessReview>,
 ) -> impl Responder {
-    let collection: Collection<BusinessReview> =
-        client.database("cucura-ccdb").collection("reviews");
-    let new_business_review = business_review.into_inner();
-    //check if review already exists
-    let business_review = bson::to_document(&new_business_review).unwrap();
-    let filter = doc! { "business_review": &business_review };
-    let review_exists = collection.find_one(filter.clone()).await.unwrap();
-    match review_exists {
-        Some(_) => (),
-        None => return HttpResponse::Ok().json("Error 10001 : Review does not exist"),
+    let business_review = business_review.into_inner();
+    if !valid_score(business_review.rating.score) {
+        return HttpResponse::BadRequest().json("Score must be between 1 and 5");
+    }
+    let held = match screen_comment(filter.get_ref(), business_review.comment.as_deref()) {
+        Ok(held) => held,
+        Err(response) => return response,
+    };
+    if let Err(response) = own_review(&client, business_review.business_review_id, &user).await {
+        return response;
     }
-    let update_result = collection.update_one(filter, business_review).await;
 
-    match update_result {
-        Ok(_) => HttpResponse::Ok().json("review updated successfully"),
-        Err(e) => {
-            eprintln!("Failed to update document: {}", e);
-            HttpResponse::InternalServerError().json("Failed to update review")
+    let set = doc! {
+        "rating.score": business_review.rating.score,
+        "rating.description": &business_review.rating.description,
+        "comment": &business_review.comment,
+        "review_link": &business_review.review_link,
+        "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+    };
+    match edit_review(
+        &client,
+        business_review.business_review_id,
+        &user,
+        set,
+        Some(business_review.rating.score),
+    )
+    .await
+    {
+        Ok(review) => {
+            if let Some(note) = held {
+                hold_review(&client, &review, note).await;
+            }
+            HttpResponse::Ok().json("review updated successfully")
         }
+        Err(response) => response,
     }
 }
 
-pub async fn delete_review(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
-    let collection: Collection<BusinessReview> =
-        client.database("cucura-ccdb").collection("reviews");
-    let business_review_id = path.into_inner();
-    let filter = doc! { "business_review_id": business_review_id };
-
-    let delete_result = collection.delete_one(filter).await;
-
-    match delete_result {
-        Ok(_) => HttpResponse::Ok().json("review deleted successfully"),
-        Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
-            HttpResponse::InternalServerError().json("Failed to delete review")
-        }
+pub async fn delete_review(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let Ok(business_review_id) = Uuid::parse_str(&path.into_inner()) else {
+        return HttpResponse::BadRequest().json("Invalid review ID format");
+    };
+    match delete_own_review(&client, business_review_id, &user).await {
+        Ok(()) => HttpResponse::Ok().json("review deleted successfully"),
+        Err(response) => response,
     }
 }
 
@@ -78,13 +513,13 @@ pub async fn find_review(client: web::Data<Client>, path: web::Path<String>) ->
     let collection: Collection<BusinessReview> =
         client.database("cucura-ccdb").collection("reviews");
     let business_review_id = path.into_inner();
-    let filter = doc! { "business_review_id": business_review_id };
+    let filter = doc! { "business_review_id": business_review_id, "hidden": { "$ne": true } };
 
     match collection.find_one(filter).await {
         Ok(Some(review)) => HttpResponse::Ok().json(review),
         Ok(None) => HttpResponse::NotFound().json("review not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find review")
         }
     }
@@ -93,7 +528,7 @@ pub async fn find_review(client: web::Data<Client>, path: web::Path<String>) ->
 pub async fn find_all_reviews(client: web::Data<Client>) -> impl Responder {
     let collection: Collection<BusinessReview> =
         client.database("cucura-ccdb").collection("reviews");
-    let filter = doc! {};
+    let filter = doc! { "hidden": { "$ne": true } };
 
     let mut cursor = collection.find(filter).await.unwrap();
 
@@ -101,9 +536,601 @@ pub async fn find_all_reviews(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(review) => reviews.push(review),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(reviews)
 }
+
+/// Ranking for [`ReviewSort::Relevant`]; see [`RELEVANCE_GRAVITY`]. Ages
+/// are counted in whole days from `created_at`.
+fn relevance() -> Document {
+    // Clamped: a review dated in the future would otherwise zero the
+    // divisor, failing the whole listing.
+    let age_days = doc! { "$max": [
+        { "$dateDiff": {
+            "startDate": { "$dateFromString": {
+                "dateString": { "$substrCP": ["$created_at", 0, 10] },
+                "onError": "$$NOW",
+            } },
+            "endDate": "$$NOW",
+            "unit": "day",
+        } },
+        0,
+    ] };
+    let net_helpful = doc! { "$subtract": [
+        { "$ifNull": ["$helpful_count", 0] },
+        { "$ifNull": ["$unhelpful_count", 0] },
+    ] };
+    doc! { "$divide": [
+        { "$add": [{ "$max": [net_helpful, 0] }, 1] },
+        { "$pow": [{ "$add": [{ "$divide": [age_days, 7] }, 2] }, RELEVANCE_GRAVITY] },
+    ] }
+}
+
+pub async fn list_business_reviews(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    query: web::Query<ReviewQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<BusinessReview> =
+        client.database("cucura-ccdb").collection("reviews");
+    let mut filter =
+        doc! { "business_id": uuid_bson(path.into_inner()), "hidden": { "$ne": true } };
+    match query.verified {
+        Some(true) => filter.insert("verified_purchase", true),
+        Some(false) => filter.insert("verified_purchase", doc! { "$ne": true }),
+        None => None,
+    };
+    let mut pipeline = vec![doc! { "$match": filter }];
+    let sort = match query.sort.unwrap_or_default() {
+        ReviewSort::Relevant => {
+            pipeline.push(doc! { "$set": { "relevance": relevance() } });
+            doc! { "relevance": -1, "created_at": -1 }
+        }
+        ReviewSort::Helpful => doc! { "helpful_count": -1, "unhelpful_count": 1, "created_at": -1 },
+        ReviewSort::Newest => doc! { "created_at": -1 },
+        ReviewSort::Oldest => doc! { "created_at": 1 },
+        ReviewSort::Highest => doc! { "rating.score": -1, "created_at": -1 },
+        ReviewSort::Lowest => doc! { "rating.score": 1, "created_at": -1 },
+    };
+    pipeline.extend([
+        doc! { "$sort": sort },
+        doc! { "$skip": pagination.skip() as i64 },
+        doc! { "$limit": pagination.limit() },
+        doc! { "$unset": "relevance" },
+    ]);
+
+    let cursor = collection
+        .aggregate(pipeline)
+        .with_type::<BusinessReview>()
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut reviews = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(review) => reviews.push(review),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(reviews)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find reviews")
+        }
+    }
+}
+
+pub async fn create_business_review(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    filter: web::Data<dyn ContentFilter>,
+    path: web::Path<Uuid>,
+    review: web::Json<NewReview>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let review = review.into_inner();
+    if review
+        .reviewer_id
+        .is_some_and(|reviewer_id| reviewer_id != user.user_id)
+    {
+        return HttpResponse::Forbidden().json("Reviews can only be posted as yourself");
+    }
+    if !valid_score(review.score) {
+        return HttpResponse::BadRequest().json("Score must be between 1 and 5");
+    }
+    let held = match screen_comment(filter.get_ref(), review.comment.as_deref()) {
+        Ok(held) => held,
+        Err(response) => return response,
+    };
+    let business = match check_new_review(&client, business_id, user.user_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    let verified_purchase = match check_purchase(&client, &business, user.user_id).await {
+        Ok(verified) => verified,
+        Err(response) => return response,
+    };
+
+    let mut rating = Rating::new(review.score);
+    rating.description = review.description;
+    let mut new_review = BusinessReview::new(
+        Uuid::new_v4(),
+        user.user_id,
+        business_id,
+        rating,
+        review.comment,
+        review.review_link,
+    );
+    new_review.hidden = held.is_some();
+    new_review.verified_purchase = verified_purchase;
+
+    match insert_review(&client, &new_review).await {
+        Ok(()) => {
+            if let Some(note) = held {
+                hold_review(&client, &new_review, note).await;
+            }
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/reviews/{}", new_review.business_review_id),
+                ))
+                .json(new_review)
+        }
+        Err(response) => response,
+    }
+}
+
+/// Hidden reviews are only shown to their reviewer and admins.
+pub async fn get_review(
+    user: Option<AuthenticatedUser>,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let collection: Collection<BusinessReview> =
+        client.database("cucura-ccdb").collection("reviews");
+    let filter = doc! { "business_review_id": uuid_bson(path.into_inner()) };
+    let may_see_hidden = |review: &BusinessReview| {
+        user.as_ref()
+            .is_some_and(|user| user.user_id == review.reviewer_id || user.is_admin())
+    };
+
+    match collection.find_one(filter).await {
+        Ok(Some(review)) if !review.hidden || may_see_hidden(&review) => {
+            HttpResponse::Ok().json(review)
+        }
+        Ok(_) => HttpResponse::NotFound().json("review not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find review")
+        }
+    }
+}
+
+pub async fn patch_review(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    filter: web::Data<dyn ContentFilter>,
+    path: web::Path<Uuid>,
+    patch: web::Json<ReviewPatch>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    let patch = patch.into_inner();
+
+    let held = match screen_comment(filter.get_ref(), patch.comment.as_deref()) {
+        Ok(held) => held,
+        Err(response) => return response,
+    };
+
+    let mut set = doc! { "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap() };
+    if let Some(score) = patch.score {
+        if !valid_score(score) {
+            return HttpResponse::BadRequest().json("Score must be between 1 and 5");
+        }
+        set.insert("rating.score", score);
+    }
+    if let Some(description) = patch.description {
+        set.insert("rating.description", description);
+    }
+    if let Some(comment) = patch.comment {
+        set.insert("comment", comment);
+    }
+    if let Some(review_link) = patch.review_link {
+        set.insert("review_link", review_link);
+    }
+    if let Err(response) = own_review(&client, business_review_id, &user).await {
+        return response;
+    }
+
+    match edit_review(&client, business_review_id, &user, set, patch.score).await {
+        Ok(mut review) => {
+            if let Some(note) = held {
+                hold_review(&client, &review, note).await;
+                review.hidden = true;
+            }
+            HttpResponse::Ok().json(review)
+        }
+        Err(response) => response,
+    }
+}
+
+pub async fn remove_review(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    match delete_own_review(&client, path.into_inner(), &user).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(response) => response,
+    }
+}
+
+/// Earlier versions of an edited review, oldest first.
+pub async fn list_review_revisions(
+    _admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let cursor = revisions(&client)
+        .find(doc! { "business_review_id": uuid_bson(path.into_inner()) })
+        .sort(doc! { "replaced_at": 1 })
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut found = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(revision) => found.push(revision),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(found)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find revisions")
+        }
+    }
+}
+
+/// The review and its business, if `user` may answer for the business
+/// (owners, admins and editors).
+async fn answerable_review(
+    client: &Client,
+    business_review_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<(BusinessReview, Business), HttpResponse> {
+    let review = match reviews(client)
+        .find_one(doc! { "business_review_id": uuid_bson(business_review_id) })
+        .await
+    {
+        Ok(Some(review)) => review,
+        Ok(None) => return Err(HttpResponse::NotFound().json("review not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return Err(HttpResponse::InternalServerError().json("Failed to find review"));
+        }
+    };
+    let businesses: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let business = match businesses
+        .find_one(doc! { "business_id": uuid_bson(review.business_id) })
+        .await
+    {
+        Ok(Some(business)) => business,
+        Ok(None) => return Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return Err(HttpResponse::InternalServerError().json("Failed to find business"));
+        }
+    };
+    match role_of(&business, user.user_id) {
+        Some(BusinessRole::Owner | BusinessRole::Admin | BusinessRole::Editor) => {
+            Ok((review, business))
+        }
+        _ => Err(HttpResponse::Forbidden()
+            .json("Only owners, admins and editors can respond to reviews")),
+    }
+}
+
+fn response_body(body: &str) -> Result<String, HttpResponse> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_RESPONSE_LENGTH {
+        return Err(HttpResponse::BadRequest().json("Responses must be 1 to 2000 characters"));
+    }
+    Ok(body.to_string())
+}
+
+/// Answers the review for its business; a review takes one response,
+/// which is then edited rather than added to.
+pub async fn create_response(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    response: web::Json<NewResponse>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    let body = match response_body(&response.body) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let (review, business) = match answerable_review(&client, business_review_id, &user).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    let now = Utc::now().naive_utc();
+    let response = ReviewResponse {
+        responder_id: user.user_id,
+        body,
+        created_at: now,
+        updated_at: now,
+    };
+    let update_result = reviews(&client)
+        .find_one_and_update(
+            doc! { "business_review_id": uuid_bson(business_review_id), "response": null },
+            doc! { "$set": { "response": bson::to_bson(&response).unwrap() } },
+        )
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(answered)) => {
+            let notification = Notification::new(
+                NotificationType::SomeoneRespondedToReview,
+                review.reviewer_id,
+                format!("{} responded to your review", business.name),
+            );
+            if let Err(e) = client
+                .database("cucura-ccdb")
+                .collection::<Notification>("notifications")
+                .insert_one(notification)
+                .await
+            {
+                warn!(error = %e, "Failed to insert notification");
+            }
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/reviews/{}/response", business_review_id),
+                ))
+                .json(answered)
+        }
+        Ok(None) => {
+            HttpResponse::Conflict().json("This review already has a response; edit it instead")
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to add response")
+        }
+    }
+}
+
+pub async fn edit_response(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    response: web::Json<NewResponse>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    let body = match response_body(&response.body) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    if let Err(response) = answerable_review(&client, business_review_id, &user).await {
+        return response;
+    }
+
+    let update_result = reviews(&client)
+        .find_one_and_update(
+            doc! {
+                "business_review_id": uuid_bson(business_review_id),
+                "response": { "$ne": null },
+            },
+            doc! { "$set": {
+                "response.body": body,
+                "response.responder_id": uuid_bson(user.user_id),
+                "response.updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+            } },
+        )
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(review)) => HttpResponse::Ok().json(review),
+        Ok(None) => HttpResponse::NotFound().json("This review has no response"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update response")
+        }
+    }
+}
+
+pub async fn remove_response(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    if let Err(response) = answerable_review(&client, business_review_id, &user).await {
+        return response;
+    }
+
+    let update_result = reviews(&client)
+        .update_one(
+            doc! {
+                "business_review_id": uuid_bson(business_review_id),
+                "response": { "$ne": null },
+            },
+            doc! { "$set": { "response": null } },
+        )
+        .await;
+
+    match update_result {
+        Ok(result) if result.matched_count == 0 => {
+            HttpResponse::NotFound().json("This review has no response")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to delete response")
+        }
+    }
+}
+
+/// Moves the review's counts from the user's `removed` vote to their
+/// `added` one; `None` for no vote.
+async fn count_vote(
+    client: &Client,
+    business_review_id: Uuid,
+    removed: Option<bool>,
+    added: Option<bool>,
+) {
+    if removed == added {
+        return;
+    }
+    let field = |helpful| {
+        if helpful {
+            "helpful_count"
+        } else {
+            "unhelpful_count"
+        }
+    };
+    let mut inc = Document::new();
+    if let Some(helpful) = removed {
+        inc.insert(field(helpful), -1i64);
+    }
+    if let Some(helpful) = added {
+        inc.insert(field(helpful), 1i64);
+    }
+    if let Err(e) = reviews(client)
+        .update_one(
+            doc! { "business_review_id": uuid_bson(business_review_id) },
+            doc! { "$inc": inc },
+        )
+        .await
+    {
+        error!(error = %e, business_review_id = %business_review_id, "Failed to update vote counts");
+    }
+}
+
+fn vote_filter(business_review_id: Uuid, user_id: Uuid) -> Document {
+    doc! { "business_review_id": uuid_bson(business_review_id), "user_id": uuid_bson(user_id) }
+}
+
+/// The signed-in user's vote on the review.
+pub async fn get_vote(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    match votes(&client)
+        .find_one(vote_filter(path.into_inner(), user.user_id))
+        .await
+    {
+        Ok(Some(vote)) => HttpResponse::Ok().json(vote),
+        Ok(None) => HttpResponse::NotFound().json("You have not voted on this review"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find vote")
+        }
+    }
+}
+
+/// Records or changes the signed-in user's vote. Reviewers cannot vote on
+/// their own reviews.
+pub async fn put_vote(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    vote: web::Json<NewVote>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    let filter = doc! {
+        "business_review_id": uuid_bson(business_review_id),
+        "hidden": { "$ne": true },
+    };
+    match reviews(&client).find_one(filter).await {
+        Ok(Some(review)) if review.reviewer_id == user.user_id => {
+            return HttpResponse::Forbidden().json("You cannot vote on your own review")
+        }
+        Ok(Some(_)) => (),
+        Ok(None) => return HttpResponse::NotFound().json("review not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to record vote");
+        }
+    }
+
+    let now = Utc::now().naive_utc();
+    let update = doc! {
+        "$set": { "helpful": vote.helpful, "updated_at": bson::to_bson(&now).unwrap() },
+        "$setOnInsert": { "created_at": bson::to_bson(&now).unwrap() },
+    };
+    let collection = votes(&client);
+    let upsert = || {
+        collection
+            .find_one_and_update(
+                vote_filter(business_review_id, user.user_id),
+                update.clone(),
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+    };
+    // A concurrent first vote by the same user loses to the unique index;
+    // by the retry the vote exists and is simply updated.
+    let previous = match upsert().await {
+        Err(e) if is_duplicate_key(&e) => upsert().await,
+        result => result,
+    };
+
+    match previous {
+        Ok(previous) => {
+            count_vote(
+                &client,
+                business_review_id,
+                previous.as_ref().map(|previous| previous.helpful),
+                Some(vote.helpful),
+            )
+            .await;
+            HttpResponse::Ok().json(ReviewVote {
+                business_review_id,
+                user_id: user.user_id,
+                helpful: vote.helpful,
+                created_at: previous.map_or(now, |previous| previous.created_at),
+                updated_at: now,
+            })
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to record vote")
+        }
+    }
+}
+
+pub async fn remove_vote(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business_review_id = path.into_inner();
+    match votes(&client)
+        .find_one_and_delete(vote_filter(business_review_id, user.user_id))
+        .await
+    {
+        Ok(Some(vote)) => {
+            count_vote(&client, business_review_id, Some(vote.helpful), None).await;
+            HttpResponse::NoContent().finish()
+        }
+        Ok(None) => HttpResponse::NotFound().json("You have not voted on this review"),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete vote")
+        }
+    }
+}
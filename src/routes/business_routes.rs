@@ -1,21 +1,346 @@
 // routes/business_routes.rs
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
 use bson::serde_helpers::uuid_1_as_binary;
+use chrono::{NaiveDate, Utc};
 use futures::StreamExt;
-use models::business::Business;
+use models::business::{owners, Business, BusinessRole, RatingSummary, VerificationStatus};
+use models::geo::GeoPoint;
+use models::opening_hours::OpeningHours;
 use mongodb::{
-    bson::{self, doc, oid::ObjectId, Bson},
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    options::ReturnDocument,
     Client, Collection,
 };
+use tracing::{error, warn};
 use uuid::Uuid;
 
+use super::membership_routes::role_of;
+use super::PaginationParams;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::metrics;
 use crate::models;
-use serde::Deserialize;
+use crate::schedule;
+use crate::taxonomy;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize)]
-pub struct PaginationParams {
-    pub page: Option<u32>,
-    pub limit: Option<u32>,
+#[derive(Debug, Deserialize)]
+pub struct NewBusiness {
+    pub name: String,
+    pub description: String,
+    pub logo: Option<String>,
+    pub pictures: Option<Vec<String>>,
+    pub founder: String,
+    /// Defaults to the name of the first category.
+    pub industry: Option<String>,
+    /// Taxonomy codes; mapped from `industry` when absent.
+    pub category_codes: Option<Vec<String>>,
+    pub phone: String,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub country: String,
+    pub website: Option<String>,
+    pub contact_email: Option<String>,
+    pub founded_on: Option<NaiveDate>,
+    /// Geocoded from `city` and `country` when absent.
+    pub location: Option<GeoPoint>,
+    pub opening_hours: Option<OpeningHours>,
+}
+
+impl NewBusiness {
+    fn into_business(self, owner: Uuid, industry: String, category_codes: Vec<String>) -> Business {
+        let mut business = Business::new(
+            Some(vec![owner]),
+            self.name,
+            self.description,
+            self.founder,
+            industry,
+            self.phone,
+            self.country,
+        );
+        business.category_codes = category_codes;
+        business.logo = self.logo;
+        business.pictures = self.pictures;
+        business.address = self.address;
+        business.city = self.city;
+        business.region = self.region;
+        business.website = self.website;
+        business.contact_email = self.contact_email;
+        business.founded_on = self.founded_on;
+        business.opening_hours = self.opening_hours;
+        match self.location {
+            Some(location) => business.location = Some(location),
+            None => business.locate(),
+        }
+        business
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BusinessPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pictures: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub founder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub industry: Option<String>,
+    /// Replaces the categories; `industry` follows the first one unless
+    /// also given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_codes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact_email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub founded_on: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_hours: Option<OpeningHours>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NearQuery {
+    pub lat: f64,
+    pub lng: f64,
+    /// Defaults to 10 km, at most 500 km.
+    pub radius_km: Option<f64>,
+    pub industry: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoundsQuery {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+    pub industry: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearbyBusiness {
+    #[serde(flatten)]
+    pub business: Business,
+    /// Metres from the query point (the box centre for bounding-box queries).
+    pub distance_m: f64,
+}
+
+/// Directory filters for `GET /v2/businesses`; all optional and combined with AND.
+#[derive(Debug, Deserialize)]
+pub struct DirectoryQuery {
+    /// A taxonomy code; matches its subcategories too.
+    pub category: Option<String>,
+    pub industry: Option<String>,
+    pub country: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    /// 1 to 5; businesses without reviews never match.
+    pub min_rating: Option<f64>,
+    pub founded_from: Option<NaiveDate>,
+    pub founded_to: Option<NaiveDate>,
+    pub has_website: Option<bool>,
+    pub verified: Option<bool>,
+    /// Open at the time of the request, in each business's own timezone.
+    pub open_now: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Facet {
+    Category,
+    Industry,
+    Country,
+    Region,
+    City,
+    Rating,
+    Founded,
+    Website,
+    Verified,
+    OpenNow,
+}
+
+impl DirectoryQuery {
+    fn validate(&self) -> Result<(), &'static str> {
+        if self
+            .category
+            .as_deref()
+            .is_some_and(|code| !taxonomy::valid_code(code))
+        {
+            return Err("category must be a category code");
+        }
+        if let Some(min_rating) = self.min_rating {
+            if !(1.0..=5.0).contains(&min_rating) {
+                return Err("min_rating must be between 1 and 5");
+            }
+        }
+        if let (Some(from), Some(to)) = (self.founded_from, self.founded_to) {
+            if from > to {
+                return Err("founded_from must not be after founded_to");
+            }
+        }
+        Ok(())
+    }
+
+    /// One condition per requested filter, tagged with the facet it narrows.
+    fn conditions(&self) -> Vec<(Facet, Document)> {
+        let mut conditions = Vec::new();
+        if let Some(code) = &self.category {
+            // Codes are letters and digits only, so need no escaping.
+            conditions.push((
+                Facet::Category,
+                doc! { "category_codes": { "$regex": format!("^{}", code) } },
+            ));
+        }
+        for (facet, field, value) in [
+            (Facet::Industry, "industry", &self.industry),
+            (Facet::Country, "country", &self.country),
+            (Facet::Region, "region", &self.region),
+            (Facet::City, "city", &self.city),
+        ] {
+            if let Some(value) = value {
+                conditions.push((facet, doc! { field: value }));
+            }
+        }
+        if let Some(min_rating) = self.min_rating {
+            conditions.push((
+                Facet::Rating,
+                doc! { "rating_summary.average": { "$gte": min_rating } },
+            ));
+        }
+        // Dates are stored as `YYYY-MM-DD` strings, which sort chronologically.
+        let mut founded = doc! {};
+        if let Some(from) = self.founded_from {
+            founded.insert("$gte", from.to_string());
+        }
+        if let Some(to) = self.founded_to {
+            founded.insert("$lte", to.to_string());
+        }
+        if !founded.is_empty() {
+            conditions.push((Facet::Founded, doc! { "founded_on": founded }));
+        }
+        match self.has_website {
+            Some(true) => conditions.push((
+                Facet::Website,
+                doc! { "website": { "$type": "string", "$ne": "" } },
+            )),
+            Some(false) => conditions.push((
+                Facet::Website,
+                doc! { "$or": [{ "website": null }, { "website": "" }] },
+            )),
+            None => (),
+        }
+        match self.verified {
+            Some(true) => {
+                conditions.push((Facet::Verified, doc! { "verification_status": "verified" }))
+            }
+            Some(false) => conditions.push((
+                Facet::Verified,
+                doc! { "verification_status": { "$ne": "verified" } },
+            )),
+            None => (),
+        }
+        match self.open_now {
+            Some(true) => conditions.push((Facet::OpenNow, doc! { "$expr": schedule::open_now() })),
+            Some(false) => conditions.push((
+                Facet::OpenNow,
+                doc! { "$expr": { "$not": [schedule::open_now()] } },
+            )),
+            None => (),
+        }
+        conditions
+    }
+}
+
+/// Every condition except the one on `except`, so a facet counts the values
+/// the user could switch to rather than only the one already selected.
+fn match_stage(conditions: &[(Facet, Document)], except: Option<Facet>) -> Document {
+    let conditions: Vec<&Document> = conditions
+        .iter()
+        .filter(|(facet, _)| Some(*facet) != except)
+        .map(|(_, condition)| condition)
+        .collect();
+    if conditions.is_empty() {
+        doc! { "$match": {} }
+    } else {
+        doc! { "$match": { "$and": conditions } }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: Bson,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirectoryFacets {
+    pub industry: Vec<FacetCount>,
+    pub country: Vec<FacetCount>,
+    pub region: Vec<FacetCount>,
+    pub city: Vec<FacetCount>,
+    /// Cumulative: `{"value": 4, ...}` counts businesses rated 4 and up.
+    pub min_rating: Vec<FacetCount>,
+    pub founded_year: Vec<FacetCount>,
+    pub has_website: Vec<FacetCount>,
+    pub verified: Vec<FacetCount>,
+    pub open_now: Vec<FacetCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListedBusiness {
+    #[serde(flatten)]
+    pub business: Business,
+    /// `rating_summary.average`, kept for clients that read it from here.
+    pub average_rating: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BusinessDirectory {
+    pub total: i64,
+    pub facets: DirectoryFacets,
+    pub results: Vec<ListedBusiness>,
+}
+
+/// Values listed per facet, most common first.
+const MAX_FACET_VALUES: i64 = 50;
+
+#[derive(Debug, Serialize)]
+pub struct BusinessDetails {
+    #[serde(flatten)]
+    pub business: Business,
+    /// `None` when the business has not published opening hours.
+    pub open_now: Option<bool>,
+}
+
+/// A business listed for one of its members.
+#[derive(Debug, Serialize)]
+pub struct MemberBusiness {
+    #[serde(flatten)]
+    pub business: Business,
+    pub role: Option<BusinessRole>,
+}
+
+const MAX_RADIUS_KM: f64 = 500.0;
+
+fn valid_coordinates(latitude: f64, longitude: f64) -> bool {
+    (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)
 }
 
 pub fn business_routes(cfg: &mut web::ServiceConfig) {
@@ -30,12 +355,57 @@ pub fn business_routes(cfg: &mut web::ServiceConfig) {
         );
 }
 
+pub fn business_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses")
+            .route(web::get().to(list_businesses))
+            .route(web::post().to(create_business)),
+    )
+    // Registered before `/businesses/{business_id}`, which would otherwise match.
+    .service(web::resource("/businesses/near").route(web::get().to(find_businesses_near)))
+    .service(web::resource("/businesses/within").route(web::get().to(find_businesses_within)))
+    .service(
+        web::resource("/businesses/{business_id}")
+            .route(web::get().to(find_business))
+            .route(web::patch().to(patch_business))
+            .route(web::delete().to(remove_business)),
+    )
+    .service(
+        web::resource("/users/{user_id}/businesses").route(web::get().to(list_businesses_for_user)),
+    );
+}
+
+/// Like [`create_business`], the caller becomes the only owner; any
+/// `user_ids` sent are ignored.
 pub async fn register_business(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
     business: web::Json<Business>,
 ) -> impl Responder {
     let collection = client.database("cucura-ccdb").collection("businesses");
-    let new_business = business.into_inner();
+    let mut new_business = business.into_inner();
+    if let Some(Err(message)) = new_business.opening_hours.as_ref().map(schedule::validate) {
+        return HttpResponse::BadRequest().json(message);
+    }
+    if new_business.location.is_none() {
+        new_business.locate();
+    }
+    new_business.user_ids = Some(vec![user.user_id]);
+    new_business.members = owners(&[user.user_id]);
+    new_business.verification_status = VerificationStatus::Unverified;
+    new_business.verified_at = None;
+    new_business.rating_summary = RatingSummary::default();
+    new_business.hidden = false;
+    match categorize_business(&client, &new_business).await {
+        Ok(Ok(category_codes)) => new_business.category_codes = category_codes,
+        Ok(Err(code)) => {
+            return HttpResponse::BadRequest().json(format!("Unknown category {}", code))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to find categories");
+            return HttpResponse::InternalServerError().json("Failed to register business");
+        }
+    }
     //find if business already exists
     let filter = doc! { "name": &new_business.name };
     let business_exists = collection.find_one(filter.clone()).await.unwrap();
@@ -47,15 +417,24 @@ pub async fn register_business(
     let insert_result = collection.insert_one(new_business).await;
 
     match insert_result {
-        Ok(_) => HttpResponse::Ok().json("business registered successfully"),
+        Ok(_) => {
+            metrics::BUSINESS_REGISTRATIONS.inc();
+            HttpResponse::Ok().json("business registered successfully")
+        }
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to register business")
         }
     }
 }
 
-pub async fn find_business(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
+/// Businesses hidden by moderation are only shown to their members and
+/// admins.
+pub async fn find_business(
+    user: Option<AuthenticatedUser>,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
     let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
     let business_id = path.into_inner();
     let business_id_result = Uuid::parse_str(&business_id);
@@ -66,43 +445,100 @@ pub async fn find_business(client: web::Data<Client>, path: web::Path<String>) -
     };
     let filter = doc! { "business_id": Bson::Binary(bson::Binary {
          subtype: bson::spec::BinarySubtype::UserDefined(0), bytes: business_id_uuid.as_bytes().to_vec() }) };
+    let may_see_hidden = |business: &Business| {
+        user.as_ref()
+            .is_some_and(|user| user.is_admin() || role_of(business, user.user_id).is_some())
+    };
     match collection.find_one(filter).await {
-        Ok(Some(business)) => HttpResponse::Ok().json(business),
-        Ok(None) => HttpResponse::NotFound().json("Business not found"),
+        Ok(Some(business)) if !business.hidden || may_see_hidden(&business) => {
+            record_business_view(&client, business.business_id).await;
+            HttpResponse::Ok().json(BusinessDetails {
+                open_now: business
+                    .opening_hours
+                    .as_ref()
+                    .map(|hours| schedule::is_open(hours, Utc::now())),
+                business,
+            })
+        }
+        Ok(_) => HttpResponse::NotFound().json("Business not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find business")
         }
     }
 }
 
+/// Daily view counts for the owners' analytics; failing to count one never
+/// fails the read.
+async fn record_business_view(client: &Client, business_id: Uuid) {
+    let result = client
+        .database("cucura-ccdb")
+        .collection::<Document>("business_views")
+        .update_one(
+            doc! {
+                "business_id": uuid_bson(business_id),
+                "date": Utc::now().date_naive().to_string(),
+            },
+            doc! { "$inc": { "views": 1 } },
+        )
+        .upsert(true)
+        .await;
+    if let Err(e) = result {
+        warn!(error = %e, "Failed to record business view");
+    }
+}
+
+/// Owners, admins and editors can change a business; its members and
+/// verification are left as they are.
 pub async fn update_business(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
     business: web::Json<Business>,
 ) -> impl Responder {
     let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
     let mut new_business = business.into_inner();
-    //check if business already exists
-    let business_id_result = Uuid::parse_str(&new_business.business_id.to_string());
-    let business_id_uuid: Uuid;
-    match business_id_result {
-        Ok(uuid) => business_id_uuid = uuid,
-        Err(_) => return HttpResponse::BadRequest().json("Invalid business ID format"),
+    if let Some(Err(message)) = new_business.opening_hours.as_ref().map(schedule::validate) {
+        return HttpResponse::BadRequest().json(message);
+    }
+    let existing = match business_for_role(
+        &client,
+        new_business.business_id,
+        &user,
+        &[
+            BusinessRole::Owner,
+            BusinessRole::Admin,
+            BusinessRole::Editor,
+        ],
+        "Only owners, admins and editors can change the business",
+    )
+    .await
+    {
+        Ok(existing) => existing,
+        Err(response) => return response,
     };
+    let filter = doc! { "business_id": uuid_bson(existing.business_id) };
+    let renamed = existing.name != new_business.name;
 
-    let binary_business_id = Bson::Binary(bson::Binary {
-        subtype: bson::spec::BinarySubtype::UserDefined(0),
-        bytes: business_id_uuid.as_bytes().to_vec(),
-    });
-    let filter = doc! { "business_id": &binary_business_id};
-    let business_exists = collection.find_one(filter.clone()).await.unwrap();
-    match business_exists {
-        Some(_) => (),
-        None => return HttpResponse::Ok().json("Error 10001 : Business does not exist"),
+    match categorize_business(&client, &new_business).await {
+        Ok(Ok(category_codes)) => new_business.category_codes = category_codes,
+        Ok(Err(code)) => {
+            return HttpResponse::BadRequest().json(format!("Unknown category {}", code))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to find categories");
+            return HttpResponse::InternalServerError().json("Failed to update business");
+        }
+    }
+
+    // Older clients never send a location: only a move re-geocodes, and a
+    // miss keeps whatever location the business already had.
+    if new_business.location.is_none() && new_business.moved_from(&existing) {
+        new_business.location = existing.location;
+        new_business.relocate();
     }
 
     //let update_doc = doc! { "$set": bson::to_document(&new_business).unwrap() };
-    let update_doc = doc! {
+    let mut update_doc = doc! {
         "$set": {
             "name": &new_business.name,
             "description": &new_business.description,
@@ -110,6 +546,7 @@ pub async fn update_business(
             "pictures": &new_business.pictures,
             "founder": &new_business.founder,
             "industry": &new_business.industry,
+            "category_codes": &new_business.category_codes,
             "phone": &new_business.phone,
             "address": &new_business.address,
             "city": &new_business.city,
@@ -117,31 +554,64 @@ pub async fn update_business(
             "country": &new_business.country,
             "website": &new_business.website,
             "contact_email": &new_business.contact_email,
+            "founded_on": bson::to_bson(&new_business.founded_on).unwrap(),
             "updated_at": &new_business.updated_at,
         }
     };
+    // Older clients don't send hours; keep any set through v2.
+    if let Some(opening_hours) = &new_business.opening_hours {
+        let set = update_doc.get_document_mut("$set").unwrap();
+        set.insert("opening_hours", bson::to_bson(opening_hours).unwrap());
+    }
+    if renamed {
+        let set = update_doc.get_document_mut("$set").unwrap();
+        set.insert("verification_status", "unverified");
+        set.insert("verified_at", Bson::Null);
+    }
+    if let Some(location) = &new_business.location {
+        let set = update_doc.get_document_mut("$set").unwrap();
+        set.insert("location", bson::to_bson(location).unwrap());
+    }
     let update_result = collection.update_one(filter, update_doc).await;
 
     match update_result {
         Ok(_) => HttpResponse::Ok().json("business updated successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to update business")
         }
     }
 }
 
-pub async fn delete_business(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
+/// Only owners can delete a business.
+pub async fn delete_business(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
     let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
-    let business_id = path.into_inner();
-    let filter = doc! { "business_id": &business_id};
+    let Ok(business_id) = Uuid::parse_str(&path.into_inner()) else {
+        return HttpResponse::BadRequest().json("Invalid business ID format");
+    };
+    if let Err(response) = business_for_role(
+        &client,
+        business_id,
+        &user,
+        &[BusinessRole::Owner],
+        "Only owners can delete the business",
+    )
+    .await
+    {
+        return response;
+    }
+    let filter = doc! { "business_id": uuid_bson(business_id) };
 
     let delete_result = collection.delete_one(filter).await;
 
     match delete_result {
         Ok(_) => HttpResponse::Ok().json("business deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete business")
         }
     }
@@ -152,7 +622,7 @@ pub async fn find_all_businesses(
     query: web::Query<PaginationParams>,
 ) -> impl Responder {
     let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
-    let filter = doc! {};
+    let filter = doc! { "hidden": { "$ne": true } };
 
     let page = query.page.unwrap_or(1);
     let limit = query.limit.unwrap_or(20);
@@ -169,41 +639,685 @@ pub async fn find_all_businesses(
     while let Some(result) = cursor.next().await {
         match result {
             Ok(business) => businesses.push(business),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(businesses)
 }
 
+/// The business directory: filtered, name-ordered businesses plus counts
+/// per facet value for the filter sidebar.
+pub async fn list_businesses(
+    client: web::Data<Client>,
+    query: web::Query<DirectoryQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    if let Err(message) = query.validate() {
+        return HttpResponse::BadRequest().json(message);
+    }
+    match business_directory(&client, &query, &pagination).await {
+        Ok(directory) => HttpResponse::Ok().json(directory),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find businesses")
+        }
+    }
+}
+
+async fn business_directory(
+    client: &Client,
+    query: &DirectoryQuery,
+    pagination: &PaginationParams,
+) -> Result<BusinessDirectory, String> {
+    let collection: Collection<Document> = client.database("cucura-ccdb").collection("businesses");
+    let conditions = query.conditions();
+
+    let values = |facet: Facet, field: &str| {
+        vec![
+            match_stage(&conditions, Some(facet)),
+            doc! { "$match": { field: { "$type": "string", "$ne": "" } } },
+            doc! { "$group": { "_id": format!("${}", field), "count": { "$sum": 1 } } },
+            doc! { "$sort": { "count": -1, "_id": 1 } },
+            doc! { "$limit": MAX_FACET_VALUES },
+            doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+        ]
+    };
+    let pipeline = [
+        doc! { "$match": { "hidden": { "$ne": true } } },
+        doc! { "$facet": {
+            "results": [
+                match_stage(&conditions, None),
+                doc! { "$sort": { "name": 1, "business_id": 1 } },
+                doc! { "$skip": pagination.skip() as i64 },
+                doc! { "$limit": pagination.limit() },
+            ],
+            "total": [match_stage(&conditions, None), doc! { "$count": "count" }],
+            "industry": values(Facet::Industry, "industry"),
+            "country": values(Facet::Country, "country"),
+            "region": values(Facet::Region, "region"),
+            "city": values(Facet::City, "city"),
+            "rating": [
+                match_stage(&conditions, Some(Facet::Rating)),
+                doc! { "$match": { "rating_summary.average": { "$ne": null } } },
+                doc! { "$group": {
+                    "_id": { "$toInt": { "$floor": "$rating_summary.average" } },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+            ],
+            "founded_year": [
+                match_stage(&conditions, Some(Facet::Founded)),
+                doc! { "$match": { "founded_on": { "$type": "string" } } },
+                doc! { "$group": {
+                    "_id": { "$toInt": { "$substrBytes": ["$founded_on", 0, 4] } },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$sort": { "_id": -1 } },
+                doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+            ],
+            "has_website": [
+                match_stage(&conditions, Some(Facet::Website)),
+                doc! { "$group": {
+                    "_id": { "$and": [
+                        { "$eq": [{ "$type": "$website" }, "string"] },
+                        { "$ne": ["$website", ""] },
+                    ] },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$sort": { "_id": -1 } },
+                doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+            ],
+            "verified": [
+                match_stage(&conditions, Some(Facet::Verified)),
+                doc! { "$group": {
+                    "_id": { "$eq": ["$verification_status", "verified"] },
+                    "count": { "$sum": 1 },
+                } },
+                doc! { "$sort": { "_id": -1 } },
+                doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+            ],
+            "open_now": [
+                match_stage(&conditions, Some(Facet::OpenNow)),
+                doc! { "$group": { "_id": schedule::open_now(), "count": { "$sum": 1 } } },
+                doc! { "$sort": { "_id": -1 } },
+                doc! { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+            ],
+        } },
+    ];
+
+    let mut cursor = collection
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut page = match cursor.next().await {
+        Some(page) => page.map_err(|e| e.to_string())?,
+        None => return Err("$facet returned no document".to_string()),
+    };
+
+    let mut facet = |name: &str| -> Result<Vec<FacetCount>, String> {
+        let counts = page.remove(name).unwrap_or(Bson::Array(Vec::new()));
+        bson::from_bson(counts).map_err(|e| e.to_string())
+    };
+    let industry = facet("industry")?;
+    let country = facet("country")?;
+    let region = facet("region")?;
+    let city = facet("city")?;
+    let rating = facet("rating")?;
+    let founded_year = facet("founded_year")?;
+    let has_website = facet("has_website")?;
+    let verified = facet("verified")?;
+    let open_now = facet("open_now")?;
+
+    // Scores are whole stars, so averages floor to 1..=5; "4 and up" also
+    // counts the 5s.
+    let min_rating = (1..=4)
+        .rev()
+        .map(|stars| FacetCount {
+            value: Bson::Int32(stars),
+            count: rating
+                .iter()
+                .filter(|bucket| matches!(bucket.value, Bson::Int32(value) if value >= stars))
+                .map(|bucket| bucket.count)
+                .sum(),
+        })
+        .collect();
+
+    let total = page
+        .get_array("total")
+        .ok()
+        .and_then(|total| total.first())
+        .and_then(Bson::as_document)
+        .and_then(|total| total.get_i32("count").ok())
+        .unwrap_or(0) as i64;
+    let mut results = Vec::new();
+    for document in page.get_array("results").map_err(|e| e.to_string())? {
+        let Some(document) = document.as_document().cloned() else {
+            continue;
+        };
+        match bson::from_document::<Business>(document) {
+            Ok(business) => results.push(ListedBusiness {
+                average_rating: business.rating_summary.average,
+                business,
+            }),
+            Err(e) => error!(error = %e, "Failed to read document"),
+        }
+    }
+
+    Ok(BusinessDirectory {
+        total,
+        facets: DirectoryFacets {
+            industry,
+            country,
+            region,
+            city,
+            min_rating,
+            founded_year,
+            has_website,
+            verified,
+            open_now,
+        },
+        results,
+    })
+}
+
+/// Categories for a business sent to the v1 endpoints, which predate the
+/// taxonomy: given codes are checked, otherwise `industry` is mapped.
+async fn categorize_business(
+    client: &Client,
+    business: &Business,
+) -> mongodb::error::Result<Result<Vec<String>, String>> {
+    let categories = taxonomy::load(&client.database("cucura-ccdb")).await?;
+    Ok(taxonomy::categorize(
+        &business.category_codes,
+        Some(&business.industry),
+        &categories,
+    ))
+}
+
 //create new function of find busineses by user_id
 pub async fn find_businesses_by_user_id(
     client: web::Data<Client>,
     path: web::Path<String>,
 ) -> impl Responder {
     let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
-    let user_id = path.into_inner();
+    let user_id = match Uuid::parse_str(&path.into_inner()) {
+        Ok(user_id) => user_id,
+        Err(_) => return HttpResponse::BadRequest().json("Invalid user ID format"),
+    };
     //check if user exists
     let collection_user: Collection<models::user::User> =
         client.database("cucura-ccdb").collection("users");
-    let filter_user = doc! {"user_id": &user_id};
+    let filter_user = doc! {"user_id": uuid_bson(user_id)};
     let user_exists = collection_user.find_one(filter_user.clone()).await.unwrap();
     match user_exists {
         Some(_) => (),
         None => return HttpResponse::Ok().json("Error 10001 : User does not exist"),
     }
 
-    let filter = doc! { "user_ids": {"$elemMatch": &user_id} };
+    let filter = doc! { "members.user_id": uuid_bson(user_id) };
 
     let mut cursor = collection.find(filter).await.unwrap();
 
     let mut businesses = Vec::new();
     while let Some(result) = cursor.next().await {
         match result {
-            Ok(business) => businesses.push(business),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Ok(business) => businesses.push(MemberBusiness {
+                role: role_of(&business, user_id),
+                business,
+            }),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(businesses)
 }
+
+/// The caller becomes the business's only owner; others join through
+/// invitations.
+pub async fn create_business(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    business: web::Json<NewBusiness>,
+) -> impl Responder {
+    let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let new_business = business.into_inner();
+    if let Some([longitude, latitude]) = new_business.location.map(|l| l.coordinates) {
+        if !valid_coordinates(latitude, longitude) {
+            return HttpResponse::BadRequest().json("Invalid location coordinates");
+        }
+    }
+    if let Some(Err(message)) = new_business.opening_hours.as_ref().map(schedule::validate) {
+        return HttpResponse::BadRequest().json(message);
+    }
+    let categories = match taxonomy::load(&client.database("cucura-ccdb")).await {
+        Ok(categories) => categories,
+        Err(e) => {
+            error!(error = %e, "Failed to find categories");
+            return HttpResponse::InternalServerError().json("Failed to register business");
+        }
+    };
+    let category_codes = match taxonomy::categorize(
+        new_business.category_codes.as_deref().unwrap_or_default(),
+        new_business.industry.as_deref(),
+        &categories,
+    ) {
+        Ok(category_codes) => category_codes,
+        Err(code) => return HttpResponse::BadRequest().json(format!("Unknown category {}", code)),
+    };
+    let industry = new_business
+        .industry
+        .clone()
+        .filter(|industry| !industry.trim().is_empty())
+        .or_else(|| {
+            let first = taxonomy::find(category_codes.first()?, &categories)?;
+            Some(first.name.clone())
+        });
+    let Some(industry) = industry else {
+        return HttpResponse::BadRequest().json("industry or category_codes is required");
+    };
+    let new_business = new_business.into_business(user.user_id, industry, category_codes);
+
+    match collection
+        .find_one(doc! { "name": &new_business.name })
+        .await
+    {
+        Ok(Some(_)) => return HttpResponse::Conflict().json("Business already exists"),
+        Ok(None) => (),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to register business");
+        }
+    }
+
+    match collection.insert_one(&new_business).await {
+        Ok(_) => {
+            metrics::BUSINESS_REGISTRATIONS.inc();
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/businesses/{}", new_business.business_id),
+                ))
+                .json(new_business)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to register business")
+        }
+    }
+}
+
+/// The business, if `user`'s role on it is one of `roles`.
+async fn business_for_role(
+    client: &Client,
+    business_id: Uuid,
+    user: &AuthenticatedUser,
+    roles: &[BusinessRole],
+    denied: &'static str,
+) -> Result<Business, HttpResponse> {
+    let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    match collection
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => match role_of(&business, user.user_id) {
+            Some(role) if roles.contains(&role) => Ok(business),
+            _ => Err(HttpResponse::Forbidden().json(denied)),
+        },
+        Ok(None) => Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find business"))
+        }
+    }
+}
+
+/// Owners, admins and editors can change a business.
+pub async fn patch_business(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    patch: web::Json<BusinessPatch>,
+) -> impl Responder {
+    let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let business_id = path.into_inner();
+    let mut patch = patch.into_inner();
+    if let Err(response) = business_for_role(
+        &client,
+        business_id,
+        &user,
+        &[
+            BusinessRole::Owner,
+            BusinessRole::Admin,
+            BusinessRole::Editor,
+        ],
+        "Only owners, admins and editors can change the business",
+    )
+    .await
+    {
+        return response;
+    }
+    if let Some([longitude, latitude]) = patch.location.map(|l| l.coordinates) {
+        if !valid_coordinates(latitude, longitude) {
+            return HttpResponse::BadRequest().json("Invalid location coordinates");
+        }
+    }
+    if let Some(Err(message)) = patch.opening_hours.as_ref().map(schedule::validate) {
+        return HttpResponse::BadRequest().json(message);
+    }
+
+    if let Some(name) = &patch.name {
+        let filter = doc! { "name": name, "business_id": { "$ne": uuid_bson(business_id) } };
+        match collection.find_one(filter).await {
+            Ok(Some(_)) => return HttpResponse::Conflict().json("Business already exists"),
+            Ok(None) => (),
+            Err(e) => {
+                error!(error = %e, "Failed to find document");
+                return HttpResponse::InternalServerError().json("Failed to update business");
+            }
+        }
+    }
+
+    if let Some(codes) = &patch.category_codes {
+        let categories = match taxonomy::load(&client.database("cucura-ccdb")).await {
+            Ok(categories) => categories,
+            Err(e) => {
+                error!(error = %e, "Failed to find categories");
+                return HttpResponse::InternalServerError().json("Failed to update business");
+            }
+        };
+        let codes = match taxonomy::resolve(codes, &categories) {
+            Ok(codes) => codes,
+            Err(code) => {
+                return HttpResponse::BadRequest().json(format!("Unknown category {}", code))
+            }
+        };
+        if patch.industry.is_none() {
+            patch.industry = codes
+                .first()
+                .and_then(|code| taxonomy::find(code, &categories))
+                .map(|category| category.name.clone());
+        }
+        patch.category_codes = Some(codes);
+    }
+
+    let mut set = bson::to_document(&patch).unwrap();
+    set.insert("updated_at", Utc::now().to_string());
+    if let Some(name) = &patch.name {
+        // The badge vouches for the name; a new one needs verifying again.
+        let renamed = collection
+            .update_one(
+                doc! { "business_id": uuid_bson(business_id), "name": { "$ne": name } },
+                doc! { "$set": { "verification_status": "unverified", "verified_at": Bson::Null } },
+            )
+            .await;
+        if let Err(e) = renamed {
+            error!(error = %e, "Failed to update document");
+            return HttpResponse::InternalServerError().json("Failed to update business");
+        }
+    }
+    let update_result = collection
+        .find_one_and_update(
+            doc! { "business_id": uuid_bson(business_id) },
+            doc! { "$set": set },
+        )
+        .return_document(ReturnDocument::After)
+        .await;
+
+    let mut business = match update_result {
+        Ok(Some(business)) => business,
+        Ok(None) => return HttpResponse::NotFound().json("Business not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            return HttpResponse::InternalServerError().json("Failed to update business");
+        }
+    };
+
+    // A move without explicit coordinates re-geocodes from the new address;
+    // a miss keeps the old location.
+    if patch.location.is_none()
+        && (patch.city.is_some() || patch.country.is_some())
+        && business.relocate()
+    {
+        let update = doc! { "$set": {
+            "location": bson::to_bson(&business.location).unwrap(),
+            "region": &business.region,
+        } };
+        if let Err(e) = collection
+            .update_one(doc! { "business_id": uuid_bson(business_id) }, update)
+            .await
+        {
+            error!(error = %e, "Failed to update document");
+            return HttpResponse::InternalServerError().json("Failed to update business");
+        }
+    }
+    HttpResponse::Ok().json(business)
+}
+
+/// Only owners can delete a business.
+pub async fn remove_business(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let business_id = path.into_inner();
+    if let Err(response) = business_for_role(
+        &client,
+        business_id,
+        &user,
+        &[BusinessRole::Owner],
+        "Only owners can delete the business",
+    )
+    .await
+    {
+        return response;
+    }
+    let filter = doc! { "business_id": uuid_bson(business_id) };
+
+    match collection.delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("Business not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete business")
+        }
+    }
+}
+
+pub async fn list_businesses_for_user(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Business> = client.database("cucura-ccdb").collection("businesses");
+    let user_id = path.into_inner();
+    let filter = doc! { "members.user_id": uuid_bson(user_id) };
+
+    let cursor = collection
+        .find(filter)
+        .skip(query.skip())
+        .limit(query.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut businesses = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(business) => businesses.push(MemberBusiness {
+                        role: role_of(&business, user_id),
+                        business,
+                    }),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(businesses)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find businesses")
+        }
+    }
+}
+
+/// Businesses within `radius_km` of a point, nearest first.
+pub async fn find_businesses_near(
+    client: web::Data<Client>,
+    query: web::Query<NearQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    if !valid_coordinates(query.lat, query.lng) {
+        return HttpResponse::BadRequest().json("Invalid lat/lng");
+    }
+    let radius_km = query.radius_km.unwrap_or(10.0);
+    if !(radius_km > 0.0 && radius_km <= MAX_RADIUS_KM) {
+        return HttpResponse::BadRequest()
+            .json(format!("radius_km must be between 0 and {}", MAX_RADIUS_KM));
+    }
+
+    let mut filter = doc! {};
+    if let Some(industry) = &query.industry {
+        filter.insert("industry", industry);
+    }
+    geo_near(
+        &client,
+        GeoPoint::new(query.lng, query.lat),
+        Some(radius_km * 1000.0),
+        filter,
+        &pagination,
+    )
+    .await
+}
+
+/// Businesses inside a bounding box (e.g. the visible map), nearest to its
+/// centre first. Boxes crossing the antimeridian are not supported.
+pub async fn find_businesses_within(
+    client: web::Data<Client>,
+    query: web::Query<BoundsQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    if !valid_coordinates(query.min_lat, query.min_lng)
+        || !valid_coordinates(query.max_lat, query.max_lng)
+        || query.min_lat >= query.max_lat
+        || query.min_lng >= query.max_lng
+    {
+        return HttpResponse::BadRequest().json("Invalid bounding box");
+    }
+
+    let (west, south, east, north) = (query.min_lng, query.min_lat, query.max_lng, query.max_lat);
+    let mut filter = within_box(west, south, east, north);
+    if let Some(industry) = &query.industry {
+        filter.insert("industry", industry);
+    }
+    let centre = GeoPoint::new((west + east) / 2.0, (south + north) / 2.0);
+    geo_near(&client, centre, None, filter, &pagination).await
+}
+
+/// Points inside the box on the map. `$box` is flat, with edges along
+/// parallels and meridians; a GeoJSON polygon's edges follow great circles
+/// instead, which bow away from the parallels on wide boxes.
+fn within_box(west: f64, south: f64, east: f64, north: f64) -> Document {
+    doc! { "location": { "$geoWithin": { "$box": [[west, south], [east, north]] } } }
+}
+
+async fn geo_near(
+    client: &Client,
+    near: GeoPoint,
+    max_distance_m: Option<f64>,
+    mut filter: Document,
+    pagination: &PaginationParams,
+) -> HttpResponse {
+    let collection: Collection<Document> = client.database("cucura-ccdb").collection("businesses");
+    filter.insert("hidden", doc! { "$ne": true });
+    let mut geo_near = doc! {
+        "near": bson::to_bson(&near).unwrap(),
+        "distanceField": "distance_m",
+        "query": filter,
+        "spherical": true,
+    };
+    if let Some(max_distance_m) = max_distance_m {
+        geo_near.insert("maxDistance", max_distance_m);
+    }
+    let pipeline = [
+        doc! { "$geoNear": geo_near },
+        doc! { "$skip": pagination.skip() as i64 },
+        doc! { "$limit": pagination.limit() },
+    ];
+
+    match collection.aggregate(pipeline).await {
+        Ok(mut cursor) => {
+            let mut businesses = Vec::new();
+            while let Some(result) = cursor.next().await {
+                let nearby = result.map_err(|e| e.to_string()).and_then(|mut document| {
+                    let distance_m = document.get_f64("distance_m").unwrap_or_default();
+                    document.remove("distance_m");
+                    bson::from_document::<Business>(document)
+                        .map(|business| NearbyBusiness {
+                            business,
+                            distance_m,
+                        })
+                        .map_err(|e| e.to_string())
+                });
+                match nearby {
+                    Ok(nearby) => businesses.push(nearby),
+                    Err(e) => error!(error = %e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(businesses)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find businesses")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn within_box_is_a_flat_box_from_south_west_to_north_east() {
+        assert_eq!(
+            within_box(-10.0, 35.0, 30.0, 60.0),
+            doc! { "location": { "$geoWithin": { "$box": [[-10.0, 35.0], [30.0, 60.0]] } } }
+        );
+    }
+
+    /// Needs MongoDB 5.1 or later at `MONGODB_URI`:
+    /// `cargo test -- --ignored within_box`.
+    #[tokio::test]
+    #[ignore]
+    async fn within_box_follows_parallels_on_wide_boxes() {
+        let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let client = Client::with_uri_str(uri).await.unwrap();
+        // Just north of the box's top edge, where a polygon edge from
+        // (-60, 50) to (60, 50) bows up past 60° north.
+        let points = [
+            ("inside", GeoPoint::new(0.0, 49.0)),
+            ("above the top edge", GeoPoint::new(0.0, 52.0)),
+            ("below the bottom edge", GeoPoint::new(0.0, -1.0)),
+            ("inside a corner", GeoPoint::new(59.0, 49.5)),
+        ];
+        let documents: Vec<Document> = points
+            .iter()
+            .map(|(name, point)| doc! { "name": *name, "location": bson::to_bson(point).unwrap() })
+            .collect();
+        let mut cursor = client
+            .database("cucura-ccdb")
+            .aggregate([
+                doc! { "$documents": documents },
+                doc! { "$match": within_box(-60.0, 0.0, 60.0, 50.0) },
+            ])
+            .await
+            .unwrap();
+        let mut found = Vec::new();
+        while let Some(document) = cursor.next().await {
+            found.push(document.unwrap().get_str("name").unwrap().to_string());
+        }
+        assert_eq!(found, ["inside", "inside a corner"]);
+    }
+}
@@ -0,0 +1,464 @@
+// routes/catalogue_routes.rs
+use std::collections::HashSet;
+
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    bson::{self, doc, Bson},
+    options::ReturnDocument,
+    Client, Collection,
+};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use super::membership_routes::role_of;
+use super::PaginationParams;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::media;
+use crate::models::business::{Business, BusinessRole};
+use crate::models::catalogue::{Availability, CatalogueItem, ItemKind, Price};
+use crate::models::payment::PaymentItem;
+
+const MAX_IMAGES: usize = 10;
+const MAX_CATEGORIES: usize = 10;
+const MAX_CATEGORY_LENGTH: usize = 40;
+const MAX_PRICE: i64 = 1_000_000_000_000;
+const MAX_ORDER_LINES: usize = 100;
+const MAX_QUANTITY: u32 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct NewItem {
+    pub kind: ItemKind,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: Price,
+    #[serde(default)]
+    pub images: Vec<String>,
+    pub availability: Option<Availability>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemPatch {
+    pub kind: Option<ItemKind>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub price: Option<Price>,
+    pub images: Option<Vec<String>>,
+    pub availability: Option<Availability>,
+    pub categories: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogueQuery {
+    pub kind: Option<ItemKind>,
+    pub availability: Option<Availability>,
+    pub category: Option<String>,
+}
+
+/// One line of a payment, by catalogue item.
+#[derive(Debug, Deserialize)]
+pub struct OrderLine {
+    pub item_id: Uuid,
+    pub quantity: u32,
+}
+
+/// Catalogue lines of a payment, priced from the catalogue.
+pub struct PricedOrder {
+    pub business: Business,
+    pub items: Vec<PaymentItem>,
+    pub total: Price,
+}
+
+pub fn catalogue_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/catalogue")
+            .route(web::get().to(list_items))
+            .route(web::post().to(create_item)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/catalogue/{item_id}")
+            .route(web::get().to(get_item))
+            .route(web::patch().to(patch_item))
+            .route(web::delete().to(remove_item)),
+    );
+}
+
+fn items(client: &Client) -> Collection<CatalogueItem> {
+    client.database("cucura-ccdb").collection("catalogue_items")
+}
+
+fn businesses(client: &Client) -> Collection<Business> {
+    client.database("cucura-ccdb").collection("businesses")
+}
+
+/// Decimal places of `currency`'s minor unit, per ISO 4217.
+pub fn minor_unit_digits(currency: &str) -> i32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+fn check_price(price: &mut Price) -> Result<(), &'static str> {
+    price.currency = price.currency.trim().to_uppercase();
+    if price.currency.len() != 3 || !price.currency.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err("currency must be a three-letter ISO 4217 code");
+    }
+    if !(0..=MAX_PRICE).contains(&price.amount) {
+        return Err("price must be a non-negative amount in minor units");
+    }
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<(), &'static str> {
+    if name.trim().is_empty() {
+        return Err("name must not be empty");
+    }
+    Ok(())
+}
+
+/// Uploaded images or http(s) links.
+fn check_images(images: &[String]) -> Result<(), &'static str> {
+    if images.len() > MAX_IMAGES {
+        return Err("An item can have at most 10 images");
+    }
+    let uploads = media::public_url("");
+    if !images.iter().all(|url| {
+        url.starts_with("https://") || url.starts_with("http://") || url.starts_with(&uploads)
+    }) {
+        return Err("images must be http(s) links");
+    }
+    Ok(())
+}
+
+/// Trimmed and without repeats, ignoring case.
+fn normalize_categories(categories: Vec<String>) -> Result<Vec<String>, &'static str> {
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::new();
+    for category in categories {
+        let category = category.trim().to_string();
+        if category.is_empty() || category.chars().count() > MAX_CATEGORY_LENGTH {
+            return Err("categories must be between 1 and 40 characters");
+        }
+        if seen.insert(category.to_lowercase()) {
+            normalized.push(category);
+        }
+    }
+    if normalized.len() > MAX_CATEGORIES {
+        return Err("An item can have at most 10 categories");
+    }
+    Ok(normalized)
+}
+
+async fn find_business(client: &Client, business_id: Uuid) -> Result<Business, HttpResponse> {
+    match businesses(client)
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => Ok(business),
+        Ok(None) => Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find business"))
+        }
+    }
+}
+
+/// The business, if `user` may change its catalogue (owners, admins and editors).
+async fn editable_business(
+    client: &Client,
+    business_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<Business, HttpResponse> {
+    let business = find_business(client, business_id).await?;
+    match role_of(&business, user.user_id) {
+        Some(BusinessRole::Owner | BusinessRole::Admin | BusinessRole::Editor) => Ok(business),
+        _ => Err(HttpResponse::Forbidden()
+            .json("Only owners, admins and editors can change the catalogue")),
+    }
+}
+
+/// Looks up and prices `lines`, which must all be available items of one
+/// business in one currency.
+pub async fn price_order(
+    client: &Client,
+    lines: &[OrderLine],
+) -> Result<PricedOrder, HttpResponse> {
+    if lines.len() > MAX_ORDER_LINES {
+        return Err(HttpResponse::BadRequest().json("A payment can have at most 100 items"));
+    }
+    if lines
+        .iter()
+        .any(|line| line.quantity == 0 || line.quantity > MAX_QUANTITY)
+    {
+        return Err(HttpResponse::BadRequest().json("quantity must be between 1 and 1000"));
+    }
+    let ids: HashSet<Uuid> = lines.iter().map(|line| line.item_id).collect();
+    if ids.len() != lines.len() {
+        return Err(HttpResponse::BadRequest().json("Each item may only be listed once"));
+    }
+
+    let ids: Vec<Bson> = ids.into_iter().map(uuid_bson).collect();
+    let mut cursor = match items(client).find(doc! { "item_id": { "$in": ids } }).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return Err(HttpResponse::InternalServerError().json("Failed to find items"));
+        }
+    };
+    let mut found = Vec::new();
+    while let Some(result) = cursor.next().await {
+        match result {
+            Ok(item) => found.push(item),
+            Err(e) => {
+                error!(error = ?e, "Failed to read document");
+                return Err(HttpResponse::InternalServerError().json("Failed to find items"));
+            }
+        }
+    }
+
+    let mut priced = Vec::new();
+    let mut total = 0;
+    for line in lines {
+        let Some(item) = found.iter().find(|item| item.item_id == line.item_id) else {
+            return Err(HttpResponse::BadRequest()
+                .json(format!("Catalogue item {} not found", line.item_id)));
+        };
+        if item.business_id != found[0].business_id {
+            return Err(
+                HttpResponse::BadRequest().json("All items must come from the same business")
+            );
+        }
+        if item.price.currency != found[0].price.currency {
+            return Err(HttpResponse::BadRequest().json("All items must be priced in one currency"));
+        }
+        if item.availability != Availability::Available {
+            return Err(HttpResponse::Conflict().json(format!("{} is not available", item.name)));
+        }
+        total += item.price.amount * i64::from(line.quantity);
+        priced.push(PaymentItem {
+            item_id: item.item_id,
+            name: item.name.clone(),
+            quantity: line.quantity,
+            unit_price: item.price.clone(),
+        });
+    }
+    let Some(first) = found.first() else {
+        return Err(HttpResponse::BadRequest().json("No items given"));
+    };
+    let business = find_business(client, first.business_id).await?;
+    Ok(PricedOrder {
+        business,
+        items: priced,
+        total: Price {
+            amount: total,
+            currency: first.price.currency.clone(),
+        },
+    })
+}
+
+/// The business's items, by name. Open to everyone.
+pub async fn list_items(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    query: web::Query<CatalogueQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    if let Err(response) = find_business(&client, business_id).await {
+        return response;
+    }
+    let mut filter = doc! { "business_id": uuid_bson(business_id) };
+    if let Some(kind) = query.kind {
+        filter.insert("kind", bson::to_bson(&kind).unwrap());
+    }
+    if let Some(availability) = query.availability {
+        filter.insert("availability", bson::to_bson(&availability).unwrap());
+    }
+    if let Some(category) = &query.category {
+        filter.insert("categories", category.trim());
+    }
+
+    let cursor = items(&client)
+        .find(filter)
+        .sort(doc! { "name": 1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut found = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(item) => found.push(item),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(found)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find items")
+        }
+    }
+}
+
+pub async fn create_item(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    item: web::Json<NewItem>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let mut item = item.into_inner();
+    let categories = match check_name(&item.name)
+        .and_then(|_| check_price(&mut item.price))
+        .and_then(|_| check_images(&item.images))
+        .and_then(|_| normalize_categories(item.categories))
+    {
+        Ok(categories) => categories,
+        Err(message) => return HttpResponse::BadRequest().json(message),
+    };
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+
+    let new_item = CatalogueItem {
+        item_id: Uuid::new_v4(),
+        business_id,
+        kind: item.kind,
+        name: item.name.trim().to_string(),
+        description: item.description,
+        price: item.price,
+        images: item.images,
+        availability: item.availability.unwrap_or(Availability::Available),
+        categories,
+        created_at: Utc::now().to_string(),
+        updated_at: Utc::now().to_string(),
+    };
+    match items(&client).insert_one(&new_item).await {
+        Ok(_) => HttpResponse::Created()
+            .insert_header((
+                header::LOCATION,
+                format!(
+                    "/v2/businesses/{}/catalogue/{}",
+                    business_id, new_item.item_id
+                ),
+            ))
+            .json(new_item),
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to add item")
+        }
+    }
+}
+
+pub async fn get_item(client: web::Data<Client>, path: web::Path<(Uuid, Uuid)>) -> impl Responder {
+    let (business_id, item_id) = path.into_inner();
+    let filter = doc! { "business_id": uuid_bson(business_id), "item_id": uuid_bson(item_id) };
+
+    match items(&client).find_one(filter).await {
+        Ok(Some(item)) => HttpResponse::Ok().json(item),
+        Ok(None) => HttpResponse::NotFound().json("Item not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find item")
+        }
+    }
+}
+
+pub async fn patch_item(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+    patch: web::Json<ItemPatch>,
+) -> impl Responder {
+    let (business_id, item_id) = path.into_inner();
+    let patch = patch.into_inner();
+
+    let mut set = doc! { "updated_at": Utc::now().to_string() };
+    if let Some(kind) = patch.kind {
+        set.insert("kind", bson::to_bson(&kind).unwrap());
+    }
+    if let Some(name) = &patch.name {
+        if let Err(message) = check_name(name) {
+            return HttpResponse::BadRequest().json(message);
+        }
+        set.insert("name", name.trim());
+    }
+    if let Some(description) = patch.description {
+        set.insert("description", description);
+    }
+    if let Some(mut price) = patch.price {
+        if let Err(message) = check_price(&mut price) {
+            return HttpResponse::BadRequest().json(message);
+        }
+        set.insert("price", bson::to_bson(&price).unwrap());
+    }
+    if let Some(images) = &patch.images {
+        if let Err(message) = check_images(images) {
+            return HttpResponse::BadRequest().json(message);
+        }
+        set.insert("images", images);
+    }
+    if let Some(availability) = patch.availability {
+        set.insert("availability", bson::to_bson(&availability).unwrap());
+    }
+    if let Some(categories) = patch.categories {
+        match normalize_categories(categories) {
+            Ok(categories) => set.insert("categories", categories),
+            Err(message) => return HttpResponse::BadRequest().json(message),
+        };
+    }
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+
+    let update_result = items(&client)
+        .find_one_and_update(
+            doc! { "business_id": uuid_bson(business_id), "item_id": uuid_bson(item_id) },
+            doc! { "$set": set },
+        )
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(item)) => HttpResponse::Ok().json(item),
+        Ok(None) => HttpResponse::NotFound().json("Item not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update item")
+        }
+    }
+}
+
+/// Payments keep their own copy of the items paid for, so items can be
+/// removed at any time; mark an item discontinued to keep it listed.
+pub async fn remove_item(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (business_id, item_id) = path.into_inner();
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+    let filter = doc! { "business_id": uuid_bson(business_id), "item_id": uuid_bson(item_id) };
+
+    match items(&client).delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => HttpResponse::NotFound().json("Item not found"),
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete item")
+        }
+    }
+}
@@ -0,0 +1,252 @@
+// routes/category_routes.rs
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::Utc;
+use mongodb::{
+    bson::{self, doc},
+    options::ReturnDocument,
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::auth::Admin;
+use crate::models::category::Category;
+use crate::taxonomy;
+
+#[derive(Debug, Deserialize)]
+pub struct NewCategory {
+    pub code: String,
+    pub name: String,
+    pub parent_code: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub requires_verified_purchase: bool,
+}
+
+/// Codes and parents are fixed once created: businesses and child codes
+/// refer to them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requires_verified_purchase: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryQuery {
+    /// Only the direct children of this code; the whole taxonomy by default.
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CategoryListing {
+    #[serde(flatten)]
+    pub category: Category,
+    /// Businesses in this category or any of its subcategories.
+    pub business_count: u64,
+}
+
+pub fn category_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/categories")
+            .route(web::get().to(list_categories))
+            .route(web::post().to(create_category)),
+    )
+    .service(
+        web::resource("/categories/{code}")
+            .route(web::get().to(find_category))
+            .route(web::patch().to(patch_category))
+            .route(web::delete().to(delete_category)),
+    );
+}
+
+fn categories(client: &Client) -> Collection<Category> {
+    client.database("cucura-ccdb").collection("categories")
+}
+
+pub async fn list_categories(
+    client: web::Data<Client>,
+    query: web::Query<CategoryQuery>,
+) -> impl Responder {
+    let db = client.database("cucura-ccdb");
+    let listing = async {
+        let categories = taxonomy::load(&db).await?;
+        let counts = taxonomy::business_counts(&db, &categories).await?;
+        Ok::<_, mongodb::error::Error>((categories, counts))
+    };
+    match listing.await {
+        Ok((categories, counts)) => {
+            let listings: Vec<CategoryListing> = categories
+                .into_iter()
+                .filter(|category| query.parent.is_none() || category.parent_code == query.parent)
+                .map(|category| CategoryListing {
+                    business_count: counts.get(&category.code).copied().unwrap_or(0),
+                    category,
+                })
+                .collect();
+            HttpResponse::Ok().json(listings)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find categories")
+        }
+    }
+}
+
+pub async fn find_category(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
+    let code = path.into_inner();
+    let db = client.database("cucura-ccdb");
+    let listing = async {
+        let categories = taxonomy::load(&db).await?;
+        let counts = taxonomy::business_counts(&db, &categories).await?;
+        Ok::<_, mongodb::error::Error>(taxonomy::find(&code, &categories).map(|category| {
+            CategoryListing {
+                business_count: counts.get(&category.code).copied().unwrap_or(0),
+                category: category.clone(),
+            }
+        }))
+    };
+    match listing.await {
+        Ok(Some(listing)) => HttpResponse::Ok().json(listing),
+        Ok(None) => HttpResponse::NotFound().json("Category not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find category")
+        }
+    }
+}
+
+pub async fn create_category(
+    admin: Admin,
+    client: web::Data<Client>,
+    category: web::Json<NewCategory>,
+) -> impl Responder {
+    let new_category = category.into_inner();
+    if new_category.name.trim().is_empty() {
+        return HttpResponse::BadRequest().json("name must not be empty");
+    }
+    let existing = match taxonomy::load(&client.database("cucura-ccdb")).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to create category");
+        }
+    };
+    let parent = match &new_category.parent_code {
+        Some(parent_code) => match taxonomy::find(parent_code, &existing) {
+            Some(parent) => Some(parent),
+            None => return HttpResponse::BadRequest().json("Parent category not found"),
+        },
+        None => None,
+    };
+    if taxonomy::find(&new_category.code, &existing).is_some() {
+        return HttpResponse::Conflict().json("Category already exists");
+    }
+    if let Err(message) = taxonomy::check_new_code(&new_category.code, parent, &existing) {
+        return HttpResponse::BadRequest().json(message);
+    }
+
+    let mut category = Category::new(
+        new_category.code,
+        new_category.name.trim().to_string(),
+        new_category.parent_code,
+    );
+    category.keywords = new_category.keywords;
+    category.requires_verified_purchase = new_category.requires_verified_purchase;
+    match categories(&client).insert_one(&category).await {
+        Ok(_) => {
+            info!(code = %category.code, admin = %admin.0.user_id, "Created category");
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/categories/{}", category.code),
+                ))
+                .json(category)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to create category")
+        }
+    }
+}
+
+pub async fn patch_category(
+    admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+    patch: web::Json<CategoryPatch>,
+) -> impl Responder {
+    if patch
+        .name
+        .as_ref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        return HttpResponse::BadRequest().json("name must not be empty");
+    }
+    let mut set = bson::to_document(&patch.into_inner()).unwrap();
+    set.insert("updated_at", Utc::now().to_string());
+    match categories(&client)
+        .find_one_and_update(doc! { "code": path.into_inner() }, doc! { "$set": set })
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(category)) => {
+            info!(code = %category.code, admin = %admin.0.user_id, "Updated category");
+            HttpResponse::Ok().json(category)
+        }
+        Ok(None) => HttpResponse::NotFound().json("Category not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update category")
+        }
+    }
+}
+
+/// Only leaf categories no business uses can be deleted; reassign first.
+pub async fn delete_category(
+    admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let code = path.into_inner();
+    let in_use = async {
+        let children = categories(&client)
+            .count_documents(doc! { "parent_code": &code })
+            .await?;
+        let businesses = client
+            .database("cucura-ccdb")
+            .collection::<bson::Document>("businesses")
+            .count_documents(doc! { "category_codes": &code })
+            .await?;
+        Ok::<_, mongodb::error::Error>(children + businesses > 0)
+    };
+    match in_use.await {
+        Ok(true) => {
+            return HttpResponse::Conflict()
+                .json("Category has subcategories or businesses assigned")
+        }
+        Ok(false) => (),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to delete category");
+        }
+    }
+
+    match categories(&client).delete_one(doc! { "code": &code }).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("Category not found")
+        }
+        Ok(_) => {
+            info!(code = %code, admin = %admin.0.user_id, "Deleted category");
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete category")
+        }
+    }
+}
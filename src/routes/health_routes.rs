@@ -0,0 +1,177 @@
+// routes/health_routes.rs
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, HttpResponse, Responder};
+use chrono::DateTime;
+use mongodb::{bson::doc, Client};
+use serde_json::json;
+use tracing::error;
+
+use crate::migrations;
+
+const MONGO_PING_TIMEOUT: Duration = Duration::from_secs(2);
+/// What an unauthenticated caller is told; the driver's own error names
+/// hosts and replica set members, so it only goes to the logs.
+const DATABASE_UNAVAILABLE: &str = "database unavailable";
+
+/// Flipped when a shutdown signal arrives so the orchestrator stops routing
+/// traffic here while in-flight requests drain.
+#[derive(Default)]
+pub struct HealthState {
+    pub draining: AtomicBool,
+}
+
+pub fn health_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/health/live").route(web::get().to(live)))
+        .service(web::resource("/health/ready").route(web::get().to(ready)))
+        .service(web::resource("/version").route(web::get().to(version)));
+}
+
+pub async fn live() -> impl Responder {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+pub async fn ready(client: web::Data<Client>, state: web::Data<HealthState>) -> impl Responder {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(
+        MONGO_PING_TIMEOUT,
+        client.database("admin").run_command(doc! { "ping": 1 }),
+    )
+    .await;
+    let (mongo_up, mongo) = match ping {
+        Ok(Ok(_)) => (
+            true,
+            json!({ "status": "up", "latency_ms": started.elapsed().as_millis() as u64 }),
+        ),
+        Ok(Err(e)) => {
+            error!(error = %e, "Readiness ping failed");
+            (
+                false,
+                json!({ "status": "down", "error": DATABASE_UNAVAILABLE }),
+            )
+        }
+        Err(_) => (
+            false,
+            json!({ "status": "down", "error": "ping timed out" }),
+        ),
+    };
+
+    let (migrations_up, migrations) = if mongo_up {
+        match migrations::pending(&client).await {
+            Ok(pending) => (
+                pending.is_empty(),
+                json!({ "status": if pending.is_empty() { "up" } else { "pending" }, "pending": pending }),
+            ),
+            Err(e) => {
+                error!(error = %e, "Failed to read applied migrations");
+                (
+                    false,
+                    json!({ "status": "down", "error": DATABASE_UNAVAILABLE }),
+                )
+            }
+        }
+    } else {
+        (false, json!({ "status": "unknown" }))
+    };
+
+    let draining = state.draining.load(Ordering::Relaxed);
+    let body = json!({
+        "status": if mongo_up && migrations_up && !draining { "ready" } else { "unavailable" },
+        "draining": draining,
+        "checks": { "mongo": mongo, "migrations": migrations },
+    });
+    if mongo_up && migrations_up && !draining {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+pub async fn version() -> impl Responder {
+    let build_time = env!("BUILD_TIMESTAMP")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|time| time.to_rfc3339());
+    HttpResponse::Ok().json(json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": env!("GIT_SHA"),
+        "build_time": build_time,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    /// A client for a port nothing listens on, giving up on it quickly.
+    async fn unreachable_client() -> Client {
+        Client::with_uri_str("mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=100")
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn live_always_answers() {
+        let app = test::init_service(App::new().configure(health_routes)).await;
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/health/live").to_request(),
+        )
+        .await;
+        assert!(response.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn ready_reports_the_database_down_without_its_error() {
+        let state = web::Data::new(HealthState::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_client().await))
+                .app_data(state.clone())
+                .configure(health_routes),
+        )
+        .await;
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 503);
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["draining"], false);
+        assert_eq!(body["checks"]["mongo"]["status"], "down");
+        assert_eq!(body["checks"]["mongo"]["error"], DATABASE_UNAVAILABLE);
+        assert_eq!(body["checks"]["migrations"]["status"], "unknown");
+        assert!(!body.to_string().contains("127.0.0.1"));
+    }
+
+    #[actix_web::test]
+    async fn ready_reports_draining() {
+        let state = web::Data::new(HealthState::default());
+        state.draining.store(true, Ordering::Relaxed);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_client().await))
+                .app_data(state.clone())
+                .configure(health_routes),
+        )
+        .await;
+        let request = test::TestRequest::get().uri("/health/ready").to_request();
+        let body: Value = test::read_body_json(test::call_service(&app, request).await).await;
+        assert_eq!(body["draining"], true);
+        assert_eq!(body["status"], "unavailable");
+    }
+
+    #[actix_web::test]
+    async fn version_names_the_build() {
+        let app = test::init_service(App::new().configure(health_routes)).await;
+        let request = test::TestRequest::get().uri("/version").to_request();
+        let body: Value = test::read_body_json(test::call_service(&app, request).await).await;
+        assert_eq!(body["name"], env!("CARGO_PKG_NAME"));
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["git_sha"].is_string());
+    }
+}
@@ -0,0 +1,478 @@
+// routes/media_routes.rs
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    bson::{self, doc, Bson},
+    Client, Collection,
+};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use super::membership_routes::role_of;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::media::{self, Media, ProcessError, Purpose, Storage};
+use crate::models::business::{Business, BusinessRole};
+use crate::models::profile::Profile;
+
+const MAX_PICTURES: usize = 20;
+
+pub fn media_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/logo")
+            .route(web::put().to(upload_logo))
+            .route(web::delete().to(remove_logo)),
+    )
+    .service(web::resource("/businesses/{business_id}/pictures").route(web::post().to(add_picture)))
+    .service(
+        web::resource("/businesses/{business_id}/pictures/{media_id}")
+            .route(web::delete().to(remove_picture)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/media").route(web::get().to(list_business_media)),
+    )
+    .service(
+        web::resource("/profiles/{profile_id}/avatar")
+            .route(web::put().to(upload_avatar))
+            .route(web::delete().to(remove_avatar)),
+    )
+    .service(web::resource("/media/{key:.*}").route(web::get().to(serve_media)));
+}
+
+fn businesses(client: &Client) -> Collection<Business> {
+    client.database("cucura-ccdb").collection("businesses")
+}
+
+fn profiles(client: &Client) -> Collection<Profile> {
+    client.database("cucura-ccdb").collection("profiles")
+}
+
+/// The business, if `user` may change its images (owners, admins and editors).
+async fn editable_business(
+    client: &Client,
+    business_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<Business, HttpResponse> {
+    let business = match businesses(client)
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => business,
+        Ok(None) => return Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return Err(HttpResponse::InternalServerError().json("Failed to find business"));
+        }
+    };
+    match role_of(&business, user.user_id) {
+        Some(BusinessRole::Owner | BusinessRole::Admin | BusinessRole::Editor) => Ok(business),
+        _ => {
+            Err(HttpResponse::Forbidden().json("Only owners, admins and editors can change images"))
+        }
+    }
+}
+
+/// The profile, if it is `user`'s own (or `user` is an admin).
+async fn editable_profile(
+    client: &Client,
+    profile_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<Profile, HttpResponse> {
+    match profiles(client)
+        .find_one(doc! { "profile_id": uuid_bson(profile_id) })
+        .await
+    {
+        Ok(Some(profile)) if profile.user_id == user.user_id || user.is_admin() => Ok(profile),
+        Ok(Some(_)) => Err(HttpResponse::Forbidden().json("Not your profile")),
+        Ok(None) => Err(HttpResponse::NotFound().json("Profile not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find profile"))
+        }
+    }
+}
+
+/// The contents of the `file` field, refusing anything over `max_bytes`
+/// before it is fully received.
+async fn read_file(mut payload: Multipart, max_bytes: usize) -> Result<Vec<u8>, HttpResponse> {
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|_| HttpResponse::BadRequest().json("Malformed upload"))?;
+        if field.name() != Some("file") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|_| HttpResponse::BadRequest().json("Malformed upload"))?;
+            if bytes.len() + chunk.len() > max_bytes {
+                return Err(HttpResponse::PayloadTooLarge().json(format!(
+                    "Images must be at most {} MB",
+                    max_bytes / 1024 / 1024
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok(bytes);
+    }
+    Err(HttpResponse::BadRequest().json("Expected an image in a multipart field named file"))
+}
+
+/// Reads, processes and stores an upload; the caller then points its owner
+/// at the returned record's url.
+async fn upload(
+    client: &Client,
+    storage: &dyn Storage,
+    payload: Multipart,
+    purpose: Purpose,
+    owner_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<Media, HttpResponse> {
+    let bytes = read_file(payload, purpose.max_bytes()).await?;
+    let processed = match web::block(move || media::process(&bytes, purpose)).await {
+        Ok(Ok(processed)) => processed,
+        Ok(Err(ProcessError::Unsupported)) => {
+            return Err(HttpResponse::UnsupportedMediaType()
+                .json("Only JPEG, PNG, GIF and WebP images are accepted"))
+        }
+        Ok(Err(ProcessError::Invalid(e))) => {
+            warn!(error = %e, "Rejected unreadable image");
+            return Err(HttpResponse::BadRequest().json("Could not read the image"));
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to process image");
+            return Err(HttpResponse::InternalServerError().json("Failed to upload image"));
+        }
+    };
+    media::store(client, storage, purpose, owner_id, user.user_id, processed)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Failed to store image");
+            HttpResponse::InternalServerError().json("Failed to upload image")
+        })
+}
+
+/// Replaces the business's logo; the previous upload, if any, is deleted.
+pub async fn upload_logo(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+    payload: Multipart,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+    let logo = match upload(
+        &client,
+        storage.get_ref(),
+        payload,
+        Purpose::Logo,
+        business_id,
+        &user,
+    )
+    .await
+    {
+        Ok(logo) => logo,
+        Err(response) => return response,
+    };
+
+    let update = doc! { "$set": { "logo": &logo.url, "updated_at": Utc::now().to_string() } };
+    if let Err(e) = businesses(&client)
+        .update_one(doc! { "business_id": uuid_bson(business_id) }, update)
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+        media::release(
+            &client,
+            storage.get_ref(),
+            doc! { "media_id": uuid_bson(logo.media_id) },
+        )
+        .await;
+        return HttpResponse::InternalServerError().json("Failed to upload image");
+    }
+    let replaced = doc! {
+        "purpose": "logo",
+        "owner_id": uuid_bson(business_id),
+        "media_id": { "$ne": uuid_bson(logo.media_id) },
+    };
+    media::release(&client, storage.get_ref(), replaced).await;
+    HttpResponse::Ok().json(logo)
+}
+
+pub async fn remove_logo(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+    let update = doc! { "$set": { "logo": Bson::Null, "updated_at": Utc::now().to_string() } };
+    if let Err(e) = businesses(&client)
+        .update_one(doc! { "business_id": uuid_bson(business_id) }, update)
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+        return HttpResponse::InternalServerError().json("Failed to remove logo");
+    }
+    let logos = doc! { "purpose": "logo", "owner_id": uuid_bson(business_id) };
+    media::release(&client, storage.get_ref(), logos).await;
+    HttpResponse::NoContent().finish()
+}
+
+pub async fn add_picture(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+    payload: Multipart,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let business = match editable_business(&client, business_id, &user).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    let full = || {
+        HttpResponse::Conflict().json(format!(
+            "A business can have at most {} pictures",
+            MAX_PICTURES
+        ))
+    };
+    if business.pictures.as_ref().map_or(0, Vec::len) >= MAX_PICTURES {
+        return full();
+    }
+    let picture = match upload(
+        &client,
+        storage.get_ref(),
+        payload,
+        Purpose::Picture,
+        business_id,
+        &user,
+    )
+    .await
+    {
+        Ok(picture) => picture,
+        Err(response) => return response,
+    };
+
+    // Checked again here: other uploads may have finished meanwhile.
+    let filter = doc! {
+        "business_id": uuid_bson(business_id),
+        format!("pictures.{}", MAX_PICTURES - 1): { "$exists": false },
+    };
+    let update = vec![doc! { "$set": {
+        "pictures": { "$concatArrays": [{ "$ifNull": ["$pictures", []] }, [&picture.url]] },
+        "updated_at": Utc::now().to_string(),
+    } }];
+    let release = || {
+        media::release(
+            &client,
+            storage.get_ref(),
+            doc! { "media_id": uuid_bson(picture.media_id) },
+        )
+    };
+    match businesses(&client).update_one(filter, update).await {
+        Ok(result) if result.matched_count == 0 => {
+            release().await;
+            full()
+        }
+        Ok(_) => HttpResponse::Created().json(&picture),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            release().await;
+            HttpResponse::InternalServerError().json("Failed to upload image")
+        }
+    }
+}
+
+pub async fn remove_picture(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (business_id, media_id) = path.into_inner();
+    if let Err(response) = editable_business(&client, business_id, &user).await {
+        return response;
+    }
+    let filter = doc! {
+        "media_id": uuid_bson(media_id),
+        "purpose": "picture",
+        "owner_id": uuid_bson(business_id),
+    };
+    let picture = match media::media(&client).find_one(filter.clone()).await {
+        Ok(Some(picture)) => picture,
+        Ok(None) => return HttpResponse::NotFound().json("Picture not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to remove picture");
+        }
+    };
+    let update = doc! {
+        "$pull": { "pictures": &picture.url },
+        "$set": { "updated_at": Utc::now().to_string() },
+    };
+    if let Err(e) = businesses(&client)
+        .update_one(doc! { "business_id": uuid_bson(business_id) }, update)
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+        return HttpResponse::InternalServerError().json("Failed to remove picture");
+    }
+    media::release(&client, storage.get_ref(), filter).await;
+    HttpResponse::NoContent().finish()
+}
+
+/// The business's uploaded logo and pictures, with sizes and thumbnails.
+/// Images given as external URLs are not included.
+pub async fn list_business_media(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let business = match businesses(&client)
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => business,
+        Ok(None) => return HttpResponse::NotFound().json("Business not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to find media");
+        }
+    };
+    let mut cursor = match media::media(&client)
+        .find(
+            doc! { "owner_id": uuid_bson(business_id), "purpose": { "$in": ["logo", "picture"] } },
+        )
+        .sort(doc! { "created_at": 1 })
+        .await
+    {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to find media");
+        }
+    };
+    let pictures = business.pictures.unwrap_or_default();
+    let mut found = Vec::new();
+    while let Some(record) = cursor.next().await {
+        match record {
+            // Replaced uploads linger until the sweep; only list what is shown.
+            Ok(record)
+                if business.logo.as_ref() == Some(&record.url)
+                    || pictures.contains(&record.url) =>
+            {
+                found.push(record)
+            }
+            Ok(_) => (),
+            Err(e) => error!(error = ?e, "Failed to read document"),
+        }
+    }
+    HttpResponse::Ok().json(found)
+}
+
+/// Replaces the profile's avatar; the previous upload, if any, is deleted.
+pub async fn upload_avatar(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+    payload: Multipart,
+) -> impl Responder {
+    let profile_id = path.into_inner();
+    if let Err(response) = editable_profile(&client, profile_id, &user).await {
+        return response;
+    }
+    let avatar = match upload(
+        &client,
+        storage.get_ref(),
+        payload,
+        Purpose::Avatar,
+        profile_id,
+        &user,
+    )
+    .await
+    {
+        Ok(avatar) => avatar,
+        Err(response) => return response,
+    };
+
+    let update = doc! { "$set": {
+        "avatar": &avatar.url,
+        "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+    } };
+    if let Err(e) = profiles(&client)
+        .update_one(doc! { "profile_id": uuid_bson(profile_id) }, update)
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+        media::release(
+            &client,
+            storage.get_ref(),
+            doc! { "media_id": uuid_bson(avatar.media_id) },
+        )
+        .await;
+        return HttpResponse::InternalServerError().json("Failed to upload image");
+    }
+    let replaced = doc! {
+        "purpose": "avatar",
+        "owner_id": uuid_bson(profile_id),
+        "media_id": { "$ne": uuid_bson(avatar.media_id) },
+    };
+    media::release(&client, storage.get_ref(), replaced).await;
+    HttpResponse::Ok().json(avatar)
+}
+
+pub async fn remove_avatar(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let profile_id = path.into_inner();
+    if let Err(response) = editable_profile(&client, profile_id, &user).await {
+        return response;
+    }
+    let update = doc! { "$set": {
+        "avatar": Bson::Null,
+        "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+    } };
+    if let Err(e) = profiles(&client)
+        .update_one(doc! { "profile_id": uuid_bson(profile_id) }, update)
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+        return HttpResponse::InternalServerError().json("Failed to remove avatar");
+    }
+    let avatars = doc! { "purpose": "avatar", "owner_id": uuid_bson(profile_id) };
+    media::release(&client, storage.get_ref(), avatars).await;
+    HttpResponse::NoContent().finish()
+}
+
+/// Serves stored files when no `MEDIA_PUBLIC_URL` fronts the storage. Keys
+/// are never reused, so responses can be cached indefinitely.
+pub async fn serve_media(
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let key = path.into_inner();
+    if !media::valid_key(&key) {
+        return HttpResponse::NotFound().finish();
+    }
+    match storage.get(&key).await {
+        Ok(Some(bytes)) => HttpResponse::Ok()
+            .content_type(media::content_type(&key))
+            .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+            .body(bytes),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(error = %e, key = %key, "Failed to read media");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,682 @@
+// routes/membership_routes.rs
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use futures::StreamExt;
+use mongodb::{
+    bson::{self, doc, Document},
+    options::{Collation, CollationStrength},
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::auth::{new_token, token_hash, AuthenticatedUser};
+use crate::db::uuid_bson;
+use crate::mailer::{self, Email, Mailer};
+use crate::models::business::{Business, BusinessRole, Membership};
+use crate::models::notification::{Notification, NotificationType};
+use crate::models::user::User;
+
+const INVITATION_TTL_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Revoked,
+}
+
+/// An emailed offer to join a business. The invitee accepts or declines
+/// while signed in with the invited address, using the token from the
+/// email: account emails are not verified, so the address alone does not
+/// prove who someone is.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Invitation {
+    pub invitation_id: Uuid,
+    pub business_id: Uuid,
+    pub business_name: String,
+    /// Lowercased.
+    pub email: String,
+    /// SHA-256 of the token emailed to `email`; the token itself is never
+    /// stored, so listing invitations does not give it away. Empty for
+    /// invitations sent before tokens, which can no longer be answered.
+    #[serde(default)]
+    pub token_hash: String,
+    pub role: BusinessRole,
+    pub invited_by: Uuid,
+    pub status: InvitationStatus,
+    pub created_at: String,
+    pub expires_at: DateTime<Utc>,
+    pub responded_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewInvitation {
+    pub email: String,
+    pub role: BusinessRole,
+}
+
+/// What the invitee sends to accept or decline.
+#[derive(Debug, Deserialize)]
+pub struct InvitationResponse {
+    /// From the invitation email.
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleChange {
+    pub role: BusinessRole,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnershipTransfer {
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub username: Option<String>,
+    pub role: BusinessRole,
+    pub joined_at: String,
+}
+
+pub fn membership_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/members").route(web::get().to(list_members)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/members/{user_id}")
+            .route(web::patch().to(change_member_role))
+            .route(web::delete().to(remove_member)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/ownership")
+            .route(web::put().to(transfer_ownership)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/invitations")
+            .route(web::get().to(list_business_invitations))
+            .route(web::post().to(create_invitation)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/invitations/{invitation_id}")
+            .route(web::delete().to(revoke_invitation)),
+    )
+    .service(web::resource("/invitations").route(web::get().to(list_my_invitations)))
+    .service(
+        web::resource("/invitations/{invitation_id}/accept")
+            .route(web::post().to(accept_invitation)),
+    )
+    .service(
+        web::resource("/invitations/{invitation_id}/decline")
+            .route(web::post().to(decline_invitation)),
+    );
+}
+
+pub fn role_of(business: &Business, user_id: Uuid) -> Option<BusinessRole> {
+    business
+        .members
+        .iter()
+        .find(|member| member.user_id == user_id)
+        .map(|member| member.role)
+}
+
+/// Owners manage everyone; admins manage, and may grant, editor and viewer.
+fn manages(actor: BusinessRole, role: BusinessRole) -> bool {
+    match actor {
+        BusinessRole::Owner => true,
+        BusinessRole::Admin => matches!(role, BusinessRole::Editor | BusinessRole::Viewer),
+        BusinessRole::Editor | BusinessRole::Viewer => false,
+    }
+}
+
+/// Matches a business only while `user_id` could stop being an owner
+/// without leaving it ownerless: they are not an owner, or someone else is.
+fn keeps_an_owner(user_id: Uuid) -> Document {
+    doc! { "$or": [
+        { "members": { "$elemMatch": { "user_id": uuid_bson(user_id), "role": { "$ne": "owner" } } } },
+        { "members": { "$elemMatch": { "user_id": { "$ne": uuid_bson(user_id) }, "role": "owner" } } },
+    ] }
+}
+
+/// `expires_at` condition for invitations still open. Written in the format
+/// chrono serializes `expires_at` in, so the strings compare as times.
+fn unexpired() -> Document {
+    doc! { "$gt": Utc::now().to_rfc3339_opts(SecondsFormat::AutoSi, true) }
+}
+
+fn businesses(client: &Client) -> Collection<Business> {
+    client.database("cucura-ccdb").collection("businesses")
+}
+
+fn invitations(client: &Client) -> Collection<Invitation> {
+    client
+        .database("cucura-ccdb")
+        .collection("business_invitations")
+}
+
+/// The business and the caller's role in it, or the response to send.
+async fn membership(
+    client: &Client,
+    business_id: Uuid,
+    user: &AuthenticatedUser,
+) -> Result<(Business, BusinessRole), HttpResponse> {
+    match businesses(client)
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => match role_of(&business, user.user_id) {
+            Some(role) => Ok((business, role)),
+            None => Err(HttpResponse::Forbidden().json("Not a member of this business")),
+        },
+        Ok(None) => Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find business"))
+        }
+    }
+}
+
+pub async fn list_members(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business = match membership(&client, path.into_inner(), &user).await {
+        Ok((business, _)) => business,
+        Err(response) => return response,
+    };
+
+    let user_ids: Vec<_> = business
+        .members
+        .iter()
+        .map(|member| uuid_bson(member.user_id))
+        .collect();
+    let mut usernames = std::collections::HashMap::new();
+    match client
+        .database("cucura-ccdb")
+        .collection::<User>("users")
+        .find(doc! { "user_id": { "$in": user_ids } })
+        .await
+    {
+        Ok(mut cursor) => {
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(user) => {
+                        usernames.insert(user.user_id, user.username);
+                    }
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            return HttpResponse::InternalServerError().json("Failed to find members");
+        }
+    }
+
+    let members: Vec<Member> = business
+        .members
+        .into_iter()
+        .map(|member| Member {
+            username: usernames.remove(&member.user_id),
+            user_id: member.user_id,
+            role: member.role,
+            joined_at: member.joined_at,
+        })
+        .collect();
+    HttpResponse::Ok().json(members)
+}
+
+pub async fn change_member_role(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+    change: web::Json<RoleChange>,
+) -> impl Responder {
+    let (business_id, member_id) = path.into_inner();
+    let (business, actor) = match membership(&client, business_id, &user).await {
+        Ok(membership) => membership,
+        Err(response) => return response,
+    };
+    let Some(current) = role_of(&business, member_id) else {
+        return HttpResponse::NotFound().json("Member not found");
+    };
+    if !manages(actor, current) || !manages(actor, change.role) {
+        return HttpResponse::Forbidden().json("Not allowed to change this member's role");
+    }
+
+    let mut filter = doc! {
+        "business_id": uuid_bson(business_id),
+        "members.user_id": uuid_bson(member_id),
+    };
+    if change.role != BusinessRole::Owner {
+        filter.extend(keeps_an_owner(member_id));
+    }
+    let update =
+        doc! { "$set": { "members.$[member].role": bson::to_bson(&change.role).unwrap() } };
+    match businesses(&client)
+        .update_one(filter, update)
+        .array_filters(vec![doc! { "member.user_id": uuid_bson(member_id) }])
+        .await
+    {
+        Ok(result) if result.matched_count == 0 => {
+            HttpResponse::Conflict().json("A business must keep at least one owner")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to change role")
+        }
+    }
+}
+
+/// Removes a member, or lets the caller leave when it is their own id.
+pub async fn remove_member(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (business_id, member_id) = path.into_inner();
+    let (business, actor) = match membership(&client, business_id, &user).await {
+        Ok(membership) => membership,
+        Err(response) => return response,
+    };
+    let Some(current) = role_of(&business, member_id) else {
+        return HttpResponse::NotFound().json("Member not found");
+    };
+    if member_id != user.user_id && !manages(actor, current) {
+        return HttpResponse::Forbidden().json("Not allowed to remove this member");
+    }
+
+    let mut filter = doc! {
+        "business_id": uuid_bson(business_id),
+        "members.user_id": uuid_bson(member_id),
+    };
+    filter.extend(keeps_an_owner(member_id));
+    let update = doc! { "$pull": {
+        "members": { "user_id": uuid_bson(member_id) },
+        "user_ids": uuid_bson(member_id),
+    } };
+    match businesses(&client).update_one(filter, update).await {
+        Ok(result) if result.matched_count == 0 => HttpResponse::Conflict()
+            .json("A business must keep at least one owner; transfer ownership first"),
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to remove member")
+        }
+    }
+}
+
+/// Makes another member the owner; the caller stays on as an admin.
+pub async fn transfer_ownership(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    transfer: web::Json<OwnershipTransfer>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let (business, actor) = match membership(&client, business_id, &user).await {
+        Ok(membership) => membership,
+        Err(response) => return response,
+    };
+    if actor != BusinessRole::Owner {
+        return HttpResponse::Forbidden().json("Only an owner can transfer ownership");
+    }
+    if transfer.user_id == user.user_id {
+        return HttpResponse::BadRequest().json("Already the owner");
+    }
+    if role_of(&business, transfer.user_id).is_none() {
+        return HttpResponse::BadRequest().json("The new owner must already be a member");
+    }
+
+    let filter = doc! {
+        "business_id": uuid_bson(business_id),
+        "members": { "$elemMatch": { "user_id": uuid_bson(user.user_id), "role": "owner" } },
+        "members.user_id": uuid_bson(transfer.user_id),
+    };
+    let update = doc! { "$set": {
+        "members.$[new].role": "owner",
+        "members.$[old].role": "admin",
+    } };
+    match businesses(&client)
+        .update_one(filter, update)
+        .array_filters(vec![
+            doc! { "new.user_id": uuid_bson(transfer.user_id) },
+            doc! { "old.user_id": uuid_bson(user.user_id) },
+        ])
+        .await
+    {
+        Ok(result) if result.matched_count == 0 => {
+            HttpResponse::Conflict().json("Membership changed; try again")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to transfer ownership")
+        }
+    }
+}
+
+pub async fn create_invitation(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    mailer: web::Data<dyn Mailer>,
+    path: web::Path<Uuid>,
+    invitation: web::Json<NewInvitation>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let (business, actor) = match membership(&client, business_id, &user).await {
+        Ok(membership) => membership,
+        Err(response) => return response,
+    };
+    if !manages(actor, invitation.role) {
+        return HttpResponse::Forbidden().json("Not allowed to invite with this role");
+    }
+    let email = invitation.email.trim().to_lowercase();
+    if !email.contains('@') {
+        return HttpResponse::BadRequest().json("Invalid email");
+    }
+
+    // Emails are compared case-insensitively.
+    let invitee = client
+        .database("cucura-ccdb")
+        .collection::<User>("users")
+        .find_one(doc! { "email": &email })
+        .collation(
+            Collation::builder()
+                .locale("en")
+                .strength(CollationStrength::Secondary)
+                .build(),
+        )
+        .await;
+    let invitee = match invitee {
+        Ok(invitee) => invitee,
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to invite member");
+        }
+    };
+    if let Some(invitee) = &invitee {
+        if role_of(&business, invitee.user_id).is_some() {
+            return HttpResponse::Conflict().json("Already a member");
+        }
+    }
+    let pending = doc! {
+        "business_id": uuid_bson(business_id),
+        "email": &email,
+        "status": "pending",
+        "expires_at": unexpired(),
+    };
+    match invitations(&client).find_one(pending).await {
+        Ok(Some(_)) => return HttpResponse::Conflict().json("Already invited"),
+        Ok(None) => (),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to invite member");
+        }
+    }
+
+    let token = new_token();
+    let new_invitation = Invitation {
+        invitation_id: Uuid::new_v4(),
+        business_id,
+        business_name: business.name.clone(),
+        email,
+        token_hash: token_hash(&token),
+        role: invitation.role,
+        invited_by: user.user_id,
+        status: InvitationStatus::Pending,
+        created_at: Utc::now().to_string(),
+        expires_at: Utc::now() + Duration::days(INVITATION_TTL_DAYS),
+        responded_at: None,
+    };
+    if let Err(e) = invitations(&client).insert_one(&new_invitation).await {
+        error!(error = %e, "Failed to insert document");
+        return HttpResponse::InternalServerError().json("Failed to invite member");
+    }
+
+    let link = mailer::app_link(&format!(
+        "/invitations/{}?token={}",
+        new_invitation.invitation_id, token
+    ));
+    let email = Email {
+        to: new_invitation.email.clone(),
+        subject: format!("You're invited to join {} on Cucura", business.name),
+        body: format!(
+            "You have been invited to join {} as {}. Accept or decline at {} within {} days.",
+            business.name,
+            format!("{:?}", new_invitation.role).to_lowercase(),
+            link,
+            INVITATION_TTL_DAYS
+        ),
+    };
+    // The invitation stands even if delivery fails; it is listed in the app.
+    if let Err(e) = mailer.send(&email).await {
+        warn!(error = %e, "Failed to send invitation email");
+    }
+    if let Some(invitee) = invitee {
+        let notification = Notification::new(
+            NotificationType::SomeoneInvitedToBusiness,
+            invitee.user_id,
+            format!("You have been invited to join {}", business.name),
+        );
+        if let Err(e) = client
+            .database("cucura-ccdb")
+            .collection::<Notification>("notifications")
+            .insert_one(notification)
+            .await
+        {
+            warn!(error = %e, "Failed to insert notification");
+        }
+    }
+
+    HttpResponse::Created()
+        .insert_header((
+            header::LOCATION,
+            format!(
+                "/v2/businesses/{}/invitations/{}",
+                business_id, new_invitation.invitation_id
+            ),
+        ))
+        .json(new_invitation)
+}
+
+async fn collect_invitations(
+    client: &Client,
+    filter: Document,
+) -> mongodb::error::Result<Vec<Invitation>> {
+    let mut cursor = invitations(client)
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .await?;
+    let mut found = Vec::new();
+    while let Some(invitation) = cursor.next().await {
+        match invitation {
+            Ok(invitation) => found.push(invitation),
+            Err(e) => error!(error = ?e, "Failed to read document"),
+        }
+    }
+    Ok(found)
+}
+
+/// Pending, unexpired invitations to the business; admins and owners only.
+pub async fn list_business_invitations(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    match membership(&client, business_id, &user).await {
+        Ok((_, BusinessRole::Owner | BusinessRole::Admin)) => (),
+        Ok(_) => {
+            return HttpResponse::Forbidden().json("Only owners and admins can see invitations")
+        }
+        Err(response) => return response,
+    }
+    let filter = doc! {
+        "business_id": uuid_bson(business_id),
+        "status": "pending",
+        "expires_at": unexpired(),
+    };
+    match collect_invitations(&client, filter).await {
+        Ok(found) => HttpResponse::Ok().json(found),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find invitations")
+        }
+    }
+}
+
+pub async fn revoke_invitation(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (business_id, invitation_id) = path.into_inner();
+    match membership(&client, business_id, &user).await {
+        Ok((_, BusinessRole::Owner | BusinessRole::Admin)) => (),
+        Ok(_) => {
+            return HttpResponse::Forbidden().json("Only owners and admins can revoke invitations")
+        }
+        Err(response) => return response,
+    }
+    let filter = doc! {
+        "invitation_id": uuid_bson(invitation_id),
+        "business_id": uuid_bson(business_id),
+        "status": "pending",
+    };
+    let update = doc! { "$set": { "status": "revoked", "responded_at": Utc::now().to_string() } };
+    match invitations(&client).update_one(filter, update).await {
+        Ok(result) if result.matched_count == 0 => {
+            HttpResponse::NotFound().json("Invitation not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to revoke invitation")
+        }
+    }
+}
+
+/// The caller's pending invitations, matched on their account email.
+pub async fn list_my_invitations(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+) -> impl Responder {
+    let filter = doc! {
+        "email": user.email.to_lowercase(),
+        "status": "pending",
+        "expires_at": unexpired(),
+    };
+    match collect_invitations(&client, filter).await {
+        Ok(found) => HttpResponse::Ok().json(found),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find invitations")
+        }
+    }
+}
+
+/// Marks the caller's pending invitation `status`, returning it. The token
+/// must match, and the invitation stops being pending, so each token works
+/// once.
+async fn respond(
+    client: &Client,
+    user: &AuthenticatedUser,
+    invitation_id: Uuid,
+    token: &str,
+    status: InvitationStatus,
+) -> Result<Invitation, HttpResponse> {
+    let filter = doc! {
+        "invitation_id": uuid_bson(invitation_id),
+        "email": user.email.to_lowercase(),
+        "token_hash": token_hash(token.trim()),
+        "status": "pending",
+        "expires_at": unexpired(),
+    };
+    let update = doc! { "$set": {
+        "status": bson::to_bson(&status).unwrap(),
+        "responded_at": Utc::now().to_string(),
+    } };
+    match invitations(client)
+        .find_one_and_update(filter, update)
+        .await
+    {
+        Ok(Some(invitation)) => Ok(invitation),
+        Ok(None) => {
+            Err(HttpResponse::NotFound().json("No pending invitation with this id and token"))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            Err(HttpResponse::InternalServerError().json("Failed to respond to invitation"))
+        }
+    }
+}
+
+pub async fn accept_invitation(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    response: web::Json<InvitationResponse>,
+) -> impl Responder {
+    let invitation = match respond(
+        &client,
+        &user,
+        path.into_inner(),
+        &response.token,
+        InvitationStatus::Accepted,
+    )
+    .await
+    {
+        Ok(invitation) => invitation,
+        Err(response) => return response,
+    };
+
+    let membership = Membership {
+        user_id: user.user_id,
+        role: invitation.role,
+        joined_at: Utc::now().to_string(),
+    };
+    let filter = doc! {
+        "business_id": uuid_bson(invitation.business_id),
+        "members.user_id": { "$ne": uuid_bson(user.user_id) },
+    };
+    let update = doc! {
+        "$push": { "members": bson::to_bson(&membership).unwrap() },
+        "$addToSet": { "user_ids": uuid_bson(user.user_id) },
+    };
+    match businesses(&client).update_one(filter, update).await {
+        // Already a member (e.g. two invitations accepted): nothing to add.
+        Ok(_) => HttpResponse::Ok().json(membership),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to accept invitation")
+        }
+    }
+}
+
+pub async fn decline_invitation(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    response: web::Json<InvitationResponse>,
+) -> impl Responder {
+    match respond(
+        &client,
+        &user,
+        path.into_inner(),
+        &response.token,
+        InvitationStatus::Declined,
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(response) => response,
+    }
+}
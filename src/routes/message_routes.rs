@@ -1,11 +1,42 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
 
+use super::PaginationParams;
+use crate::db::uuid_bson;
+use crate::metrics;
 use crate::models::message::Message;
-use crate::models::notification::Notification;
+use crate::models::notification::{Notification, NotificationType};
 use futures::stream::StreamExt;
 use mongodb::bson::oid::ObjectId;
 use mongodb::{bson::doc, Client, Collection};
 
+#[derive(Debug, Deserialize)]
+pub struct NewMessage {
+    pub sender: Uuid,
+    pub receiver: Uuid,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageQuery {
+    pub sender_id: Option<Uuid>,
+    pub receiver_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewNotification {
+    pub notification_type: NotificationType,
+    pub user_id: Uuid,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotificationPatch {
+    pub confirmed: bool,
+}
+
 pub fn message_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/messages/send").route(web::post().to(send_message)))
         .service(web::resource("/messages/delete/{id}").route(web::delete().to(delete_message)))
@@ -33,16 +64,47 @@ pub fn message_routes(cfg: &mut web::ServiceConfig) {
         );
 }
 
+pub fn message_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/messages")
+            .route(web::get().to(list_messages))
+            .route(web::post().to(create_message)),
+    )
+    .service(
+        web::resource("/messages/{message_id}")
+            .route(web::get().to(get_message))
+            .route(web::delete().to(remove_message)),
+    )
+    .service(
+        web::resource("/users/{user_id}/conversations/{other_user_id}")
+            .route(web::get().to(list_conversation)),
+    )
+    .service(web::resource("/notifications").route(web::post().to(create_notification_v2)))
+    .service(
+        web::resource("/notifications/{notification_id}")
+            .route(web::patch().to(patch_notification))
+            .route(web::delete().to(delete_notification)),
+    )
+    .service(
+        web::resource("/users/{user_id}/notifications")
+            .route(web::get().to(list_user_notifications)),
+    );
+}
+
 async fn send_message(client: web::Data<Client>, message: web::Json<Message>) -> impl Responder {
     let collection = client.database("cucura-ccdb").collection("messages");
-    let new_message = message.into_inner();
+    let mut new_message = message.into_inner();
+    new_message.hidden = false;
 
     let insert_result = collection.insert_one(new_message).await;
 
     match insert_result {
-        Ok(_) => HttpResponse::Ok().json("Message sent successfully"),
+        Ok(_) => {
+            metrics::MESSAGES_SENT.inc();
+            HttpResponse::Ok().json("Message sent successfully")
+        }
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to send message")
         }
     }
@@ -62,7 +124,7 @@ pub async fn find_messages_between_parties(
     while let Some(result) = cursor.next().await {
         match result {
             Ok(message) => messages.push(message),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
     HttpResponse::Ok().json(messages)
@@ -80,7 +142,7 @@ pub async fn find_message_by_sender(
         Ok(Some(message)) => HttpResponse::Ok().json(message),
         Ok(None) => HttpResponse::NotFound().json("Message not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find message")
         }
     }
@@ -98,7 +160,7 @@ pub async fn find_message_by_receiver(
         Ok(Some(message)) => HttpResponse::Ok().json(message),
         Ok(None) => HttpResponse::NotFound().json("Message not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find message")
         }
     }
@@ -114,7 +176,7 @@ pub async fn delete_message(client: web::Data<Client>, path: web::Path<String>)
     match delete_result {
         Ok(_) => HttpResponse::Ok().json("Message deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete message")
         }
     }
@@ -122,7 +184,7 @@ pub async fn delete_message(client: web::Data<Client>, path: web::Path<String>)
 
 pub async fn find_all_messages(client: web::Data<Client>) -> impl Responder {
     let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
-    let filter = doc! {};
+    let filter = doc! { "hidden": { "$ne": true } };
 
     let mut cursor = collection.find(filter).await.unwrap();
 
@@ -130,7 +192,7 @@ pub async fn find_all_messages(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(message) => messages.push(message),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
@@ -149,7 +211,7 @@ pub async fn create_notification(
     match insert_result {
         Ok(_) => HttpResponse::Ok().json("Notification created successfully"),
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to create notification")
         }
     }
@@ -177,7 +239,7 @@ async fn get_notifications(
                         }
                     }
                     Err(e) => {
-                        eprintln!("Failed to parse document: {}", e);
+                        error!(error = %e, "Failed to parse document");
                         return HttpResponse::InternalServerError()
                             .json("Failed to parse notifications");
                     }
@@ -186,7 +248,7 @@ async fn get_notifications(
             HttpResponse::Ok().json(notifications)
         }
         Err(e) => {
-            eprintln!("Failed to execute query: {}", e);
+            error!(error = %e, "Failed to execute query");
             HttpResponse::InternalServerError().json("Failed to get notifications")
         }
     }
@@ -207,7 +269,7 @@ pub async fn confirm_notification(
     match update_result {
         Ok(_) => HttpResponse::Ok().json("Notification confirmed successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to confirm notification")
         }
     }
@@ -227,8 +289,216 @@ pub async fn delete_notification(
     match delete_result {
         Ok(_) => HttpResponse::Ok().json("Notification deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete notification")
         }
     }
 }
+
+async fn create_message(
+    client: web::Data<Client>,
+    message: web::Json<NewMessage>,
+) -> impl Responder {
+    let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
+    let message = message.into_inner();
+    let new_message = Message::new(message.sender, message.receiver, message.content);
+
+    match collection.insert_one(&new_message).await {
+        Ok(_) => {
+            metrics::MESSAGES_SENT.inc();
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/messages/{}", new_message.message_id),
+                ))
+                .json(new_message)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to send message")
+        }
+    }
+}
+
+/// Messages hidden by moderation are left out.
+async fn collect_messages(
+    collection: &Collection<Message>,
+    mut filter: mongodb::bson::Document,
+    pagination: &PaginationParams,
+) -> HttpResponse {
+    filter.insert("hidden", doc! { "$ne": true });
+    let cursor = collection
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut messages = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(message) => messages.push(message),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(messages)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find messages")
+        }
+    }
+}
+
+async fn list_messages(
+    client: web::Data<Client>,
+    query: web::Query<MessageQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
+    let mut filter = doc! {};
+    if let Some(sender_id) = query.sender_id {
+        filter.insert("sender", uuid_bson(sender_id));
+    }
+    if let Some(receiver_id) = query.receiver_id {
+        filter.insert("receiver", uuid_bson(receiver_id));
+    }
+
+    collect_messages(&collection, filter, &pagination).await
+}
+
+async fn list_conversation(
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
+    let (user_id, other_user_id) = path.into_inner();
+    let filter = doc! { "$or": [
+        { "sender": uuid_bson(user_id), "receiver": uuid_bson(other_user_id) },
+        { "sender": uuid_bson(other_user_id), "receiver": uuid_bson(user_id) },
+    ] };
+
+    collect_messages(&collection, filter, &pagination).await
+}
+
+async fn get_message(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
+    let filter = doc! { "message_id": uuid_bson(path.into_inner()), "hidden": { "$ne": true } };
+
+    match collection.find_one(filter).await {
+        Ok(Some(message)) => HttpResponse::Ok().json(message),
+        Ok(None) => HttpResponse::NotFound().json("Message not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find message")
+        }
+    }
+}
+
+async fn remove_message(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<Message> = client.database("cucura-ccdb").collection("messages");
+    let filter = doc! { "message_id": uuid_bson(path.into_inner()) };
+
+    match collection.delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("Message not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete message")
+        }
+    }
+}
+
+async fn create_notification_v2(
+    client: web::Data<Client>,
+    notification: web::Json<NewNotification>,
+) -> impl Responder {
+    let collection: Collection<Notification> =
+        client.database("cucura-ccdb").collection("notifications");
+    let notification = notification.into_inner();
+    let new_notification = Notification::new(
+        notification.notification_type,
+        notification.user_id,
+        notification.message,
+    );
+
+    match collection.insert_one(&new_notification).await {
+        Ok(result) => {
+            let id = result.inserted_id.as_object_id().map(|id| id.to_hex());
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/notifications/{}", id.unwrap_or_default()),
+                ))
+                .json(new_notification)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to create notification")
+        }
+    }
+}
+
+async fn list_user_notifications(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Notification> =
+        client.database("cucura-ccdb").collection("notifications");
+    let filter = doc! { "user_id": uuid_bson(path.into_inner()) };
+
+    let cursor = collection
+        .find(filter)
+        .sort(doc! { "created_at": -1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut notifications = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(notification) => notifications.push(notification),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(notifications)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to get notifications")
+        }
+    }
+}
+
+async fn patch_notification(
+    client: web::Data<Client>,
+    path: web::Path<String>,
+    patch: web::Json<NotificationPatch>,
+) -> impl Responder {
+    let collection: Collection<Notification> =
+        client.database("cucura-ccdb").collection("notifications");
+    let id = match ObjectId::parse_str(path.into_inner()) {
+        Ok(id) => id,
+        Err(_) => return HttpResponse::BadRequest().json("Invalid notification ID format"),
+    };
+    let update = doc! { "$set": { "confirmed": patch.confirmed } };
+
+    match collection.update_one(doc! { "_id": id }, update).await {
+        Ok(result) if result.matched_count == 0 => {
+            HttpResponse::NotFound().json("Notification not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update notification")
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// routes/metrics_routes.rs
+use actix_web::{web, HttpResponse, Responder};
+use prometheus::{Encoder, TextEncoder};
+use tracing::error;
+
+use crate::metrics::REGISTRY;
+
+pub fn metrics_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/metrics").route(web::get().to(metrics)));
+}
+
+pub async fn metrics() -> impl Responder {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    match encoder.encode(&REGISTRY.gather(), &mut buffer) {
+        Ok(_) => HttpResponse::Ok()
+            .content_type(encoder.format_type())
+            .body(buffer),
+        Err(e) => {
+            error!(error = %e, "Failed to encode metrics");
+            HttpResponse::InternalServerError().json("Failed to encode metrics")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App};
+
+    use crate::metrics;
+    use crate::middleware::metrics::record_metrics;
+
+    async fn scrape() -> String {
+        let app = test::init_service(App::new().configure(metrics_routes)).await;
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri("/metrics").to_request()).await;
+        assert!(response.status().is_success());
+        let body = test::read_body(response).await;
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn requests_are_labelled_by_route_template() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(record_metrics))
+                .route("/metrics-test/{id}", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let request = test::TestRequest::get()
+            .uri("/metrics-test/42")
+            .to_request();
+        test::call_service(&app, request).await;
+
+        let scraped = scrape().await;
+        assert!(scraped.contains(r#"route="/metrics-test/{id}""#));
+        assert!(!scraped.contains("/metrics-test/42"));
+    }
+
+    #[actix_web::test]
+    async fn unknown_payment_statuses_are_counted_as_other() {
+        metrics::record_payment("Completed");
+        metrics::record_payment("paid-in-goats");
+
+        let scraped = scrape().await;
+        assert!(scraped.contains(r#"cucura_payments_total{status="completed"}"#));
+        assert!(scraped.contains(r#"cucura_payments_total{status="other"}"#));
+        assert!(!scraped.contains("paid-in-goats"));
+    }
+}
@@ -1,8 +1,73 @@
+use actix_web::web;
+use serde::Deserialize;
+
+pub mod analytics_routes;
 pub mod auth_routes;
 pub mod business_review_routes;
 pub mod business_routes;
+pub mod catalogue_routes;
+pub mod category_routes;
+pub mod health_routes;
+pub mod media_routes;
+pub mod membership_routes;
 pub mod message_routes;
+pub mod metrics_routes;
+pub mod moderation_routes;
 pub mod payment_routes;
 pub mod profile_routes;
+pub mod search_routes;
 pub mod training_routes;
 pub mod user_routes;
+pub mod verification_routes;
+
+#[derive(Deserialize)]
+pub struct PaginationParams {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+impl PaginationParams {
+    pub fn skip(&self) -> u64 {
+        (self.page.unwrap_or(1).max(1) as u64 - 1) * self.limit() as u64
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(20).clamp(1, 100) as i64
+    }
+}
+
+/// Routes served under `/v1` (and, deprecated, at the root for older app builds).
+pub fn v1(cfg: &mut web::ServiceConfig) {
+    cfg.configure(user_routes::user_routes)
+        .configure(profile_routes::profile_routes)
+        .configure(business_review_routes::review_routes)
+        .configure(training_routes::training_routes)
+        .configure(business_routes::business_routes)
+        .configure(payment_routes::payment_routes)
+        .configure(message_routes::message_routes)
+        .configure(auth_routes::auth_routes);
+}
+
+/// Resource-oriented routes served under `/v2`: plural nouns, HTTP verbs instead
+/// of `/add`, `/update` and `/delete` segments, ids rather than usernames in
+/// paths, and 201 + `Location` on create. The verb-style v1 routes stay
+/// mounted as the compatibility layer.
+pub fn v2(cfg: &mut web::ServiceConfig) {
+    cfg.configure(user_routes::user_routes_v2)
+        .configure(profile_routes::profile_routes_v2)
+        .configure(business_review_routes::review_routes_v2)
+        .configure(training_routes::training_routes_v2)
+        .configure(business_routes::business_routes_v2)
+        .configure(membership_routes::membership_routes_v2)
+        .configure(verification_routes::verification_routes_v2)
+        .configure(media_routes::media_routes_v2)
+        .configure(catalogue_routes::catalogue_routes_v2)
+        .configure(analytics_routes::analytics_routes_v2)
+        .configure(payment_routes::payment_routes_v2)
+        .configure(message_routes::message_routes_v2)
+        .configure(moderation_routes::moderation_routes_v2)
+        .configure(search_routes::search_routes_v2)
+        .configure(category_routes::category_routes_v2)
+        .configure(auth_routes::session_routes_v2)
+        .configure(auth_routes::auth_routes);
+}
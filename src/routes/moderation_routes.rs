@@ -0,0 +1,373 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::bson::{self, doc};
+use mongodb::options::ReturnDocument;
+use mongodb::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::PaginationParams;
+use crate::auth::{Admin, AuthenticatedUser};
+use crate::db::uuid_bson;
+use crate::models::business::{Business, BusinessRole};
+use crate::models::business_review::BusinessReview;
+use crate::models::message::Message;
+use crate::models::notification::{Notification, NotificationType};
+use crate::moderation::{
+    self, Action, ActionTaken, CaseStatus, ModerationCase, Report, ReportReason, TargetKind,
+};
+
+const MAX_NOTE_LENGTH: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct NewReport {
+    pub target_kind: TargetKind,
+    pub target_id: Uuid,
+    pub reason: ReportReason,
+    /// Required when the reason is `other`.
+    pub note: Option<String>,
+}
+
+/// What a reporter gets back; the case itself is for moderators.
+#[derive(Debug, Serialize)]
+pub struct ReportReceipt {
+    pub case_id: Uuid,
+    pub target_kind: TargetKind,
+    pub target_id: Uuid,
+    pub reason: ReportReason,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CaseQuery {
+    pub status: Option<CaseStatus>,
+    pub reason: Option<ReportReason>,
+    pub target_kind: Option<TargetKind>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewAction {
+    pub action: Action,
+    /// Included in the warning sent to the authors.
+    pub note: Option<String>,
+}
+
+pub fn moderation_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/reports").route(web::post().to(create_report)))
+        .service(web::resource("/moderation/cases").route(web::get().to(list_cases)))
+        .service(web::resource("/moderation/cases/{case_id}").route(web::get().to(get_case)))
+        .service(
+            web::resource("/moderation/cases/{case_id}/actions").route(web::post().to(take_action)),
+        );
+}
+
+fn note(note: Option<&str>) -> Result<Option<String>, HttpResponse> {
+    match note.map(str::trim).filter(|note| !note.is_empty()) {
+        Some(note) if note.chars().count() > MAX_NOTE_LENGTH => {
+            Err(HttpResponse::BadRequest().json("Notes must be at most 1000 characters"))
+        }
+        note => Ok(note.map(str::to_string)),
+    }
+}
+
+/// The authors of the reported item, if `reporter_id` can see it: anyone
+/// can see reviews and businesses, but only the receiver can report a
+/// message.
+async fn find_authors(
+    client: &Client,
+    target_kind: TargetKind,
+    target_id: Uuid,
+    reporter_id: Uuid,
+) -> Result<Vec<Uuid>, HttpResponse> {
+    let db = client.database("cucura-ccdb");
+    let filter = doc! { target_kind.id_field(): uuid_bson(target_id) };
+    let found = match target_kind {
+        TargetKind::Review => db
+            .collection::<BusinessReview>("reviews")
+            .find_one(filter)
+            .await
+            .map(|review| review.map(|review| vec![review.reviewer_id])),
+        TargetKind::Message => db
+            .collection::<Message>("messages")
+            .find_one(filter)
+            .await
+            .map(|message| {
+                message
+                    .filter(|message| message.receiver == reporter_id)
+                    .map(|message| vec![message.sender])
+            }),
+        TargetKind::Business => db
+            .collection::<Business>("businesses")
+            .find_one(filter)
+            .await
+            .map(|business| {
+                business.map(|business| {
+                    business
+                        .members
+                        .iter()
+                        .filter(|member| member.role == BusinessRole::Owner)
+                        .map(|member| member.user_id)
+                        .collect()
+                })
+            }),
+    };
+    match found {
+        Ok(Some(authors)) if authors.contains(&reporter_id) => {
+            Err(HttpResponse::BadRequest().json("You cannot report your own content"))
+        }
+        Ok(Some(authors)) => Ok(authors),
+        Ok(None) => {
+            Err(HttpResponse::NotFound().json(format!("{} not found", target_kind.label())))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to add report"))
+        }
+    }
+}
+
+/// Reports a review, message or business to the moderators. Each user can
+/// report an item once while its case is open.
+pub async fn create_report(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    report: web::Json<NewReport>,
+) -> impl Responder {
+    let report = report.into_inner();
+    let note = match note(report.note.as_deref()) {
+        Ok(note) => note,
+        Err(response) => return response,
+    };
+    if report.reason == ReportReason::Other && note.is_none() {
+        return HttpResponse::BadRequest().json("A note is required when the reason is other");
+    }
+    let author_ids =
+        match find_authors(&client, report.target_kind, report.target_id, user.user_id).await {
+            Ok(author_ids) => author_ids,
+            Err(response) => return response,
+        };
+
+    let filed = moderation::file_report(
+        &client,
+        report.target_kind,
+        report.target_id,
+        &author_ids,
+        Report {
+            reporter_id: Some(user.user_id),
+            reason: report.reason,
+            note,
+            created_at: Utc::now().naive_utc(),
+        },
+        false,
+    )
+    .await;
+
+    match filed {
+        Ok(Some(case)) => HttpResponse::Created().json(ReportReceipt {
+            case_id: case.case_id,
+            target_kind: case.target_kind,
+            target_id: case.target_id,
+            reason: report.reason,
+        }),
+        Ok(None) => HttpResponse::Conflict().json("You have already reported this"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to add report")
+        }
+    }
+}
+
+/// The moderation queue: open cases by default, most reported first, then
+/// oldest first.
+pub async fn list_cases(
+    _admin: Admin,
+    client: web::Data<Client>,
+    query: web::Query<CaseQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let mut filter =
+        doc! { "status": bson::to_bson(&query.status.unwrap_or(CaseStatus::Open)).unwrap() };
+    if let Some(reason) = query.reason {
+        filter.insert("reasons", bson::to_bson(&reason).unwrap());
+    }
+    if let Some(target_kind) = query.target_kind {
+        filter.insert("target_kind", bson::to_bson(&target_kind).unwrap());
+    }
+
+    let cursor = moderation::cases(&client)
+        .find(filter)
+        .sort(doc! { "report_count": -1, "created_at": 1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut found = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(case) => found.push(case),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(found)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find cases")
+        }
+    }
+}
+
+async fn find_case(client: &Client, case_id: Uuid) -> Result<ModerationCase, HttpResponse> {
+    match moderation::cases(client)
+        .find_one(doc! { "case_id": uuid_bson(case_id) })
+        .await
+    {
+        Ok(Some(case)) => Ok(case),
+        Ok(None) => Err(HttpResponse::NotFound().json("Case not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find case"))
+        }
+    }
+}
+
+pub async fn get_case(
+    _admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    match find_case(&client, path.into_inner()).await {
+        Ok(case) => HttpResponse::Ok().json(case),
+        Err(response) => response,
+    }
+}
+
+async fn warn_authors(client: &Client, case: &ModerationCase, note: Option<&str>) {
+    let message = match note {
+        Some(note) => format!(
+            "A moderator warned you about your {}: {}",
+            case.target_kind.label(),
+            note
+        ),
+        None => format!(
+            "A moderator warned you about your {}",
+            case.target_kind.label()
+        ),
+    };
+    let notifications: Vec<Notification> = case
+        .author_ids
+        .iter()
+        .map(|user_id| {
+            Notification::new(
+                NotificationType::ModeratorWarnedYou,
+                *user_id,
+                message.clone(),
+            )
+        })
+        .collect();
+    if notifications.is_empty() {
+        return;
+    }
+    if let Err(e) = client
+        .database("cucura-ccdb")
+        .collection::<Notification>("notifications")
+        .insert_many(notifications)
+        .await
+    {
+        warn!(error = %e, "Failed to insert notifications");
+    }
+}
+
+/// Acts on a case. Hiding, removing and warning mark it actioned;
+/// dismissing marks it dismissed and shows the item again if the case hid
+/// it. Actions can follow one another, e.g. a warning after hiding.
+pub async fn take_action(
+    admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    action: web::Json<NewAction>,
+) -> impl Responder {
+    let action = action.into_inner();
+    let note = match note(action.note.as_deref()) {
+        Ok(note) => note,
+        Err(response) => return response,
+    };
+    let case = match find_case(&client, path.into_inner()).await {
+        Ok(case) => case,
+        Err(response) => return response,
+    };
+
+    let (kind, id) = (case.target_kind, case.target_id);
+    let applied = match action.action {
+        Action::Hide => moderation::set_hidden(&client, kind, id, true).await,
+        // Removing what is already gone still closes the case.
+        Action::Remove => moderation::remove(&client, kind, id).await.map(|_| true),
+        Action::Dismiss => {
+            let hid_it = case.auto_hidden
+                || case
+                    .actions
+                    .iter()
+                    .any(|taken| taken.action == Action::Hide);
+            if hid_it {
+                moderation::set_hidden(&client, kind, id, false)
+                    .await
+                    .map(|_| true)
+            } else {
+                Ok(true)
+            }
+        }
+        Action::Warn => {
+            warn_authors(&client, &case, note.as_deref()).await;
+            Ok(true)
+        }
+    };
+    match applied {
+        Ok(true) => (),
+        Ok(false) => return HttpResponse::NotFound().json("The reported content no longer exists"),
+        Err(e) => {
+            error!(error = %e, case_id = %case.case_id, "Failed to apply moderation action");
+            return HttpResponse::InternalServerError().json("Failed to apply action");
+        }
+    }
+
+    let status = match action.action {
+        Action::Dismiss => CaseStatus::Dismissed,
+        _ => CaseStatus::Actioned,
+    };
+    let taken = ActionTaken {
+        action: action.action,
+        moderator_id: admin.0.user_id,
+        note,
+        taken_at: Utc::now().naive_utc(),
+    };
+    let update = doc! {
+        "$set": {
+            "status": bson::to_bson(&status).unwrap(),
+            "updated_at": bson::to_bson(&taken.taken_at).unwrap(),
+        },
+        "$push": { "actions": bson::to_bson(&taken).unwrap() },
+    };
+    match moderation::cases(&client)
+        .find_one_and_update(doc! { "case_id": uuid_bson(case.case_id) }, update)
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(updated)) => {
+            info!(
+                case_id = %updated.case_id,
+                admin = %admin.0.user_id,
+                action = ?taken.action,
+                "Applied moderation action"
+            );
+            HttpResponse::Ok().json(updated)
+        }
+        Ok(None) => HttpResponse::NotFound().json("Case not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to apply action")
+        }
+    }
+}
@@ -1,13 +1,59 @@
 // routes/payment_routes.rs
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::Utc;
 use futures::StreamExt;
+use models::business::Business;
 use models::payment::Payment;
+use models::payment_type::PaymentType;
 use mongodb::{
-    bson::{self, doc},
+    bson::{self, doc, Document},
+    options::ReturnDocument,
     Client, Collection,
 };
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
 
+use super::catalogue_routes::{self, OrderLine};
+use super::membership_routes::role_of;
+use super::PaginationParams;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::metrics;
 use crate::models;
+use crate::purchases;
+
+/// The purchaser is the caller.
+#[derive(Debug, Deserialize)]
+pub struct NewPayment {
+    pub seller_id: Uuid,
+    pub payment_type: String,
+    pub description: Option<String>,
+    /// Worked out from `items` when they are given; must match if also sent.
+    pub amount: Option<f64>,
+    pub currency: String,
+    /// Anything but completed, which only the seller can set.
+    pub status: Option<String>,
+    /// Catalogue items paid for, all from one business.
+    #[serde(default)]
+    pub items: Vec<OrderLine>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentPatch {
+    pub description: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentQuery {
+    pub seller_id: Option<Uuid>,
+    pub purchaser_id: Option<Uuid>,
+    pub status: Option<String>,
+    pub business_id: Option<Uuid>,
+    /// Payments including this catalogue item.
+    pub item_id: Option<Uuid>,
+}
 
 pub fn payment_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/payments/add").route(web::post().to(add_payment)))
@@ -25,80 +71,244 @@ pub fn payment_routes(cfg: &mut web::ServiceConfig) {
         );
 }
 
-pub async fn add_payment(client: web::Data<Client>, payment: web::Json<Payment>) -> impl Responder {
-    let collection = client.database("cucura-ccdb").collection("payments");
-    let new_payment = payment.into_inner();
+pub fn payment_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/payments")
+            .route(web::get().to(list_payments))
+            .route(web::post().to(create_payment)),
+    )
+    .service(
+        web::resource("/payments/{payment_id}")
+            .route(web::get().to(get_payment))
+            .route(web::patch().to(patch_payment))
+            .route(web::delete().to(remove_payment)),
+    );
+}
+
+/// Re-checks the verified-purchase badge on the purchaser's reviews of
+/// the businesses the payment went to.
+async fn refresh_purchases(client: &Client, payment: &Payment) {
+    purchases::refresh(
+        &client.database("cucura-ccdb"),
+        payment.purchaser_id,
+        payment.seller_id,
+        payment.business_id,
+    )
+    .await;
+}
+
+/// Limits `filter` to the payments `user` may see: those they made or
+/// received, or any for admins.
+fn visible_to(mut filter: Document, user: &AuthenticatedUser) -> Document {
+    if !user.is_admin() {
+        let mine = uuid_bson(user.user_id);
+        filter.insert(
+            "$or",
+            vec![doc! { "purchaser_id": &mine }, doc! { "seller_id": mine }],
+        );
+    }
+    filter
+}
+
+fn is_completed(status: &str) -> bool {
+    status.eq_ignore_ascii_case("completed")
+}
+
+/// Whether `user_id` may mark the payment completed: never its purchaser;
+/// for a catalogue order, a member of `business`, the business it went to;
+/// otherwise its seller.
+fn may_complete_as(payment: &Payment, business: Option<&Business>, user_id: Uuid) -> bool {
+    if payment.purchaser_id == user_id {
+        return false;
+    }
+    match payment.business_id {
+        Some(business_id) => business.is_some_and(|business| {
+            business.business_id == business_id && role_of(business, user_id).is_some()
+        }),
+        None => payment.seller_id == user_id,
+    }
+}
+
+/// Looks up the payment's business for [`may_complete_as`].
+async fn may_complete(
+    client: &Client,
+    payment: &Payment,
+    user: &AuthenticatedUser,
+) -> Result<bool, HttpResponse> {
+    let Some(business_id) = payment.business_id else {
+        return Ok(may_complete_as(payment, None, user.user_id));
+    };
+    match client
+        .database("cucura-ccdb")
+        .collection::<Business>("businesses")
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(business) => Ok(may_complete_as(payment, business.as_ref(), user.user_id)),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to update payment"))
+        }
+    }
+}
+
+/// Who completes the payment by moving it to `status`: `Ok(None)` when the
+/// payment is left not completed, 403 unless `user` may complete it.
+async fn completion(
+    client: &Client,
+    payment: &Payment,
+    status: &str,
+    user: &AuthenticatedUser,
+) -> Result<Option<Uuid>, HttpResponse> {
+    if !is_completed(status) {
+        return Ok(None);
+    }
+    if payment.completed_by.is_some() && is_completed(&payment.status) {
+        return Ok(payment.completed_by);
+    }
+    if may_complete(client, payment, user).await? {
+        Ok(Some(user.user_id))
+    } else {
+        Err(HttpResponse::Forbidden().json("Only the seller can mark a payment completed"))
+    }
+}
+
+/// The caller is the purchaser, and may not also be the seller; payments
+/// start out not completed. Catalogue orders go through
+/// [`create_payment`], which prices them.
+pub async fn add_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    payment: web::Json<Payment>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let mut new_payment = payment.into_inner();
+    if is_completed(&new_payment.status) {
+        return HttpResponse::Forbidden().json("Only the seller can mark a payment completed");
+    }
+    if new_payment.seller_id == user.user_id {
+        return HttpResponse::BadRequest().json("The seller cannot also be the purchaser");
+    }
+    if new_payment.business_id.is_some() || !new_payment.items.is_empty() {
+        return HttpResponse::BadRequest()
+            .json("Catalogue orders must be made through /v2/payments");
+    }
+    new_payment.purchaser_id = user.user_id;
+    new_payment.from_catalogue = false;
+    new_payment.completed_by = None;
 
-    let insert_result = collection.insert_one(new_payment).await;
+    let insert_result = collection.insert_one(&new_payment).await;
 
     match insert_result {
-        Ok(_) => HttpResponse::Ok().json("payment added successfully"),
+        Ok(_) => {
+            metrics::record_payment(&new_payment.status);
+            refresh_purchases(&client, &new_payment).await;
+            HttpResponse::Ok().json("payment added successfully")
+        }
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to add payment")
         }
     }
 }
 
+/// The purchaser or seller can update a payment, but not who they are;
+/// only the seller can mark it completed.
 pub async fn update_payment(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
     payment: web::Json<Payment>,
 ) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
-    let payment_for_update = payment.into_inner();
+    let mut payment_for_update = payment.into_inner();
     //check if payment already exists
     let filter = doc! {"payment_id": payment_for_update.payment_id.to_string() };
     let payment_exists = collection.find_one(filter.clone()).await.unwrap();
-    match payment_exists {
-        Some(_) => (),
+    let existing = match payment_exists {
+        Some(existing) => existing,
         None => return HttpResponse::Ok().json("Error 10001 : Payment does not exist"),
+    };
+    if user.user_id != existing.purchaser_id && user.user_id != existing.seller_id {
+        return HttpResponse::Forbidden().json("Only the purchaser or seller can update a payment");
     }
+    payment_for_update.completed_by =
+        match completion(&client, &existing, &payment_for_update.status, &user).await {
+            Ok(completed_by) => completed_by,
+            Err(response) => return response,
+        };
+    payment_for_update.purchaser_id = existing.purchaser_id;
+    payment_for_update.seller_id = existing.seller_id;
+    payment_for_update.business_id = existing.business_id;
+    payment_for_update.items = existing.items;
+    payment_for_update.from_catalogue = existing.from_catalogue;
     let update_doc = doc! { "$set": bson::to_document(&payment_for_update).unwrap() };
     let update_result = collection.update_one(filter, update_doc).await;
 
     match update_result {
-        Ok(_) => HttpResponse::Ok().json("payment updated successfully"),
+        Ok(_) => {
+            metrics::record_payment(&payment_for_update.status);
+            refresh_purchases(&client, &payment_for_update).await;
+            HttpResponse::Ok().json("payment updated successfully")
+        }
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to update payment")
         }
     }
 }
 
-pub async fn delete_payment(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
+/// The purchaser, the seller or an admin can delete a payment.
+pub async fn delete_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
     let payment_id = path.into_inner();
-    let filter = doc! { "payment_id": &payment_id};
+    let filter = visible_to(doc! { "payment_id": &payment_id}, &user);
 
-    let delete_result = collection.delete_one(filter).await;
+    let delete_result = collection.find_one_and_delete(filter).await;
 
     match delete_result {
-        Ok(_) => HttpResponse::Ok().json(format!("Payment {} : deleted successfully", &payment_id)),
+        Ok(deleted) => {
+            if let Some(payment) = deleted {
+                refresh_purchases(&client, &payment).await;
+            }
+            HttpResponse::Ok().json(format!("Payment {} : deleted successfully", &payment_id))
+        }
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete payment")
         }
     }
 }
 
-pub async fn find_payment(client: web::Data<Client>, path: web::Path<String>) -> impl Responder {
+pub async fn find_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<String>,
+) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
     let payment_id = path.into_inner();
-    let filter = doc! { "payment_id": payment_id};
+    let filter = visible_to(doc! { "payment_id": payment_id}, &user);
 
     match collection.find_one(filter).await {
         Ok(Some(payment)) => HttpResponse::Ok().json(payment),
         Ok(None) => HttpResponse::NotFound().json("payment not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find payment")
         }
     }
 }
 
-pub async fn find_all_payments(client: web::Data<Client>) -> impl Responder {
+/// Every payment for admins; the caller's own for everyone else.
+pub async fn find_all_payments(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
-    let filter = doc! {};
+    let filter = visible_to(doc! {}, &user);
 
     let mut cursor = collection.find(filter).await.unwrap();
 
@@ -106,7 +316,7 @@ pub async fn find_all_payments(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(payment) => payments.push(payment),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
@@ -114,12 +324,13 @@ pub async fn find_all_payments(client: web::Data<Client>) -> impl Responder {
 }
 
 pub async fn find_payments_by_seller_id(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
     path: web::Path<String>,
 ) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
     let seller_id = path.into_inner();
-    let filter = doc! { "seller_id": seller_id };
+    let filter = visible_to(doc! { "seller_id": seller_id }, &user);
 
     let mut cursor = collection.find(filter).await.unwrap();
 
@@ -127,7 +338,7 @@ pub async fn find_payments_by_seller_id(
     while let Some(result) = cursor.next().await {
         match result {
             Ok(payment) => payments.push(payment),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
@@ -135,12 +346,13 @@ pub async fn find_payments_by_seller_id(
 }
 
 pub async fn find_payments_by_purchaser_id(
+    user: AuthenticatedUser,
     client: web::Data<Client>,
     path: web::Path<String>,
 ) -> impl Responder {
     let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
     let purchaser_id = path.into_inner();
-    let filter = doc! { "purchaser_id": purchaser_id };
+    let filter = visible_to(doc! { "purchaser_id": purchaser_id }, &user);
 
     let mut cursor = collection.find(filter).await.unwrap();
 
@@ -148,9 +360,304 @@ pub async fn find_payments_by_purchaser_id(
     while let Some(result) = cursor.next().await {
         match result {
             Ok(payment) => payments.push(payment),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(payments)
 }
+
+pub async fn create_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    payment: web::Json<NewPayment>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let payment = payment.into_inner();
+    if payment.status.as_deref().is_some_and(is_completed) {
+        return HttpResponse::Forbidden().json("Only the seller can mark a payment completed");
+    }
+    if payment.seller_id == user.user_id {
+        return HttpResponse::BadRequest().json("The seller cannot also be the purchaser");
+    }
+    let mut currency = payment.currency;
+    let mut amount = payment.amount;
+    let mut order = None;
+    if !payment.items.is_empty() {
+        let priced = match catalogue_routes::price_order(&client, &payment.items).await {
+            Ok(priced) => priced,
+            Err(response) => return response,
+        };
+        if !currency.eq_ignore_ascii_case(&priced.total.currency) {
+            return HttpResponse::BadRequest()
+                .json(format!("The items are priced in {}", priced.total.currency));
+        }
+        if role_of(&priced.business, payment.seller_id).is_none() {
+            return HttpResponse::BadRequest()
+                .json("seller_id must be a member of the business selling the items");
+        }
+        let scale = 10f64.powi(catalogue_routes::minor_unit_digits(&priced.total.currency));
+        let total = priced.total.amount as f64 / scale;
+        if amount.is_some_and(|amount| (amount * scale).round() as i64 != priced.total.amount) {
+            return HttpResponse::BadRequest().json(format!(
+                "The items add up to {} {}",
+                total, priced.total.currency
+            ));
+        }
+        currency = priced.total.currency;
+        amount = Some(total);
+        order = Some((priced.business.business_id, priced.items));
+    }
+    let Some(amount) = amount.filter(|amount| *amount > 0.0) else {
+        return HttpResponse::BadRequest().json("Amount must be positive");
+    };
+
+    let mut new_payment = Payment::new(
+        user.user_id,
+        payment.seller_id,
+        PaymentType::new(payment.payment_type),
+        payment.description,
+        amount,
+        currency,
+        payment.status.unwrap_or_else(|| "PENDING".to_string()),
+    );
+    if let Some((business_id, items)) = order {
+        new_payment.business_id = Some(business_id);
+        new_payment.items = items;
+        new_payment.from_catalogue = true;
+    }
+
+    match collection.insert_one(&new_payment).await {
+        Ok(_) => {
+            metrics::record_payment(&new_payment.status);
+            refresh_purchases(&client, &new_payment).await;
+            HttpResponse::Created()
+                .insert_header((
+                    header::LOCATION,
+                    format!("/v2/payments/{}", new_payment.payment_id),
+                ))
+                .json(new_payment)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to add payment")
+        }
+    }
+}
+
+/// Every payment for admins; the caller's own for everyone else.
+pub async fn list_payments(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    query: web::Query<PaymentQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let mut filter = doc! {};
+    if let Some(seller_id) = query.seller_id {
+        filter.insert("seller_id", uuid_bson(seller_id));
+    }
+    if let Some(purchaser_id) = query.purchaser_id {
+        filter.insert("purchaser_id", uuid_bson(purchaser_id));
+    }
+    if let Some(status) = &query.status {
+        filter.insert("status", status);
+    }
+    if let Some(business_id) = query.business_id {
+        filter.insert("business_id", uuid_bson(business_id));
+    }
+    if let Some(item_id) = query.item_id {
+        filter.insert("items.item_id", uuid_bson(item_id));
+    }
+
+    let cursor = collection
+        .find(visible_to(filter, &user))
+        .sort(doc! { "created_at": -1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut payments = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(payment) => payments.push(payment),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(payments)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find payments")
+        }
+    }
+}
+
+/// The purchaser, the seller or an admin can see a payment.
+pub async fn get_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let filter = visible_to(doc! { "payment_id": uuid_bson(path.into_inner()) }, &user);
+
+    match collection.find_one(filter).await {
+        Ok(Some(payment)) => HttpResponse::Ok().json(payment),
+        Ok(None) => HttpResponse::NotFound().json("payment not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find payment")
+        }
+    }
+}
+
+/// The purchaser or seller can patch a payment; only the seller can mark
+/// it completed.
+pub async fn patch_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    patch: web::Json<PaymentPatch>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let patch = patch.into_inner();
+    let filter = doc! { "payment_id": uuid_bson(path.into_inner()) };
+    let existing = match collection.find_one(filter.clone()).await {
+        Ok(Some(existing)) => existing,
+        Ok(None) => return HttpResponse::NotFound().json("payment not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to update payment");
+        }
+    };
+    if user.user_id != existing.purchaser_id && user.user_id != existing.seller_id {
+        return HttpResponse::Forbidden().json("Only the purchaser or seller can update a payment");
+    }
+
+    let mut set = doc! { "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap() };
+    if let Some(description) = patch.description {
+        set.insert("description", description);
+    }
+    if let Some(status) = &patch.status {
+        let completed_by = match completion(&client, &existing, status, &user).await {
+            Ok(completed_by) => completed_by,
+            Err(response) => return response,
+        };
+        set.insert("status", status);
+        set.insert("completed_by", completed_by.map(uuid_bson));
+    }
+
+    let update_result = collection
+        .find_one_and_update(filter, doc! { "$set": set })
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(payment)) => {
+            if let Some(status) = &patch.status {
+                metrics::record_payment(status);
+                refresh_purchases(&client, &payment).await;
+            }
+            HttpResponse::Ok().json(payment)
+        }
+        Ok(None) => HttpResponse::NotFound().json("payment not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update payment")
+        }
+    }
+}
+
+/// The purchaser, the seller or an admin can delete a payment.
+pub async fn remove_payment(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let collection: Collection<Payment> = client.database("cucura-ccdb").collection("payments");
+    let filter = visible_to(doc! { "payment_id": uuid_bson(path.into_inner()) }, &user);
+
+    match collection.find_one_and_delete(filter).await {
+        Ok(Some(payment)) => {
+            refresh_purchases(&client, &payment).await;
+            HttpResponse::NoContent().finish()
+        }
+        Ok(None) => HttpResponse::NotFound().json("payment not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete payment")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::business::{BusinessRole, Membership};
+
+    fn business(member: Uuid, role: BusinessRole) -> Business {
+        let mut business = Business::new(
+            None,
+            "Sunrise Bakery".to_string(),
+            String::new(),
+            String::new(),
+            "Bakery".to_string(),
+            String::new(),
+            "GB".to_string(),
+        );
+        business.members.push(Membership {
+            user_id: member,
+            role,
+            joined_at: String::new(),
+        });
+        business
+    }
+
+    fn payment(purchaser_id: Uuid, seller_id: Uuid, business_id: Option<Uuid>) -> Payment {
+        let mut payment = Payment::new(
+            purchaser_id,
+            seller_id,
+            PaymentType::new("card".to_string()),
+            None,
+            10.0,
+            "GBP".to_string(),
+            "PENDING".to_string(),
+        );
+        payment.business_id = business_id;
+        payment
+    }
+
+    #[test]
+    fn a_self_dealt_payment_cannot_be_completed() {
+        let user = Uuid::new_v4();
+        assert!(!may_complete_as(&payment(user, user, None), None, user));
+
+        let owned = business(user, BusinessRole::Owner);
+        let order = payment(user, user, Some(owned.business_id));
+        assert!(!may_complete_as(&order, Some(&owned), user));
+    }
+
+    #[test]
+    fn only_the_seller_completes_a_payment_outside_the_catalogue() {
+        let (purchaser, seller) = (Uuid::new_v4(), Uuid::new_v4());
+        let payment = payment(purchaser, seller, None);
+        assert!(may_complete_as(&payment, None, seller));
+        assert!(!may_complete_as(&payment, None, Uuid::new_v4()));
+    }
+
+    #[test]
+    fn only_members_complete_a_catalogue_order() {
+        let (purchaser, seller, member) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let shop = business(member, BusinessRole::Editor);
+        let order = payment(purchaser, seller, Some(shop.business_id));
+        assert!(may_complete_as(&order, Some(&shop), member));
+        assert!(!may_complete_as(&order, Some(&shop), seller));
+        assert!(!may_complete_as(&order, None, member));
+
+        let elsewhere = business(member, BusinessRole::Owner);
+        assert!(!may_complete_as(&order, Some(&elsewhere), member));
+    }
+}
@@ -1,17 +1,41 @@
 use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
 use futures::StreamExt;
 use models::profile::Profile;
 use mongodb::{
-    bson::{self, doc, oid::ObjectId, Bson},
+    bson::{self, doc, oid::ObjectId, Bson, Document},
+    options::ReturnDocument,
     Client, Collection,
 };
+use serde::Deserialize;
+use tracing::{error, warn};
 use uuid::Uuid;
 
+use super::PaginationParams;
+use crate::db::uuid_bson;
+use crate::middleware::deprecation::deprecated;
 use crate::models;
 
+#[derive(Debug, Deserialize)]
+pub struct ProfileQuery {
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileUpdate {
+    pub bio: Option<String>,
+    pub profile_type: Option<String>,
+    pub business: Option<String>,
+    pub interests: Option<Vec<String>>,
+}
+
 pub fn profile_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/profiles/register").route(web::post().to(register_profile)))
-        .service(web::resource("/profiles/update").route(web::put().to(update_profile)))
+        .service(
+            web::resource("/profiles/update")
+                .wrap(deprecated("/v2/profiles/{profile_id}"))
+                .route(web::put().to(update_profile)),
+        )
         .service(
             web::resource("/profiles/delete/{username}").route(web::delete().to(delete_profile)),
         )
@@ -23,11 +47,20 @@ pub fn profile_routes(cfg: &mut web::ServiceConfig) {
         );
 }
 
+pub fn profile_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/profiles").route(web::get().to(list_profiles)))
+        .service(
+            web::resource("/profiles/{profile_id}")
+                .route(web::get().to(find_profile))
+                .route(web::patch().to(update_profile_v2))
+                .route(web::delete().to(remove_profile)),
+        );
+}
+
 pub async fn register_profile(
     client: web::Data<Client>,
     profile: web::Json<Profile>,
 ) -> impl Responder {
-    println!();
     let collection = client.database("cucura-ccdb").collection("profiles");
     let new_profile = profile.into_inner();
     let filter = doc! { "email": &new_profile.email };
@@ -42,7 +75,7 @@ pub async fn register_profile(
     match insert_result {
         Ok(_) => HttpResponse::Ok().json("profile registered successfully"),
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to register profile")
         }
     }
@@ -63,7 +96,47 @@ pub async fn update_profile(
     match update_result {
         Ok(_) => HttpResponse::Ok().json("profile updated successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update profile")
+        }
+    }
+}
+
+/// v2 update: addressed by `profile_id` and only touches the fields that are sent.
+pub async fn update_profile_v2(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    profile: web::Json<ProfileUpdate>,
+) -> impl Responder {
+    let collection: Collection<Profile> = client.database("cucura-ccdb").collection("profiles");
+    let profile_id = path.into_inner();
+    let changes = profile.into_inner();
+    let filter = doc! { "profile_id": uuid_bson(profile_id) };
+
+    let mut set = doc! { "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap() };
+    if let Some(bio) = changes.bio {
+        set.insert("bio", bio);
+    }
+    if let Some(profile_type) = changes.profile_type {
+        set.insert("profile_type", profile_type);
+    }
+    if let Some(business) = changes.business {
+        set.insert("business", business);
+    }
+    if let Some(interests) = changes.interests {
+        set.insert("interests", interests);
+    }
+
+    let update_result = collection
+        .find_one_and_update(filter, doc! { "$set": set })
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(None) => HttpResponse::NotFound().json("Profile not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to update profile")
         }
     }
@@ -79,7 +152,7 @@ pub async fn delete_profile(client: web::Data<Client>, path: web::Path<String>)
     match delete_result {
         Ok(_) => HttpResponse::Ok().json("Profile deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete profile")
         }
     }
@@ -96,10 +169,13 @@ pub async fn find_profile(client: web::Data<Client>, path: web::Path<String>) ->
     let filter = doc! { "profile_id": Bson::Binary(bson::Binary {
     subtype: bson::spec::BinarySubtype::UserDefined(0), bytes: profile_uuid.as_bytes().to_vec() }) };
     match collection.find_one(filter).await {
-        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(Some(profile)) => {
+            record_profile_view(&client, profile.profile_id).await;
+            HttpResponse::Ok().json(profile)
+        }
         Ok(None) => HttpResponse::NotFound().json("Profile not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find profile")
         }
     }
@@ -114,15 +190,35 @@ pub async fn find_profile_by_username(
     let filter = doc! { "username": &username };
 
     match collection.find_one(filter).await {
-        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(Some(profile)) => {
+            record_profile_view(&client, profile.profile_id).await;
+            HttpResponse::Ok().json(profile)
+        }
         Ok(None) => HttpResponse::NotFound().json("Profile not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find profile")
         }
     }
 }
 
+/// Views feed the popularity weighting of search suggestions; failing to
+/// count one never fails the read.
+async fn record_profile_view(client: &Client, profile_id: Uuid) {
+    let result = client
+        .database("cucura-ccdb")
+        .collection::<Document>("profile_views")
+        .update_one(
+            doc! { "profile_id": uuid_bson(profile_id) },
+            doc! { "$inc": { "views": 1 } },
+        )
+        .upsert(true)
+        .await;
+    if let Err(e) = result {
+        warn!(error = %e, "Failed to record profile view");
+    }
+}
+
 async fn find_all_profiles(client: web::Data<Client>) -> impl Responder {
     let collection: Collection<Profile> = client.database("cucura-ccdb").collection("profiles");
     // Create a filter (empty filter to match all documents)
@@ -136,10 +232,61 @@ async fn find_all_profiles(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(profile) => profiles.push(profile),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     // Return the documents as a JSON response
     HttpResponse::Ok().json(profiles)
 }
+
+pub async fn list_profiles(
+    client: web::Data<Client>,
+    query: web::Query<ProfileQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Profile> = client.database("cucura-ccdb").collection("profiles");
+    let mut filter = doc! {};
+    if let Some(username) = &query.username {
+        filter.insert("username", username);
+    }
+
+    let cursor = collection
+        .find(filter)
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut profiles = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(profile) => profiles.push(profile),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(profiles)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find profiles")
+        }
+    }
+}
+
+pub async fn remove_profile(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<Profile> = client.database("cucura-ccdb").collection("profiles");
+    let filter = doc! { "profile_id": uuid_bson(path.into_inner()) };
+
+    match collection.delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("Profile not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete profile")
+        }
+    }
+}
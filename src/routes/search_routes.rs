@@ -0,0 +1,77 @@
+// routes/search_routes.rs
+use actix_web::{web, HttpResponse, Responder};
+use serde::Deserialize;
+use tracing::error;
+
+use super::PaginationParams;
+use crate::search::{suggest::SuggestIndex, SearchIndex, SearchKind, SearchRequest};
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    /// Comma-separated result types (`business`, `profile`, `training`); all by default.
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestParams {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+pub fn search_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/search").route(web::get().to(search)))
+        .service(web::resource("/search/suggest").route(web::get().to(suggest)));
+}
+
+pub async fn search(
+    index: web::Data<dyn SearchIndex>,
+    params: web::Query<SearchParams>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return HttpResponse::BadRequest().json("q must not be empty");
+    }
+    let kinds = match &params.types {
+        Some(types) => {
+            let parsed: Option<Vec<SearchKind>> = types
+                .split(',')
+                .map(str::trim)
+                .filter(|kind| !kind.is_empty())
+                .map(SearchKind::parse)
+                .collect();
+            match parsed {
+                Some(kinds) if !kinds.is_empty() => kinds,
+                _ => {
+                    return HttpResponse::BadRequest()
+                        .json("type must be a list of business, profile or training")
+                }
+            }
+        }
+        None => SearchKind::ALL.to_vec(),
+    };
+
+    let request = SearchRequest {
+        query: query.to_string(),
+        kinds,
+        skip: pagination.skip(),
+        limit: pagination.limit(),
+    };
+    match index.search(&request).await {
+        Ok(results) => HttpResponse::Ok().json(results),
+        Err(e) => {
+            error!(error = %e, "Failed to execute search");
+            HttpResponse::InternalServerError().json("Failed to search")
+        }
+    }
+}
+
+pub async fn suggest(
+    index: web::Data<SuggestIndex>,
+    params: web::Query<SuggestParams>,
+) -> impl Responder {
+    let limit = params.limit.unwrap_or(8).clamp(1, 20);
+    HttpResponse::Ok().json(index.suggest(&params.q, limit))
+}
@@ -1,14 +1,55 @@
 // routes/training_routes.rs
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::{NaiveDateTime, Utc};
 use futures::StreamExt;
 use models::training::Training;
 use mongodb::{
     bson::{self, doc, oid::ObjectId},
+    options::ReturnDocument,
     Client, Collection,
 };
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
 
+use super::PaginationParams;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
 use crate::models;
 
+#[derive(Debug, Deserialize)]
+pub struct NewTraining {
+    pub trainer_id: Uuid,
+    pub title: String,
+    pub description: String,
+    pub start_date: Option<NaiveDateTime>,
+    pub end_date: Option<NaiveDateTime>,
+    pub duration: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrainingPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+}
+
+/// A user signed up for a training.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Enrolment {
+    pub enrolment_id: Uuid,
+    pub training_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
 pub fn training_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/trainings/add").route(web::post().to(add_training)))
         .service(web::resource("/trainings/update").route(web::put().to(update_training)))
@@ -17,6 +58,34 @@ pub fn training_routes(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/trainings/{id}").route(web::get().to(find_training)));
 }
 
+pub fn training_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/trainings")
+            .route(web::get().to(list_trainings))
+            .route(web::post().to(create_training)),
+    )
+    .service(
+        web::resource("/trainings/{training_id}")
+            .route(web::get().to(get_training))
+            .route(web::patch().to(patch_training))
+            .route(web::delete().to(remove_training)),
+    )
+    .service(
+        web::resource("/trainings/{training_id}/enrolments").route(web::get().to(list_enrolments)),
+    )
+    .service(
+        web::resource("/trainings/{training_id}/enrolments/{user_id}")
+            .route(web::put().to(enrol))
+            .route(web::delete().to(unenrol)),
+    );
+}
+
+fn enrolments(client: &Client) -> Collection<Enrolment> {
+    client
+        .database("cucura-ccdb")
+        .collection("training_enrolments")
+}
+
 pub async fn add_training(
     client: web::Data<Client>,
     training: web::Json<Training>,
@@ -29,7 +98,7 @@ pub async fn add_training(
     match insert_result {
         Ok(_) => HttpResponse::Ok().json("training added successfully"),
         Err(e) => {
-            eprintln!("Failed to insert document: {}", e);
+            error!(error = %e, "Failed to insert document");
             HttpResponse::InternalServerError().json("Failed to add training")
         }
     }
@@ -55,7 +124,7 @@ pub async fn update_training(
     match update_result {
         Ok(_) => HttpResponse::Ok().json("training updated successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to update training")
         }
     }
@@ -71,7 +140,7 @@ pub async fn delete_training(client: web::Data<Client>, path: web::Path<String>)
     match delete_result {
         Ok(_) => HttpResponse::Ok().json("training deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete training")
         }
     }
@@ -86,7 +155,7 @@ pub async fn find_training(client: web::Data<Client>, path: web::Path<String>) -
         Ok(Some(training)) => HttpResponse::Ok().json(training),
         Ok(None) => HttpResponse::NotFound().json("training not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find training")
         }
     }
@@ -102,9 +171,245 @@ pub async fn find_all_trainings(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(training) => trainings.push(training),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(trainings)
 }
+
+pub async fn create_training(
+    client: web::Data<Client>,
+    training: web::Json<NewTraining>,
+) -> impl Responder {
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    let training = training.into_inner();
+    let new_training = Training::new(
+        training.trainer_id,
+        training.title,
+        training.description,
+        training.start_date,
+        training.end_date,
+        training.duration,
+    );
+
+    match collection.insert_one(&new_training).await {
+        Ok(_) => HttpResponse::Created()
+            .insert_header((
+                header::LOCATION,
+                format!("/v2/trainings/{}", new_training.training_id),
+            ))
+            .json(new_training),
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            HttpResponse::InternalServerError().json("Failed to add training")
+        }
+    }
+}
+
+pub async fn list_trainings(
+    client: web::Data<Client>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+
+    let cursor = collection
+        .find(doc! {})
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut trainings = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(training) => trainings.push(training),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(trainings)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find trainings")
+        }
+    }
+}
+
+pub async fn get_training(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    let filter = doc! { "training_id": uuid_bson(path.into_inner()) };
+
+    match collection.find_one(filter).await {
+        Ok(Some(training)) => HttpResponse::Ok().json(training),
+        Ok(None) => HttpResponse::NotFound().json("training not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find training")
+        }
+    }
+}
+
+pub async fn patch_training(
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    patch: web::Json<TrainingPatch>,
+) -> impl Responder {
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    let mut set = bson::to_document(&patch.into_inner()).unwrap();
+    set.insert(
+        "updated_at",
+        bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+    );
+
+    let update_result = collection
+        .find_one_and_update(
+            doc! { "training_id": uuid_bson(path.into_inner()) },
+            doc! { "$set": set },
+        )
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(training)) => HttpResponse::Ok().json(training),
+        Ok(None) => HttpResponse::NotFound().json("training not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update training")
+        }
+    }
+}
+
+pub async fn remove_training(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    let filter = doc! { "training_id": uuid_bson(path.into_inner()) };
+
+    match collection.delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("training not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete training")
+        }
+    }
+}
+
+/// The training's enrolments, for its trainer.
+pub async fn list_enrolments(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let training_id = path.into_inner();
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    match collection
+        .find_one(doc! { "training_id": uuid_bson(training_id) })
+        .await
+    {
+        Ok(Some(training)) if training.trainer_id == user.user_id || user.is_admin() => (),
+        Ok(Some(_)) => {
+            return HttpResponse::Forbidden().json("Only the trainer can see enrolments")
+        }
+        Ok(None) => return HttpResponse::NotFound().json("training not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to find enrolments");
+        }
+    }
+
+    let cursor = enrolments(&client)
+        .find(doc! { "training_id": uuid_bson(training_id) })
+        .sort(doc! { "created_at": 1 })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut found = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(enrolment) => found.push(enrolment),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(found)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find enrolments")
+        }
+    }
+}
+
+/// Enrols the user; enrolling again returns the existing enrolment.
+pub async fn enrol(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (training_id, user_id) = path.into_inner();
+    if user_id != user.user_id && !user.is_admin() {
+        return HttpResponse::Forbidden().json("Users can only enrol themselves");
+    }
+    let collection: Collection<Training> = client.database("cucura-ccdb").collection("trainings");
+    match collection
+        .find_one(doc! { "training_id": uuid_bson(training_id) })
+        .await
+    {
+        Ok(Some(_)) => (),
+        Ok(None) => return HttpResponse::NotFound().json("training not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to enrol");
+        }
+    }
+
+    let update_result = enrolments(&client)
+        .find_one_and_update(
+            doc! { "training_id": uuid_bson(training_id), "user_id": uuid_bson(user_id) },
+            doc! { "$setOnInsert": {
+                "enrolment_id": uuid_bson(Uuid::new_v4()),
+                "created_at": bson::to_bson(&Utc::now().naive_utc()).unwrap(),
+            } },
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(enrolment)) => HttpResponse::Ok().json(enrolment),
+        Ok(None) => HttpResponse::InternalServerError().json("Failed to enrol"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to enrol")
+        }
+    }
+}
+
+pub async fn unenrol(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (training_id, user_id) = path.into_inner();
+    if user_id != user.user_id && !user.is_admin() {
+        return HttpResponse::Forbidden().json("Users can only withdraw their own enrolment");
+    }
+    let filter = doc! { "training_id": uuid_bson(training_id), "user_id": uuid_bson(user_id) };
+
+    match enrolments(&client).delete_one(filter).await {
+        Ok(result) if result.deleted_count == 0 => {
+            HttpResponse::NotFound().json("enrolment not found")
+        }
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to withdraw enrolment")
+        }
+    }
+}
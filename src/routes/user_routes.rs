@@ -1,12 +1,63 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
 use bcrypt::{hash, DEFAULT_COST};
+use chrono::{NaiveDateTime, Utc};
 use futures::StreamExt;
 use models::user::User;
-use mongodb::{bson::doc, Client, Collection};
+use mongodb::{
+    bson::{self, doc},
+    options::ReturnDocument,
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
 use uuid::Uuid;
 
+use super::PaginationParams;
+use crate::auth::AuthenticatedUser;
+use crate::db::uuid_bson;
+use crate::metrics;
 use crate::models::{self, user::NewUser};
 
+/// What v2 hands back for a user; the password hash never leaves the server.
+#[derive(Debug, Serialize)]
+pub struct UserResponse {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub user_type: String,
+    pub admin: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        UserResponse {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            user_type: user.user_type,
+            admin: user.admin,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserQuery {
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserPatch {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    /// Admins only, like `admin`.
+    pub user_type: Option<String>,
+    pub admin: Option<bool>,
+}
+
 pub fn user_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/users/register").route(web::post().to(register_user)))
         .service(web::resource("/users/update").route(web::put().to(update_user)))
@@ -15,13 +66,28 @@ pub fn user_routes(cfg: &mut web::ServiceConfig) {
         .service(web::resource("/users/{username}").route(web::get().to(find_user)));
 }
 
+pub fn user_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/users")
+            .route(web::get().to(list_users))
+            .route(web::post().to(create_user)),
+    )
+    .service(
+        web::resource("/users/{user_id}")
+            .route(web::get().to(get_user))
+            .route(web::patch().to(patch_user))
+            .route(web::delete().to(remove_user)),
+    );
+}
+
 pub async fn register_user(
     client: web::Data<Client>,
     new_user: web::Json<NewUser>,
 ) -> impl Responder {
     let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
     let mut new_user = new_user.into_inner();
-    let hashed_password = hash(&new_user.password, DEFAULT_COST).unwrap();
+    let hashed_password =
+        metrics::time_bcrypt("hash", || hash(&new_user.password, DEFAULT_COST)).unwrap();
     new_user.password = hashed_password;
 
     // Check if user already exists
@@ -41,45 +107,64 @@ pub async fn register_user(
                 created_user.username.clone(),
             );
             profile_collection.insert_one(&new_profile).await.unwrap();
+            metrics::USER_REGISTRATIONS.inc();
 
             HttpResponse::Ok().json("User registered successfully")
         }
     }
 }
 
-pub async fn update_user(client: web::Data<Client>, user: web::Json<User>) -> impl Responder {
+/// Users can update themselves; only admins can change a user's type.
+pub async fn update_user(
+    caller: AuthenticatedUser,
+    client: web::Data<Client>,
+    user: web::Json<User>,
+) -> impl Responder {
     let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
-    let hashed_password = hash(&user.password, DEFAULT_COST).unwrap();
+    if !caller.may_act_for(user.user_id) {
+        return HttpResponse::Forbidden().json("Not allowed to change this user");
+    }
+    let hashed_password =
+        metrics::time_bcrypt("hash", || hash(&user.password, DEFAULT_COST)).unwrap();
     let mut updated_user = user.into_inner();
     updated_user.password = hashed_password;
 
     let filter = doc! { "user_id": updated_user.user_id.to_string() };
-    let update = doc! {
-        "$set": {
-            "username": updated_user.username,
-            "email": updated_user.email,
-            "password": updated_user.password,
-            "user_type": updated_user.user_type,
-        }
+    let mut set = doc! {
+        "username": updated_user.username,
+        "email": updated_user.email,
+        "password": updated_user.password,
     };
+    if caller.is_admin() {
+        set.insert("user_type", updated_user.user_type);
+    }
+    let update = doc! { "$set": set };
 
     match collection.update_one(filter, update).await {
         Ok(_) => HttpResponse::Ok().json("User updated successfully"),
         Err(e) => {
-            eprintln!("Failed to update document: {}", e);
+            error!(error = %e, "Failed to update document");
             HttpResponse::InternalServerError().json("Failed to update user")
         }
     }
 }
 
-pub async fn delete_user(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+pub async fn delete_user(
+    caller: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
     let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
-    let filter = doc! { "user_id": path.into_inner().to_string() };
+    let user_id = path.into_inner();
+    if !caller.may_act_for(user_id) {
+        return HttpResponse::Forbidden().json("Not allowed to delete this user");
+    }
+    let filter = doc! { "user_id": user_id.to_string() };
 
     match collection.delete_one(filter).await {
         Ok(_) => HttpResponse::Ok().json("User deleted successfully"),
         Err(e) => {
-            eprintln!("Failed to delete document: {}", e);
+            error!(error = %e, "Failed to delete document");
             HttpResponse::InternalServerError().json("Failed to delete user")
         }
     }
@@ -93,7 +178,7 @@ pub async fn find_user(client: web::Data<Client>, path: web::Path<String>) -> im
         Ok(Some(user)) => HttpResponse::Ok().json(user),
         Ok(None) => HttpResponse::NotFound().json("User not found"),
         Err(e) => {
-            eprintln!("Failed to find document: {}", e);
+            error!(error = %e, "Failed to find document");
             HttpResponse::InternalServerError().json("Failed to find user")
         }
     }
@@ -108,9 +193,218 @@ pub async fn find_all_users(client: web::Data<Client>) -> impl Responder {
     while let Some(result) = cursor.next().await {
         match result {
             Ok(user) => users.push(user),
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => error!(error = ?e, "Failed to read document"),
         }
     }
 
     HttpResponse::Ok().json(users)
 }
+
+pub async fn create_user(
+    client: web::Data<Client>,
+    new_user: web::Json<NewUser>,
+) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let mut new_user = new_user.into_inner();
+
+    match collection
+        .find_one(doc! { "username": &new_user.username })
+        .await
+    {
+        Ok(Some(_)) => return HttpResponse::Conflict().json("User already exists"),
+        Ok(None) => (),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return HttpResponse::InternalServerError().json("Failed to register user");
+        }
+    }
+
+    new_user.password =
+        metrics::time_bcrypt("hash", || hash(&new_user.password, DEFAULT_COST)).unwrap();
+    let created_user = new_user.to_user();
+    if let Err(e) = collection.insert_one(&created_user).await {
+        error!(error = %e, "Failed to insert document");
+        return HttpResponse::InternalServerError().json("Failed to register user");
+    }
+
+    let profile_collection: Collection<models::profile::Profile> =
+        client.database("cucura-ccdb").collection("profiles");
+    let new_profile = models::profile::Profile::new(
+        created_user.user_id,
+        created_user.email.clone(),
+        created_user.username.clone(),
+    );
+    if let Err(e) = profile_collection.insert_one(&new_profile).await {
+        error!(error = %e, "Failed to insert document");
+        return HttpResponse::InternalServerError().json("Failed to create user profile");
+    }
+    metrics::USER_REGISTRATIONS.inc();
+
+    HttpResponse::Created()
+        .insert_header((
+            header::LOCATION,
+            format!("/v2/users/{}", created_user.user_id),
+        ))
+        .json(UserResponse::from(created_user))
+}
+
+pub async fn list_users(
+    client: web::Data<Client>,
+    query: web::Query<UserQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let mut filter = doc! {};
+    if let Some(username) = &query.username {
+        filter.insert("username", username);
+    }
+
+    let cursor = collection
+        .find(filter)
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await;
+
+    match cursor {
+        Ok(mut cursor) => {
+            let mut users = Vec::new();
+            while let Some(result) = cursor.next().await {
+                match result {
+                    Ok(user) => users.push(UserResponse::from(user)),
+                    Err(e) => error!(error = ?e, "Failed to read document"),
+                }
+            }
+            HttpResponse::Ok().json(users)
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find users")
+        }
+    }
+}
+
+pub async fn get_user(client: web::Data<Client>, path: web::Path<Uuid>) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let filter = doc! { "user_id": uuid_bson(path.into_inner()) };
+
+    match collection.find_one(filter).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(UserResponse::from(user)),
+        Ok(None) => HttpResponse::NotFound().json("User not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            HttpResponse::InternalServerError().json("Failed to find user")
+        }
+    }
+}
+
+/// Users can change themselves; only admins can change a user's type or
+/// role.
+pub async fn patch_user(
+    caller: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    patch: web::Json<UserPatch>,
+) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let user_id = path.into_inner();
+    let patch = patch.into_inner();
+
+    if !caller.may_act_for(user_id) {
+        return HttpResponse::Forbidden().json("Not allowed to change this user");
+    }
+    let changes_role = patch.user_type.is_some() || patch.admin.is_some();
+    if changes_role && !caller.is_admin() {
+        return HttpResponse::Forbidden().json("Only admins can change a user's type or role");
+    }
+
+    if let Some(username) = &patch.username {
+        let filter = doc! { "username": username, "user_id": { "$ne": uuid_bson(user_id) } };
+        match collection.find_one(filter).await {
+            Ok(Some(_)) => return HttpResponse::Conflict().json("Username already taken"),
+            Ok(None) => (),
+            Err(e) => {
+                error!(error = %e, "Failed to find document");
+                return HttpResponse::InternalServerError().json("Failed to update user");
+            }
+        }
+    }
+
+    let mut set = doc! { "updated_at": bson::to_bson(&Utc::now().naive_utc()).unwrap() };
+    // username and email are denormalised onto the profile
+    let mut profile_set = set.clone();
+    if let Some(username) = patch.username {
+        set.insert("username", &username);
+        profile_set.insert("username", username);
+    }
+    if let Some(email) = patch.email {
+        set.insert("email", &email);
+        profile_set.insert("email", email);
+    }
+    if let Some(user_type) = patch.user_type {
+        set.insert("user_type", user_type);
+    }
+    if let Some(admin) = patch.admin {
+        set.insert("admin", admin);
+    }
+
+    let update_result = collection
+        .find_one_and_update(doc! { "user_id": uuid_bson(user_id) }, doc! { "$set": set })
+        .return_document(ReturnDocument::After)
+        .await;
+
+    match update_result {
+        Ok(Some(user)) => {
+            let profile_collection: Collection<models::profile::Profile> =
+                client.database("cucura-ccdb").collection("profiles");
+            if let Err(e) = profile_collection
+                .update_one(
+                    doc! { "user_id": uuid_bson(user_id) },
+                    doc! { "$set": profile_set },
+                )
+                .await
+            {
+                error!(error = %e, "Failed to update document");
+            }
+            HttpResponse::Ok().json(UserResponse::from(user))
+        }
+        Ok(None) => HttpResponse::NotFound().json("User not found"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to update user")
+        }
+    }
+}
+
+pub async fn remove_user(
+    caller: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let collection: Collection<User> = client.database("cucura-ccdb").collection("users");
+    let user_id = path.into_inner();
+    if !caller.may_act_for(user_id) {
+        return HttpResponse::Forbidden().json("Not allowed to delete this user");
+    }
+
+    match collection
+        .delete_one(doc! { "user_id": uuid_bson(user_id) })
+        .await
+    {
+        Ok(result) if result.deleted_count == 0 => HttpResponse::NotFound().json("User not found"),
+        Ok(_) => {
+            let profile_collection: Collection<models::profile::Profile> =
+                client.database("cucura-ccdb").collection("profiles");
+            if let Err(e) = profile_collection
+                .delete_one(doc! { "user_id": uuid_bson(user_id) })
+                .await
+            {
+                error!(error = %e, "Failed to delete document");
+            }
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to delete document");
+            HttpResponse::InternalServerError().json("Failed to delete user")
+        }
+    }
+}
@@ -0,0 +1,635 @@
+// routes/verification_routes.rs
+use actix_web::{http::header, web, HttpResponse, Responder};
+use chrono::Utc;
+use futures::StreamExt;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::ReturnDocument,
+    Client, Collection,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::membership_routes::role_of;
+use super::PaginationParams;
+use crate::auth::{Admin, AuthenticatedUser};
+use crate::db::uuid_bson;
+use crate::models::business::{owners, Business, BusinessRole, VerificationStatus};
+use crate::models::notification::{Notification, NotificationType};
+
+const MAX_EVIDENCE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestKind {
+    /// A member asking for the badge.
+    Verification,
+    /// A user taking over a listing nobody owns.
+    Claim,
+    /// A user contesting the current owners of a listing.
+    Dispute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Withdrawn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvidenceKind {
+    RegistrationCertificate,
+    TaxCertificate,
+    UtilityBill,
+    BusinessPermit,
+    Website,
+    Other,
+}
+
+/// A document or page supporting a request, by URL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Evidence {
+    pub kind: EvidenceKind,
+    pub url: String,
+    pub note: Option<String>,
+}
+
+/// Verification, claim or dispute awaiting (or past) admin review.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerificationRequest {
+    pub request_id: Uuid,
+    pub business_id: Uuid,
+    pub kind: RequestKind,
+    pub submitted_by: Uuid,
+    pub evidence: Vec<Evidence>,
+    pub message: Option<String>,
+    /// The current owners' side of a dispute.
+    pub owner_response: Option<String>,
+    pub status: ReviewStatus,
+    pub reviewed_by: Option<Uuid>,
+    pub review_reason: Option<String>,
+    pub reviewed_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewRequest {
+    pub evidence: Vec<Evidence>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OwnerResponse {
+    pub response: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    pub decision: Decision,
+    /// Required when rejecting; shown to the submitter.
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestQuery {
+    pub status: Option<ReviewStatus>,
+    pub kind: Option<RequestKind>,
+}
+
+pub fn verification_routes_v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/businesses/{business_id}/verification-requests")
+            .route(web::get().to(list_business_requests))
+            .route(web::post().to(request_verification)),
+    )
+    .service(
+        web::resource("/businesses/{business_id}/claims").route(web::post().to(claim_business)),
+    )
+    .service(web::resource("/verification-requests").route(web::get().to(list_requests)))
+    .service(
+        web::resource("/verification-requests/{request_id}/response")
+            .route(web::put().to(respond_to_dispute)),
+    )
+    .service(
+        web::resource("/verification-requests/{request_id}/withdrawal")
+            .route(web::post().to(withdraw_request)),
+    )
+    .service(
+        web::resource("/verification-requests/{request_id}/review")
+            .route(web::post().to(review_request)),
+    );
+}
+
+fn requests(client: &Client) -> Collection<VerificationRequest> {
+    client
+        .database("cucura-ccdb")
+        .collection("verification_requests")
+}
+
+fn businesses(client: &Client) -> Collection<Business> {
+    client.database("cucura-ccdb").collection("businesses")
+}
+
+fn validate(request: &NewRequest) -> Result<(), &'static str> {
+    if request.evidence.is_empty() || request.evidence.len() > MAX_EVIDENCE {
+        return Err("Between 1 and 10 pieces of evidence are required");
+    }
+    let is_url = |url: &str| url.starts_with("https://") || url.starts_with("http://");
+    if !request
+        .evidence
+        .iter()
+        .all(|evidence| is_url(&evidence.url))
+    {
+        return Err("Evidence urls must be http(s) links");
+    }
+    Ok(())
+}
+
+async fn notify(client: &Client, kind: NotificationType, user_ids: &[Uuid], message: &str) {
+    let notifications: Vec<Notification> = user_ids
+        .iter()
+        .map(|user_id| Notification::new(kind.clone(), *user_id, message.to_string()))
+        .collect();
+    if notifications.is_empty() {
+        return;
+    }
+    if let Err(e) = client
+        .database("cucura-ccdb")
+        .collection::<Notification>("notifications")
+        .insert_many(notifications)
+        .await
+    {
+        warn!(error = %e, "Failed to insert notifications");
+    }
+}
+
+async fn find_business(client: &Client, business_id: Uuid) -> Result<Business, HttpResponse> {
+    match businesses(client)
+        .find_one(doc! { "business_id": uuid_bson(business_id) })
+        .await
+    {
+        Ok(Some(business)) => Ok(business),
+        Ok(None) => Err(HttpResponse::NotFound().json("Business not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find business"))
+        }
+    }
+}
+
+async fn insert_request(
+    client: &Client,
+    business_id: Uuid,
+    kind: RequestKind,
+    submitted_by: Uuid,
+    new_request: NewRequest,
+) -> Result<VerificationRequest, HttpResponse> {
+    let pending = doc! {
+        "business_id": uuid_bson(business_id),
+        "submitted_by": uuid_bson(submitted_by),
+        "status": "pending",
+    };
+    match requests(client).find_one(pending).await {
+        Ok(Some(_)) => return Err(HttpResponse::Conflict().json("A request is already pending")),
+        Ok(None) => (),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            return Err(HttpResponse::InternalServerError().json("Failed to submit request"));
+        }
+    }
+
+    let request = VerificationRequest {
+        request_id: Uuid::new_v4(),
+        business_id,
+        kind,
+        submitted_by,
+        evidence: new_request.evidence,
+        message: new_request.message,
+        owner_response: None,
+        status: ReviewStatus::Pending,
+        reviewed_by: None,
+        review_reason: None,
+        reviewed_at: None,
+        created_at: Utc::now().to_string(),
+    };
+    match requests(client).insert_one(&request).await {
+        Ok(_) => Ok(request),
+        Err(e) => {
+            error!(error = %e, "Failed to insert document");
+            Err(HttpResponse::InternalServerError().json("Failed to submit request"))
+        }
+    }
+}
+
+fn created(request: VerificationRequest) -> HttpResponse {
+    HttpResponse::Created()
+        .insert_header((
+            header::LOCATION,
+            format!("/v2/verification-requests/{}", request.request_id),
+        ))
+        .json(request)
+}
+
+/// Owners and admins of a business submit evidence for the badge.
+pub async fn request_verification(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    new_request: web::Json<NewRequest>,
+) -> impl Responder {
+    let new_request = new_request.into_inner();
+    if let Err(message) = validate(&new_request) {
+        return HttpResponse::BadRequest().json(message);
+    }
+    let business_id = path.into_inner();
+    let business = match find_business(&client, business_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    match role_of(&business, user.user_id) {
+        Some(BusinessRole::Owner | BusinessRole::Admin) => (),
+        _ => {
+            return HttpResponse::Forbidden()
+                .json("Only owners and admins can request verification")
+        }
+    }
+    if business.verification_status == VerificationStatus::Verified {
+        return HttpResponse::Conflict().json("Business is already verified");
+    }
+    let request = match insert_request(
+        &client,
+        business_id,
+        RequestKind::Verification,
+        user.user_id,
+        new_request,
+    )
+    .await
+    {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+
+    if let Err(e) = businesses(&client)
+        .update_one(
+            doc! { "business_id": uuid_bson(business_id), "verification_status": { "$ne": "verified" } },
+            doc! { "$set": { "verification_status": "pending" } },
+        )
+        .await
+    {
+        error!(error = %e, "Failed to update document");
+    }
+    created(request)
+}
+
+/// Claims a listing for the caller. Listings without owners are claimed
+/// outright once an admin approves; claims on owned listings are disputes,
+/// which the owners are told about and may answer before the review.
+pub async fn claim_business(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    new_request: web::Json<NewRequest>,
+) -> impl Responder {
+    let new_request = new_request.into_inner();
+    if let Err(message) = validate(&new_request) {
+        return HttpResponse::BadRequest().json(message);
+    }
+    let business_id = path.into_inner();
+    let business = match find_business(&client, business_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    if role_of(&business, user.user_id) == Some(BusinessRole::Owner) {
+        return HttpResponse::Conflict().json("Already an owner");
+    }
+    let current_owners: Vec<Uuid> = business
+        .members
+        .iter()
+        .filter(|member| member.role == BusinessRole::Owner)
+        .map(|member| member.user_id)
+        .collect();
+    let kind = if current_owners.is_empty() {
+        RequestKind::Claim
+    } else {
+        RequestKind::Dispute
+    };
+    let request = match insert_request(&client, business_id, kind, user.user_id, new_request).await
+    {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+
+    if kind == RequestKind::Dispute {
+        let message = format!(
+            "Someone has disputed your ownership of {}. You can respond before an admin reviews it.",
+            business.name
+        );
+        notify(
+            &client,
+            NotificationType::SomeoneDisputedBusiness,
+            &current_owners,
+            &message,
+        )
+        .await;
+    }
+    created(request)
+}
+
+async fn collect_requests(
+    client: &Client,
+    filter: Document,
+    pagination: &PaginationParams,
+    oldest_first: bool,
+) -> mongodb::error::Result<Vec<VerificationRequest>> {
+    let mut cursor = requests(client)
+        .find(filter)
+        .sort(doc! { "created_at": if oldest_first { 1 } else { -1 } })
+        .skip(pagination.skip())
+        .limit(pagination.limit())
+        .await?;
+    let mut found = Vec::new();
+    while let Some(request) = cursor.next().await {
+        match request {
+            Ok(request) => found.push(request),
+            Err(e) => error!(error = ?e, "Failed to read document"),
+        }
+    }
+    Ok(found)
+}
+
+/// For admins, the review queue (pending by default, oldest first); for
+/// everyone else, the requests they submitted.
+pub async fn list_requests(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    query: web::Query<RequestQuery>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let mut filter = doc! {};
+    if let Some(kind) = query.kind {
+        filter.insert("kind", bson::to_bson(&kind).unwrap());
+    }
+    let status = if user.is_admin() {
+        Some(query.status.unwrap_or(ReviewStatus::Pending))
+    } else {
+        filter.insert("submitted_by", uuid_bson(user.user_id));
+        query.status
+    };
+    if let Some(status) = status {
+        filter.insert("status", bson::to_bson(&status).unwrap());
+    }
+    match collect_requests(&client, filter, &pagination, user.is_admin()).await {
+        Ok(found) => HttpResponse::Ok().json(found),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find requests")
+        }
+    }
+}
+
+/// Every request about a business, including disputes, for its owners and admins.
+pub async fn list_business_requests(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    pagination: web::Query<PaginationParams>,
+) -> impl Responder {
+    let business_id = path.into_inner();
+    let business = match find_business(&client, business_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    match role_of(&business, user.user_id) {
+        Some(BusinessRole::Owner | BusinessRole::Admin) => (),
+        _ if user.is_admin() => (),
+        _ => return HttpResponse::Forbidden().json("Only owners and admins can see requests"),
+    }
+    let filter = doc! { "business_id": uuid_bson(business_id) };
+    match collect_requests(&client, filter, &pagination, false).await {
+        Ok(found) => HttpResponse::Ok().json(found),
+        Err(e) => {
+            error!(error = %e, "Failed to execute query");
+            HttpResponse::InternalServerError().json("Failed to find requests")
+        }
+    }
+}
+
+async fn find_request(
+    client: &Client,
+    request_id: Uuid,
+) -> Result<VerificationRequest, HttpResponse> {
+    match requests(client)
+        .find_one(doc! { "request_id": uuid_bson(request_id) })
+        .await
+    {
+        Ok(Some(request)) => Ok(request),
+        Ok(None) => Err(HttpResponse::NotFound().json("Request not found")),
+        Err(e) => {
+            error!(error = %e, "Failed to find document");
+            Err(HttpResponse::InternalServerError().json("Failed to find request"))
+        }
+    }
+}
+
+/// An owner's answer to a pending dispute, shown to the reviewing admin.
+pub async fn respond_to_dispute(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    response: web::Json<OwnerResponse>,
+) -> impl Responder {
+    let request = match find_request(&client, path.into_inner()).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    if request.kind != RequestKind::Dispute {
+        return HttpResponse::BadRequest().json("Only disputes take an owner response");
+    }
+    let business = match find_business(&client, request.business_id).await {
+        Ok(business) => business,
+        Err(response) => return response,
+    };
+    if role_of(&business, user.user_id) != Some(BusinessRole::Owner) {
+        return HttpResponse::Forbidden().json("Only owners can respond to a dispute");
+    }
+
+    let filter = doc! { "request_id": uuid_bson(request.request_id), "status": "pending" };
+    let update = doc! { "$set": { "owner_response": response.response.trim() } };
+    match requests(&client)
+        .find_one_and_update(filter, update)
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(request)) => HttpResponse::Ok().json(request),
+        Ok(None) => HttpResponse::Conflict().json("The dispute has already been reviewed"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            HttpResponse::InternalServerError().json("Failed to respond to dispute")
+        }
+    }
+}
+
+pub async fn withdraw_request(
+    user: AuthenticatedUser,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let filter = doc! {
+        "request_id": uuid_bson(path.into_inner()),
+        "submitted_by": uuid_bson(user.user_id),
+        "status": "pending",
+    };
+    let update = doc! { "$set": { "status": "withdrawn" } };
+    let request = match requests(&client).find_one_and_update(filter, update).await {
+        Ok(Some(request)) => request,
+        Ok(None) => return HttpResponse::NotFound().json("No pending request with this id"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            return HttpResponse::InternalServerError().json("Failed to withdraw request");
+        }
+    };
+    if request.kind == RequestKind::Verification {
+        reset_pending_badge(&client, request.business_id, "unverified").await;
+    }
+    HttpResponse::NoContent().finish()
+}
+
+/// Moves a business out of `pending` unless another verification request
+/// for it is still open.
+async fn reset_pending_badge(client: &Client, business_id: Uuid, status: &str) {
+    let open = requests(client)
+        .count_documents(doc! {
+            "business_id": uuid_bson(business_id),
+            "kind": "verification",
+            "status": "pending",
+        })
+        .await;
+    match open {
+        Ok(0) => {
+            if let Err(e) = businesses(client)
+                .update_one(
+                    doc! { "business_id": uuid_bson(business_id), "verification_status": "pending" },
+                    doc! { "$set": { "verification_status": status } },
+                )
+                .await
+            {
+                error!(error = %e, "Failed to update document");
+            }
+        }
+        Ok(_) => (),
+        Err(e) => error!(error = %e, "Failed to count documents"),
+    }
+}
+
+/// Admin decision. Approving a verification grants the badge; approving a
+/// claim or dispute makes the claimant the sole owner and grants the badge,
+/// since the same evidence was reviewed.
+pub async fn review_request(
+    admin: Admin,
+    client: web::Data<Client>,
+    path: web::Path<Uuid>,
+    review: web::Json<Review>,
+) -> impl Responder {
+    let reason = review
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty());
+    if review.decision == Decision::Reject && reason.is_none() {
+        return HttpResponse::BadRequest().json("A reason is required to reject");
+    }
+    let request = match find_request(&client, path.into_inner()).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    if request.status != ReviewStatus::Pending {
+        return HttpResponse::Conflict().json("Request has already been reviewed");
+    }
+
+    let now = Utc::now().to_string();
+    if review.decision == Decision::Approve {
+        let mut filter = doc! { "business_id": uuid_bson(request.business_id) };
+        let mut set = doc! { "verification_status": "verified", "verified_at": &now };
+        if matches!(request.kind, RequestKind::Claim | RequestKind::Dispute) {
+            if request.kind == RequestKind::Claim {
+                // Someone may have become an owner since the claim was made.
+                filter.insert("members.role", doc! { "$ne": "owner" });
+            }
+            let members = owners(&[request.submitted_by]);
+            set.insert("members", bson::to_bson(&members).unwrap());
+            set.insert("user_ids", vec![uuid_bson(request.submitted_by)]);
+        }
+        match businesses(&client)
+            .update_one(filter, doc! { "$set": set })
+            .await
+        {
+            Ok(result) if result.matched_count == 0 => {
+                return HttpResponse::Conflict()
+                    .json("The business is gone or has been claimed since; reject instead")
+            }
+            Ok(_) => (),
+            Err(e) => {
+                error!(error = %e, "Failed to update document");
+                return HttpResponse::InternalServerError().json("Failed to review request");
+            }
+        }
+    }
+
+    let status = match review.decision {
+        Decision::Approve => ReviewStatus::Approved,
+        Decision::Reject => ReviewStatus::Rejected,
+    };
+    let filter = doc! { "request_id": uuid_bson(request.request_id), "status": "pending" };
+    let update = doc! { "$set": {
+        "status": bson::to_bson(&status).unwrap(),
+        "reviewed_by": uuid_bson(admin.0.user_id),
+        "review_reason": reason.map_or(Bson::Null, Bson::from),
+        "reviewed_at": &now,
+    } };
+    let reviewed = match requests(&client)
+        .find_one_and_update(filter, update)
+        .return_document(ReturnDocument::After)
+        .await
+    {
+        Ok(Some(reviewed)) => reviewed,
+        Ok(None) => return HttpResponse::Conflict().json("Request has already been reviewed"),
+        Err(e) => {
+            error!(error = %e, "Failed to update document");
+            return HttpResponse::InternalServerError().json("Failed to review request");
+        }
+    };
+    if status == ReviewStatus::Rejected && request.kind == RequestKind::Verification {
+        reset_pending_badge(&client, request.business_id, "rejected").await;
+    }
+    info!(
+        request_id = %reviewed.request_id,
+        admin = %admin.0.user_id,
+        status = ?status,
+        "Reviewed verification request"
+    );
+
+    let outcome = match status {
+        ReviewStatus::Approved => "approved".to_string(),
+        _ => format!("rejected: {}", reason.unwrap_or_default()),
+    };
+    notify(
+        &client,
+        NotificationType::SomeoneReviewedVerification,
+        &[reviewed.submitted_by],
+        &format!("Your {:?} request was {}", reviewed.kind, outcome).to_lowercase(),
+    )
+    .await;
+    HttpResponse::Ok().json(reviewed)
+}
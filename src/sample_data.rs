@@ -1,16 +1,16 @@
 pub mod models;
 pub use crate::models::{
-    business::Business, message::Message, notification::Notification, payment::Payment,
-    payment_type::PaymentType, profile::Profile, training::Training, user::User,
+    business::Business, category::bundled_taxonomy, category::Category, geo::cities,
+    message::Message, notification::Notification, payment::Payment, payment_type::PaymentType,
+    profile::Profile, training::Training, user::User,
 };
 
 use bcrypt::{hash, DEFAULT_COST};
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use dotenv::dotenv;
 use fake::{
-    faker::address::en::CountryName, faker::company::en::CompanyName,
-    faker::internet::en::SafeEmail, faker::internet::en::Username, faker::lorem::en::Sentence,
-    faker::name::en::Name, Fake,
+    faker::company::en::CompanyName, faker::internet::en::SafeEmail, faker::internet::en::Username,
+    faker::lorem::en::Sentence, faker::name::en::Name, Fake,
 };
 use models::notification::NotificationType;
 use mongodb::Client;
@@ -25,8 +25,7 @@ use std::ops::Range;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();
+    tracing_subscriber::fmt().with_env_filter("debug").init();
     dotenv().ok();
 
     let uri = env::var("MONGODB_URI").expect("MONGODB_URI must be set");
@@ -131,11 +130,26 @@ async fn main() -> std::io::Result<()> {
         training_collection.insert_one(training).await.unwrap();
     }
 
+    // Load the industry taxonomy
+    let category_collection = db.collection::<Category>("categories");
+    let taxonomy = bundled_taxonomy();
+    for category in &taxonomy {
+        category_collection.insert_one(category).await.unwrap();
+    }
+    let divisions: Vec<&Category> = taxonomy
+        .iter()
+        .filter(|category| category.parent_code.is_some())
+        .collect();
+
     // Load sample businesses
     let business_collection = db.collection::<Business>("businesses");
     let sample_businesses = (1..=12)
         .map(|_| {
-            Business::new(
+            let city = cities().choose(&mut rand::thread_rng()).unwrap();
+            let categories: Vec<&&Category> = divisions
+                .choose_multiple(&mut rand::thread_rng(), (1..=2).fake())
+                .collect();
+            let mut business = Business::new(
                 Some(vec![
                     *user_ids.choose(&mut rand::thread_rng()).unwrap(),
                     *user_ids.choose(&mut rand::thread_rng()).unwrap(),
@@ -143,10 +157,18 @@ async fn main() -> std::io::Result<()> {
                 CompanyName().fake(),
                 Sentence(3..5).fake(),
                 Name().fake(),
-                Sentence(1..2).fake(),
+                categories[0].name.clone(),
                 format!("772-456-78{:02}", (1..=12).fake::<u8>()),
-                CountryName().fake(),
-            )
+                city.country.clone(),
+            );
+            business.category_codes = categories
+                .iter()
+                .map(|category| category.code.clone())
+                .collect();
+            business.city = Some(city.name.clone());
+            business.founded_on = NaiveDate::from_ymd_opt((1990..2024).fake(), (1..=12).fake(), 1);
+            business.locate();
+            business
         })
         .collect::<Vec<_>>();
     for business in sample_businesses {
@@ -0,0 +1,476 @@
+// schedule.rs
+//! Whether a business is open, from its [`OpeningHours`]. [`is_open`]
+//! answers for one business; [`open_now`] is the same rule as a Mongo
+//! expression for filtering the directory, so the two must change together.
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use mongodb::bson::{doc, Bson, Document};
+
+use crate::models::opening_hours::{Day, OpeningHours, TimeRange};
+
+const MAX_WEEKLY_PERIODS: usize = 28;
+const MAX_SPECIAL_DAYS: usize = 366;
+
+/// In `$isoDayOfWeek` order.
+const DAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+fn valid_time(time: &str, closing: bool) -> bool {
+    if closing && time == "24:00" {
+        return true;
+    }
+    let bytes = time.as_bytes();
+    bytes.len() == 5
+        && bytes[2] == b':'
+        && time[..2].parse::<u8>().is_ok_and(|hour| hour < 24)
+        && time[3..].parse::<u8>().is_ok_and(|minute| minute < 60)
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, b)| i == 2 || b.is_ascii_digit())
+}
+
+fn check_range(range: &TimeRange) -> Result<(), String> {
+    if !valid_time(&range.opens, false) || !valid_time(&range.closes, true) {
+        return Err(format!(
+            "{}-{} is not a valid HH:MM range",
+            range.opens, range.closes
+        ));
+    }
+    if range.opens == range.closes {
+        return Err(format!(
+            "{}-{} opens and closes at the same time",
+            range.opens, range.closes
+        ));
+    }
+    Ok(())
+}
+
+/// Checked on every write: the directory's `open_now` filter fails outright
+/// on a timezone Mongo does not know.
+pub fn validate(hours: &OpeningHours) -> Result<(), String> {
+    if hours.timezone.parse::<Tz>().is_err() {
+        return Err(format!("Unknown timezone {}", hours.timezone));
+    }
+    if hours.weekly.len() > MAX_WEEKLY_PERIODS {
+        return Err(format!(
+            "At most {} weekly periods are allowed",
+            MAX_WEEKLY_PERIODS
+        ));
+    }
+    if hours.special_days.len() > MAX_SPECIAL_DAYS {
+        return Err(format!(
+            "At most {} special days are allowed",
+            MAX_SPECIAL_DAYS
+        ));
+    }
+    for period in &hours.weekly {
+        check_range(&period.hours)?;
+    }
+    let mut dates = HashSet::new();
+    for special_day in &hours.special_days {
+        if !dates.insert(special_day.date) {
+            return Err(format!("{} is listed twice", special_day.date));
+        }
+        for range in &special_day.periods {
+            check_range(range)?;
+        }
+    }
+    Ok(())
+}
+
+fn day(weekday: Weekday) -> Day {
+    match weekday {
+        Weekday::Mon => Day::Monday,
+        Weekday::Tue => Day::Tuesday,
+        Weekday::Wed => Day::Wednesday,
+        Weekday::Thu => Day::Thursday,
+        Weekday::Fri => Day::Friday,
+        Weekday::Sat => Day::Saturday,
+        Weekday::Sun => Day::Sunday,
+    }
+}
+
+/// A special day's periods if `date` is one, otherwise its weekday's.
+fn periods_on(hours: &OpeningHours, date: NaiveDate) -> Vec<&TimeRange> {
+    match hours
+        .special_days
+        .iter()
+        .find(|special_day| special_day.date == date)
+    {
+        Some(special_day) => special_day.periods.iter().collect(),
+        None => hours
+            .weekly
+            .iter()
+            .filter(|period| period.day == day(date.weekday()))
+            .map(|period| &period.hours)
+            .collect(),
+    }
+}
+
+pub fn is_open(hours: &OpeningHours, at: DateTime<Utc>) -> bool {
+    let Ok(timezone) = hours.timezone.parse::<Tz>() else {
+        return false;
+    };
+    let local = at.with_timezone(&timezone);
+    let time = local.format("%H:%M").to_string();
+    let today = local.date_naive();
+    let overnight = |range: &TimeRange| range.closes <= range.opens;
+
+    periods_on(hours, today)
+        .into_iter()
+        .any(|range| range.opens <= time && (time < range.closes || overnight(range)))
+        || today.pred_opt().is_some_and(|yesterday| {
+            periods_on(hours, yesterday)
+                .into_iter()
+                .any(|range| overnight(range) && time < range.closes)
+        })
+}
+
+/// [`is_open`] at the time of the query, as an aggregation expression over
+/// a business document; false for businesses without hours.
+pub fn open_now() -> Document {
+    let timezone = "$opening_hours.timezone";
+    let yesterday = doc! { "$dateSubtract": {
+        "startDate": "$$NOW",
+        "unit": "day",
+        "amount": 1,
+        "timezone": timezone,
+    } };
+    let periods_on = |date: Bson| {
+        doc! { "$let": {
+            "vars": { "special": { "$filter": {
+                "input": { "$ifNull": ["$opening_hours.special_days", []] },
+                "cond": { "$eq": [
+                    "$$this.date",
+                    { "$dateToString": { "date": &date, "format": "%Y-%m-%d", "timezone": timezone } },
+                ] },
+            } } },
+            "in": { "$cond": [
+                { "$gt": [{ "$size": "$$special" }, 0] },
+                { "$ifNull": [{ "$arrayElemAt": ["$$special.periods", 0] }, []] },
+                { "$filter": {
+                    "input": { "$ifNull": ["$opening_hours.weekly", []] },
+                    "cond": { "$eq": ["$$this.day", { "$arrayElemAt": [
+                        DAYS.to_vec(),
+                        { "$subtract": [{ "$isoDayOfWeek": { "date": &date, "timezone": timezone } }, 1] },
+                    ] }] },
+                } },
+            ] },
+        } }
+    };
+    let overnight = doc! { "$lte": ["$$this.closes", "$$this.opens"] };
+
+    doc! { "$cond": [
+        { "$eq": [{ "$type": timezone }, "string"] },
+        { "$let": {
+            "vars": {
+                "time": { "$dateToString": { "date": "$$NOW", "format": "%H:%M", "timezone": timezone } },
+                "today": periods_on(Bson::String("$$NOW".to_string())),
+                "yesterday": periods_on(Bson::Document(yesterday)),
+            },
+            "in": { "$or": [
+                { "$anyElementTrue": [{ "$map": {
+                    "input": "$$today",
+                    "in": { "$and": [
+                        { "$lte": ["$$this.opens", "$$time"] },
+                        { "$or": [{ "$lt": ["$$time", "$$this.closes"] }, &overnight] },
+                    ] },
+                } }] },
+                { "$anyElementTrue": [{ "$map": {
+                    "input": "$$yesterday",
+                    "in": { "$and": [&overnight, { "$lt": ["$$time", "$$this.closes"] }] },
+                } }] },
+            ] },
+        } },
+        false,
+    ] }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use futures::StreamExt;
+    use mongodb::bson;
+
+    use super::*;
+    use crate::models::opening_hours::{OpeningPeriod, SpecialDay};
+
+    fn range(opens: &str, closes: &str) -> TimeRange {
+        TimeRange {
+            opens: opens.to_string(),
+            closes: closes.to_string(),
+        }
+    }
+
+    /// Nairobi is UTC+3 all year, so local times are easy to read off.
+    fn hours(weekly: &[(Day, &str, &str)]) -> OpeningHours {
+        OpeningHours {
+            timezone: "Africa/Nairobi".to_string(),
+            weekly: weekly
+                .iter()
+                .map(|&(day, opens, closes)| OpeningPeriod {
+                    day,
+                    hours: range(opens, closes),
+                })
+                .collect(),
+            special_days: Vec::new(),
+        }
+    }
+
+    fn special(date: &str, periods: &[(&str, &str)]) -> SpecialDay {
+        SpecialDay {
+            date: date.parse().unwrap(),
+            periods: periods
+                .iter()
+                .map(|&(opens, closes)| range(opens, closes))
+                .collect(),
+            name: None,
+        }
+    }
+
+    /// A local Nairobi time; 2024-01-01 is a Monday.
+    fn nairobi(local: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("{}:00+03:00", local))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn open_within_the_day_period() {
+        let hours = hours(&[(Day::Monday, "09:00", "17:00")]);
+        assert!(!is_open(&hours, nairobi("2024-01-01T08:59")));
+        assert!(is_open(&hours, nairobi("2024-01-01T09:00")));
+        assert!(is_open(&hours, nairobi("2024-01-01T16:59")));
+        assert!(!is_open(&hours, nairobi("2024-01-01T17:00")));
+        assert!(!is_open(&hours, nairobi("2024-01-02T10:00")));
+    }
+
+    #[test]
+    fn overnight_period_runs_into_the_next_day() {
+        let hours = hours(&[(Day::Friday, "22:00", "02:00")]);
+        assert!(!is_open(&hours, nairobi("2024-01-05T21:59")));
+        assert!(is_open(&hours, nairobi("2024-01-05T23:30")));
+        assert!(is_open(&hours, nairobi("2024-01-06T01:59")));
+        assert!(!is_open(&hours, nairobi("2024-01-06T02:00")));
+        // Thursday night has no period to run over from.
+        assert!(!is_open(&hours, nairobi("2024-01-05T01:00")));
+    }
+
+    #[test]
+    fn closing_at_24_00_ends_at_midnight() {
+        let evening = hours(&[(Day::Monday, "18:00", "24:00")]);
+        assert!(is_open(&evening, nairobi("2024-01-01T23:59")));
+        assert!(!is_open(&evening, nairobi("2024-01-02T00:00")));
+
+        let all_day = hours(&[(Day::Monday, "00:00", "24:00")]);
+        assert!(is_open(&all_day, nairobi("2024-01-01T00:00")));
+        assert!(is_open(&all_day, nairobi("2024-01-01T23:59")));
+        assert!(!is_open(&all_day, nairobi("2024-01-02T00:00")));
+    }
+
+    #[test]
+    fn special_day_replaces_the_weekday() {
+        let mut hours = hours(&[(Day::Monday, "09:00", "17:00")]);
+        hours.special_days = vec![
+            special("2024-01-01", &[]),
+            special("2024-01-08", &[("12:00", "14:00")]),
+        ];
+        assert!(!is_open(&hours, nairobi("2024-01-01T10:00")));
+        assert!(!is_open(&hours, nairobi("2024-01-08T10:00")));
+        assert!(is_open(&hours, nairobi("2024-01-08T13:00")));
+        assert!(is_open(&hours, nairobi("2024-01-15T10:00")));
+    }
+
+    #[test]
+    fn previous_day_overnight_follows_that_day_s_hours() {
+        let mut hours = hours(&[(Day::Sunday, "20:00", "03:00")]);
+        // Closing Monday does not cut short Sunday night.
+        hours.special_days = vec![special("2024-01-01", &[])];
+        assert!(is_open(&hours, nairobi("2024-01-01T01:00")));
+
+        // Closing Sunday does.
+        hours.special_days = vec![special("2023-12-31", &[])];
+        assert!(!is_open(&hours, nairobi("2024-01-01T01:00")));
+
+        // An overnight special day runs into the next day.
+        hours.special_days = vec![special("2024-01-02", &[("23:00", "01:00")])];
+        assert!(is_open(&hours, nairobi("2024-01-03T00:30")));
+    }
+
+    #[test]
+    fn local_time_follows_daylight_saving() {
+        let mut hours = hours(&[
+            (Day::Saturday, "09:00", "17:00"),
+            (Day::Sunday, "09:00", "17:00"),
+        ]);
+        hours.timezone = "Europe/London".to_string();
+        let utc = |at: &str| {
+            DateTime::parse_from_rfc3339(at)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        // Clocks went forward at 01:00 UTC on 2024-03-31.
+        assert!(!is_open(&hours, utc("2024-03-30T08:30:00Z")));
+        assert!(is_open(&hours, utc("2024-03-31T08:30:00Z")));
+        assert!(is_open(&hours, utc("2024-03-31T15:59:00Z")));
+        assert!(!is_open(&hours, utc("2024-03-31T16:00:00Z")));
+    }
+
+    #[test]
+    fn unknown_timezone_is_closed() {
+        let mut hours = hours(&[(Day::Monday, "00:00", "24:00")]);
+        hours.timezone = "Mars/Olympus_Mons".to_string();
+        assert!(!is_open(&hours, nairobi("2024-01-01T12:00")));
+    }
+
+    #[test]
+    fn validate_accepts_overnight_and_24_00() {
+        let mut hours = hours(&[
+            (Day::Monday, "09:00", "17:00"),
+            (Day::Friday, "22:00", "02:00"),
+            (Day::Saturday, "18:00", "24:00"),
+        ]);
+        hours.special_days = vec![
+            special("2024-12-25", &[]),
+            special("2024-12-31", &[("20:00", "03:00")]),
+        ];
+        assert_eq!(validate(&hours), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_bad_times() {
+        for (opens, closes) in [
+            ("24:00", "10:00"),
+            ("9:00", "17:00"),
+            ("09:00", "17:60"),
+            ("09:00", "25:00"),
+            ("0a:00", "17:00"),
+            ("09:00", "09:00"),
+        ] {
+            let hours = hours(&[(Day::Monday, opens, closes)]);
+            assert!(validate(&hours).is_err(), "{}-{} passed", opens, closes);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_bad_schedules() {
+        let mut unknown = hours(&[]);
+        unknown.timezone = "Nowhere/Special".to_string();
+        assert_eq!(
+            validate(&unknown),
+            Err("Unknown timezone Nowhere/Special".to_string())
+        );
+
+        let mut twice = hours(&[]);
+        twice.special_days = vec![special("2024-12-25", &[]), special("2024-12-25", &[])];
+        assert_eq!(
+            validate(&twice),
+            Err("2024-12-25 is listed twice".to_string())
+        );
+
+        let crowded = hours(&[(Day::Monday, "09:00", "10:00"); MAX_WEEKLY_PERIODS + 1]);
+        assert!(validate(&crowded).is_err());
+    }
+
+    /// Schedules around the current time in a few timezones, so both sides
+    /// see open and closed businesses, overnight periods and special days.
+    fn fixtures(now: DateTime<Utc>) -> Vec<OpeningHours> {
+        let mut fixtures = Vec::new();
+        for timezone in ["Africa/Nairobi", "America/New_York", "Asia/Kolkata"] {
+            let local = now.with_timezone(&timezone.parse::<Tz>().unwrap());
+            let today = day(local.weekday());
+            let yesterday = day(local.weekday().pred());
+            let at = |hours: i64| (local + Duration::hours(hours)).format("%H:%M").to_string();
+            let weekly = [
+                vec![(today, at(-1), at(1))],
+                vec![(today, at(1), at(2))],
+                vec![(yesterday, at(-3), at(1))],
+                vec![(yesterday, at(1), at(-1))],
+                vec![(today, "00:00".to_string(), "24:00".to_string())],
+            ];
+            for periods in weekly {
+                let weekly = periods
+                    .into_iter()
+                    .map(|(day, opens, closes)| OpeningPeriod {
+                        day,
+                        hours: TimeRange { opens, closes },
+                    })
+                    .collect::<Vec<_>>();
+                for special_days in [
+                    Vec::new(),
+                    vec![SpecialDay {
+                        date: local.date_naive(),
+                        periods: Vec::new(),
+                        name: None,
+                    }],
+                    vec![SpecialDay {
+                        date: local.date_naive().pred_opt().unwrap(),
+                        periods: vec![range(&at(-2), &at(1))],
+                        name: None,
+                    }],
+                ] {
+                    fixtures.push(OpeningHours {
+                        timezone: timezone.to_string(),
+                        weekly: weekly.clone(),
+                        special_days,
+                    });
+                }
+            }
+        }
+        fixtures
+    }
+
+    /// Needs MongoDB 5.1 or later at `MONGODB_URI`:
+    /// `cargo test -- --ignored open_now`.
+    #[tokio::test]
+    #[ignore]
+    async fn open_now_agrees_with_is_open() {
+        let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let client = mongodb::Client::with_uri_str(uri).await.unwrap();
+        let db = client.database("cucura-ccdb");
+
+        let before = Utc::now();
+        let fixtures = fixtures(before);
+        let documents: Vec<Document> = fixtures
+            .iter()
+            .enumerate()
+            .map(
+                |(i, hours)| doc! { "i": i as i64, "opening_hours": bson::to_bson(hours).unwrap() },
+            )
+            .collect();
+        let mut cursor = db
+            .aggregate([
+                doc! { "$documents": documents },
+                doc! { "$project": { "i": 1, "open": open_now() } },
+            ])
+            .await
+            .unwrap();
+        let mut answers = Vec::new();
+        while let Some(answer) = cursor.next().await {
+            let answer = answer.unwrap();
+            answers.push((
+                answer.get_i64("i").unwrap() as usize,
+                answer.get_bool("open").unwrap(),
+            ));
+        }
+        let after = Utc::now();
+        assert_eq!(answers.len(), fixtures.len());
+        for (i, open) in answers {
+            let (expected, later) = (is_open(&fixtures[i], before), is_open(&fixtures[i], after));
+            // A minute boundary between the two sides is not a disagreement.
+            if expected == later {
+                assert_eq!(open, expected, "{:?}", fixtures[i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+// search/embedded.rs
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::RwLock;
+
+use futures::future::BoxFuture;
+
+use super::sync::SyncTarget;
+use super::{
+    term_match, tokenize, DocumentKey, SearchDocument, SearchHit, SearchIndex, SearchKind,
+    SearchRequest, SearchResults,
+};
+
+struct Entry {
+    document: SearchDocument,
+    /// Field-weighted occurrences per term.
+    terms: HashMap<String, f32>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<DocumentKey, Entry>,
+    postings: HashMap<String, HashSet<DocumentKey>>,
+}
+
+impl Inner {
+    fn insert(&mut self, key: DocumentKey, document: SearchDocument) {
+        self.remove(&key);
+        let mut terms: HashMap<String, f32> = HashMap::new();
+        for (field, text) in &document.fields {
+            let weight = document.weight(field);
+            for term in tokenize(text) {
+                *terms.entry(term).or_default() += weight;
+            }
+        }
+        for term in terms.keys() {
+            self.postings.entry(term.clone()).or_default().insert(key);
+        }
+        self.entries.insert(key, Entry { document, terms });
+    }
+
+    fn remove(&mut self, key: &DocumentKey) {
+        let Some(entry) = self.entries.remove(key) else {
+            return;
+        };
+        for term in entry.terms.keys() {
+            if let Some(keys) = self.postings.get_mut(term) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    /// Per query term, each document scores its best-matching indexed term:
+    /// match quality x saturated field weight x BM25 idf. Documents matching
+    /// only some of the terms are scaled down by the fraction matched.
+    fn scores(&self, query: &str) -> HashMap<DocumentKey, f64> {
+        let query_terms: Vec<String> = tokenize(query).collect();
+        let total_docs = self.entries.len() as f64;
+        let mut scores: HashMap<DocumentKey, (f64, usize)> = HashMap::new();
+
+        for (position, query_term) in query_terms.iter().enumerate() {
+            let is_last = position + 1 == query_terms.len();
+            let mut best: HashMap<DocumentKey, f64> = HashMap::new();
+            for (term, keys) in &self.postings {
+                let quality = term_match(query_term, term, is_last);
+                if quality == 0.0 {
+                    continue;
+                }
+                let df = keys.len() as f64;
+                let idf = (1.0 + (total_docs - df + 0.5) / (df + 0.5)).ln();
+                for key in keys {
+                    let weight = self.entries[key].terms[term] as f64;
+                    let score = quality as f64 * idf * weight * 2.2 / (weight + 1.2);
+                    let current = best.entry(*key).or_default();
+                    *current = current.max(score);
+                }
+            }
+            for (key, score) in best {
+                let entry = scores.entry(key).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        scores
+            .into_iter()
+            .map(|(key, (score, matched))| (key, score * matched as f64 / query_terms.len() as f64))
+            .collect()
+    }
+}
+
+/// In-process inverted index with typo-tolerant matching, for deployments
+/// that want search without text indexes or a round trip to Mongo.
+#[derive(Default)]
+pub struct EmbeddedIndex {
+    inner: RwLock<Inner>,
+}
+
+impl EmbeddedIndex {
+    pub fn from_documents(documents: Vec<(DocumentKey, SearchDocument)>) -> EmbeddedIndex {
+        let index = EmbeddedIndex::default();
+        index.replace_all(documents);
+        index
+    }
+
+    pub fn upsert(&self, key: DocumentKey, document: SearchDocument) {
+        self.inner.write().unwrap().insert(key, document);
+    }
+
+    pub fn remove(&self, key: &DocumentKey) {
+        self.inner.write().unwrap().remove(key);
+    }
+
+    pub fn replace_all(&self, documents: Vec<(DocumentKey, SearchDocument)>) {
+        let mut inner = Inner::default();
+        for (key, document) in documents {
+            inner.insert(key, document);
+        }
+        *self.inner.write().unwrap() = inner;
+    }
+
+    pub fn query(&self, request: &SearchRequest) -> SearchResults {
+        let inner = self.inner.read().unwrap();
+        let mut facets: BTreeMap<SearchKind, u64> =
+            SearchKind::ALL.into_iter().map(|kind| (kind, 0)).collect();
+        let mut hits = Vec::new();
+        for (key, score) in inner.scores(&request.query) {
+            *facets.entry(key.0).or_default() += 1;
+            if !request.kinds.contains(&key.0) {
+                continue;
+            }
+            let document = &inner.entries[&key].document;
+            hits.push(SearchHit {
+                kind: document.kind,
+                id: document.id,
+                title: document.title.clone(),
+                subtitle: document.subtitle.clone(),
+                score,
+            });
+        }
+        hits.sort_by(SearchHit::rank);
+
+        SearchResults {
+            total: hits.len() as u64,
+            facets,
+            results: hits
+                .into_iter()
+                .skip(request.skip as usize)
+                .take(request.limit as usize)
+                .collect(),
+        }
+    }
+}
+
+impl SearchIndex for EmbeddedIndex {
+    fn search<'a>(
+        &'a self,
+        request: &'a SearchRequest,
+    ) -> BoxFuture<'a, mongodb::error::Result<SearchResults>> {
+        Box::pin(async move { Ok(self.query(request)) })
+    }
+}
+
+impl SyncTarget for EmbeddedIndex {
+    fn replace_all(&self, documents: &[(DocumentKey, SearchDocument)]) {
+        EmbeddedIndex::replace_all(self, documents.to_vec());
+    }
+
+    fn upsert(&self, key: DocumentKey, document: &SearchDocument) {
+        EmbeddedIndex::upsert(self, key, document.clone());
+    }
+
+    fn remove(&self, key: &DocumentKey) {
+        EmbeddedIndex::remove(self, key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::oid::ObjectId;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn document(
+        kind: SearchKind,
+        title: &str,
+        fields: &[(&'static str, &str)],
+    ) -> (DocumentKey, SearchDocument) {
+        let document = SearchDocument {
+            kind,
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            subtitle: None,
+            fields: fields
+                .iter()
+                .map(|(field, text)| (*field, text.to_string()))
+                .collect(),
+        };
+        ((kind, ObjectId::new()), document)
+    }
+
+    fn business(name: &str, description: &str) -> (DocumentKey, SearchDocument) {
+        document(
+            SearchKind::Business,
+            name,
+            &[("name", name), ("description", description)],
+        )
+    }
+
+    fn request(query: &str, kinds: &[SearchKind], skip: u64, limit: i64) -> SearchRequest {
+        SearchRequest {
+            query: query.to_string(),
+            kinds: kinds.to_vec(),
+            skip,
+            limit,
+        }
+    }
+
+    fn titles(results: &SearchResults) -> Vec<&str> {
+        results
+            .results
+            .iter()
+            .map(|hit| hit.title.as_str())
+            .collect()
+    }
+
+    fn directory() -> EmbeddedIndex {
+        EmbeddedIndex::from_documents(vec![
+            business("Corner Shop", "Fresh bakery goods every morning"),
+            business("Sunrise Bakery", "Breads and cakes"),
+            business("Harbour Plumbing", "Boilers and leaks"),
+            document(
+                SearchKind::Profile,
+                "baker_bob",
+                &[("username", "baker_bob"), ("interests", "bakery sourdough")],
+            ),
+            document(
+                SearchKind::Training,
+                "Bakery basics",
+                &[
+                    ("title", "Bakery basics"),
+                    ("description", "Start a bakery"),
+                ],
+            ),
+        ])
+    }
+
+    #[test]
+    fn a_name_match_ranks_above_a_description_match() {
+        let results = directory().query(&request("bakery", &[SearchKind::Business], 0, 10));
+        assert_eq!(titles(&results), ["Sunrise Bakery", "Corner Shop"]);
+        assert!(results.results[0].score > results.results[1].score);
+    }
+
+    #[test]
+    fn a_one_edit_typo_still_matches_below_the_exact_word() {
+        let exact = directory().query(&request("bakery", &[SearchKind::Business], 0, 10));
+        let typo = directory().query(&request("bakrey", &[SearchKind::Business], 0, 10));
+        assert_eq!(titles(&typo), titles(&exact));
+        assert!(typo.results[0].score < exact.results[0].score);
+        assert_eq!(
+            directory()
+                .query(&request("plumbng", &SearchKind::ALL, 0, 10))
+                .results[0]
+                .title,
+            "Harbour Plumbing"
+        );
+    }
+
+    #[test]
+    fn the_last_term_matches_as_a_prefix() {
+        let index = directory();
+        let typing = index.query(&request("sunrise bak", &SearchKind::ALL, 0, 10));
+        assert_eq!(typing.results[0].title, "Sunrise Bakery");
+        assert!(index
+            .query(&request("bak sunrise", &[SearchKind::Business], 0, 10))
+            .results
+            .iter()
+            .all(|hit| hit.title == "Sunrise Bakery"));
+    }
+
+    #[test]
+    fn documents_matching_fewer_terms_score_lower() {
+        let index = EmbeddedIndex::from_documents(vec![
+            business("Sunrise Bakery", ""),
+            business("Bakery", ""),
+        ]);
+        let both = index.query(&request("sunrise bakery", &SearchKind::ALL, 0, 10));
+        assert_eq!(titles(&both), ["Sunrise Bakery", "Bakery"]);
+        let alone = index.query(&request("bakery", &SearchKind::ALL, 0, 10));
+        let partial = both.results[1].score;
+        assert!((partial * 2.0 - alone.results[1].score).abs() < 1e-9);
+    }
+
+    #[test]
+    fn facets_count_every_kind_whatever_is_requested() {
+        let results = directory().query(&request("bakery", &[SearchKind::Business], 0, 10));
+        assert_eq!(results.total, 2);
+        assert_eq!(results.facets[&SearchKind::Business], 2);
+        assert_eq!(results.facets[&SearchKind::Profile], 1);
+        assert_eq!(results.facets[&SearchKind::Training], 1);
+        assert!(results
+            .results
+            .iter()
+            .all(|hit| hit.kind == SearchKind::Business));
+
+        let nothing = directory().query(&request("zzzz", &SearchKind::ALL, 0, 10));
+        assert_eq!(nothing.total, 0);
+        assert!(nothing.facets.values().all(|count| *count == 0));
+        assert_eq!(nothing.facets.len(), SearchKind::ALL.len());
+    }
+
+    #[test]
+    fn skip_and_limit_page_the_ranked_hits() {
+        let index = directory();
+        let all = index.query(&request("bakery", &SearchKind::ALL, 0, 10));
+        assert_eq!(all.total, 4);
+        let page = index.query(&request("bakery", &SearchKind::ALL, 1, 2));
+        assert_eq!(page.total, 4);
+        assert_eq!(titles(&page), titles(&all)[1..3]);
+        assert!(index
+            .query(&request("bakery", &SearchKind::ALL, 4, 2))
+            .results
+            .is_empty());
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_title() {
+        let index = EmbeddedIndex::from_documents(vec![
+            business("Cafe", "tea"),
+            business("Bistro", "tea"),
+            business("Deli", "tea"),
+        ]);
+        let results = index.query(&request("tea", &SearchKind::ALL, 0, 10));
+        assert_eq!(titles(&results), ["Bistro", "Cafe", "Deli"]);
+    }
+
+    #[test]
+    fn removed_and_replaced_documents_stop_matching() {
+        let index = directory();
+        let (key, mut document) = business("Old Name Bakery", "");
+        index.upsert(key, document.clone());
+        assert!(
+            titles(&index.query(&request("old", &SearchKind::ALL, 0, 10)))
+                .contains(&"Old Name Bakery")
+        );
+
+        document.title = "New Name".to_string();
+        document.fields = vec![("name", "New Name".to_string())];
+        index.upsert(key, document);
+        assert!(index
+            .query(&request("old", &SearchKind::ALL, 0, 10))
+            .results
+            .is_empty());
+
+        index.remove(&key);
+        assert!(index
+            .query(&request("new", &SearchKind::ALL, 0, 10))
+            .results
+            .is_empty());
+    }
+}
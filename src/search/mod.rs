@@ -0,0 +1,467 @@
+// search/mod.rs
+//! Unified search over businesses, profiles and trainings. Handlers talk to a
+//! [`SearchIndex`]; `SEARCH_INDEX=embedded` keeps an in-process inverted index
+//! in sync with Mongo, anything else queries the Mongo text indexes directly.
+//! Typeahead suggestions always come from the in-memory [`SuggestIndex`].
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use mongodb::{
+    bson::{self, oid::ObjectId, Document},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::models::{business::Business, profile::Profile, training::Training};
+
+pub mod embedded;
+pub mod mongo;
+pub mod suggest;
+pub mod sync;
+
+use embedded::EmbeddedIndex;
+use mongo::MongoTextIndex;
+use suggest::SuggestIndex;
+use sync::SyncTarget;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchKind {
+    Business,
+    Profile,
+    Training,
+}
+
+impl SearchKind {
+    pub const ALL: [SearchKind; 3] = [
+        SearchKind::Business,
+        SearchKind::Profile,
+        SearchKind::Training,
+    ];
+
+    pub fn collection(self) -> &'static str {
+        match self {
+            SearchKind::Business => "businesses",
+            SearchKind::Profile => "profiles",
+            SearchKind::Training => "trainings",
+        }
+    }
+
+    pub fn from_collection(collection: &str) -> Option<SearchKind> {
+        SearchKind::ALL
+            .into_iter()
+            .find(|kind| kind.collection() == collection)
+    }
+
+    /// Searched fields and their relative weights; the Mongo text indexes are
+    /// created with the same weights so both backends rank alike.
+    pub fn fields(self) -> &'static [(&'static str, i32)] {
+        match self {
+            SearchKind::Business => &[
+                ("name", 10),
+                ("industry", 5),
+                ("city", 3),
+                ("country", 3),
+                ("description", 1),
+            ],
+            SearchKind::Profile => &[("username", 10), ("interests", 5), ("bio", 1)],
+            SearchKind::Training => &[("title", 10), ("description", 1)],
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SearchKind> {
+        match value {
+            "business" | "businesses" => Some(SearchKind::Business),
+            "profile" | "profiles" => Some(SearchKind::Profile),
+            "training" | "trainings" => Some(SearchKind::Training),
+            _ => None,
+        }
+    }
+}
+
+/// Documents are keyed by collection and `_id`, the only identity a delete
+/// change event carries.
+pub type DocumentKey = (SearchKind, ObjectId);
+
+/// The searchable projection of a business, profile or training.
+#[derive(Debug, Clone)]
+pub struct SearchDocument {
+    pub kind: SearchKind,
+    pub id: Uuid,
+    pub title: String,
+    pub subtitle: Option<String>,
+    /// Text per field name from [`SearchKind::fields`].
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl SearchDocument {
+    /// Builds the search view of a raw document from one of the searched
+    /// collections; documents that do not match the model, and businesses
+    /// hidden by moderation, are skipped.
+    pub fn from_document(kind: SearchKind, document: Document) -> Option<SearchDocument> {
+        let result = match kind {
+            SearchKind::Business => bson::from_document::<Business>(document)
+                .map(|business| (!business.hidden).then(|| (&business).into())),
+            SearchKind::Profile => {
+                bson::from_document::<Profile>(document).map(|profile| Some((&profile).into()))
+            }
+            SearchKind::Training => {
+                bson::from_document::<Training>(document).map(|training| Some((&training).into()))
+            }
+        };
+        result
+            .map_err(|e| {
+                warn!(
+                    error = %e,
+                    collection = kind.collection(),
+                    "Skipping document that does not match its model"
+                )
+            })
+            .ok()
+            .flatten()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, text)| text.as_str())
+            .filter(|text| !text.is_empty())
+    }
+
+    fn weight(&self, field: &str) -> f32 {
+        self.kind
+            .fields()
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map_or(1.0, |(_, weight)| *weight as f32)
+    }
+}
+
+impl From<&Business> for SearchDocument {
+    fn from(business: &Business) -> SearchDocument {
+        let location = [business.city.as_deref(), Some(business.country.as_str())]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+        SearchDocument {
+            kind: SearchKind::Business,
+            id: business.business_id,
+            title: business.name.clone(),
+            subtitle: Some(format!("{} · {}", business.industry, location)),
+            fields: vec![
+                ("name", business.name.clone()),
+                ("industry", business.industry.clone()),
+                ("city", business.city.clone().unwrap_or_default()),
+                ("country", business.country.clone()),
+                ("description", business.description.clone()),
+            ],
+        }
+    }
+}
+
+impl From<&Profile> for SearchDocument {
+    fn from(profile: &Profile) -> SearchDocument {
+        SearchDocument {
+            kind: SearchKind::Profile,
+            id: profile.profile_id,
+            title: profile.username.clone(),
+            subtitle: profile.profile_type.clone(),
+            fields: vec![
+                ("username", profile.username.clone()),
+                (
+                    "interests",
+                    profile.interests.clone().unwrap_or_default().join(" "),
+                ),
+                ("bio", profile.bio.clone().unwrap_or_default()),
+            ],
+        }
+    }
+}
+
+impl From<&Training> for SearchDocument {
+    fn from(training: &Training) -> SearchDocument {
+        SearchDocument {
+            kind: SearchKind::Training,
+            id: training.training_id,
+            title: training.title.clone(),
+            subtitle: Some(training.duration.clone()),
+            fields: vec![
+                ("title", training.title.clone()),
+                ("description", training.description.clone()),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchRequest {
+    pub query: String,
+    /// Result types to return; facet counts always cover every type.
+    pub kinds: Vec<SearchKind>,
+    pub skip: u64,
+    pub limit: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    #[serde(rename = "type")]
+    pub kind: SearchKind,
+    pub id: Uuid,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub score: f64,
+}
+
+impl SearchHit {
+    /// Best score first; equal scores by title and then id, so the order,
+    /// and with it every page, is the same on each request.
+    pub(crate) fn rank(a: &SearchHit, b: &SearchHit) -> Ordering {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.title.cmp(&b.title))
+            .then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SearchResults {
+    /// Matches across the requested types, before pagination.
+    pub total: u64,
+    /// Matches per type, regardless of the type filter.
+    pub facets: BTreeMap<SearchKind, u64>,
+    pub results: Vec<SearchHit>,
+}
+
+pub trait SearchIndex: Send + Sync {
+    fn search<'a>(
+        &'a self,
+        request: &'a SearchRequest,
+    ) -> BoxFuture<'a, mongodb::error::Result<SearchResults>>;
+}
+
+pub struct Search {
+    pub index: Arc<dyn SearchIndex>,
+    pub suggest: Arc<SuggestIndex>,
+}
+
+/// `SEARCH_INDEX=embedded` serves queries from memory; the default uses the
+/// Mongo text indexes from migration `0003_search_text_indexes`. In-memory
+/// indexes are loaded at startup and kept current by [`sync`], falling back
+/// to a reload every `SEARCH_REFRESH_SECS`. Suggestion popularity is
+/// recomputed every `SUGGEST_POPULARITY_REFRESH_SECS`.
+pub fn from_env(client: &Client) -> Search {
+    let secs_from_env = |key: &str, default: u64| {
+        Duration::from_secs(
+            env::var(key)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default),
+        )
+    };
+
+    let suggest = Arc::new(SuggestIndex::default());
+    suggest::spawn_popularity_refresh(
+        client.clone(),
+        suggest.clone(),
+        secs_from_env("SUGGEST_POPULARITY_REFRESH_SECS", 300),
+    );
+    let mut targets: Vec<Arc<dyn SyncTarget>> = vec![suggest.clone()];
+
+    let index: Arc<dyn SearchIndex> = match env::var("SEARCH_INDEX").as_deref() {
+        Ok("embedded") => {
+            let index = Arc::new(EmbeddedIndex::default());
+            targets.push(index.clone());
+            index
+        }
+        _ => Arc::new(MongoTextIndex::new(client)),
+    };
+    sync::spawn(
+        client.clone(),
+        targets,
+        secs_from_env("SEARCH_REFRESH_SECS", 60),
+    );
+
+    Search { index, suggest }
+}
+
+/// Lowercased alphanumeric words of at least two characters.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().nth(1).is_some())
+        .map(str::to_lowercase)
+}
+
+/// Edits tolerated for a query term: none for short words, where a single
+/// edit already turns most words into other words.
+fn max_edits(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Optimal string alignment distance (Levenshtein plus adjacent
+/// transpositions), or `None` once it exceeds `max`.
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        let mut row_min = usize::MAX;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+            row_min = row_min.min(distance);
+        }
+        if row_min > max {
+            return None;
+        }
+    }
+    Some(rows[a.len()][b.len()]).filter(|distance| *distance <= max)
+}
+
+/// How well an indexed term stands in for a query term, from 1.0 for an exact
+/// match down to 0 for no match. The last query term may also match as a
+/// prefix, since it is usually still being typed.
+pub(crate) fn term_match(query_term: &str, term: &str, is_last: bool) -> f32 {
+    if query_term == term {
+        return 1.0;
+    }
+    if is_last && query_term.chars().nth(2).is_some() && term.starts_with(query_term) {
+        return 0.8;
+    }
+    match edit_distance(query_term, term, max_edits(query_term)) {
+        Some(1) => 0.6,
+        Some(2) => 0.35,
+        _ => 0.0,
+    }
+}
+
+/// Case-insensitive regex matching `term` with up to one substitution,
+/// insertion, deletion or transposition, used when the text index finds
+/// nothing. Terms come from [`tokenize`] so contain no regex metacharacters.
+fn fuzzy_pattern(term: &str) -> String {
+    let chars: Vec<char> = term.chars().collect();
+    if max_edits(term) == 0 {
+        return term.to_string();
+    }
+    let slice = |range: std::ops::Range<usize>| chars[range].iter().collect::<String>();
+    let n = chars.len();
+    let mut alternatives = vec![term.to_string()];
+    for i in 0..n {
+        alternatives.push(format!("{}.{}", slice(0..i), slice(i + 1..n)));
+        alternatives.push(format!("{}{}", slice(0..i), slice(i + 1..n)));
+        if i + 1 < n {
+            alternatives.push(format!(
+                "{}{}{}{}",
+                slice(0..i),
+                chars[i + 1],
+                chars[i],
+                slice(i + 2..n)
+            ));
+        }
+    }
+    for i in 0..=n {
+        alternatives.push(format!("{}.{}", slice(0..i), slice(i..n)));
+    }
+    alternatives.join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_lowercases_words_of_two_or_more_characters() {
+        let terms: Vec<String> = tokenize("Joe's Café & B2B-Supplies, a Z").collect();
+        assert_eq!(terms, ["joe", "café", "b2b", "supplies"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_as_one_edit() {
+        assert_eq!(edit_distance("bakery", "bakery", 2), Some(0));
+        assert_eq!(edit_distance("bakery", "bakry", 2), Some(1));
+        assert_eq!(edit_distance("bakery", "bakrey", 2), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance("cat", "catalogue", 2), None);
+    }
+
+    #[test]
+    fn short_terms_tolerate_no_edits() {
+        assert_eq!(max_edits("spa"), 0);
+        assert_eq!(max_edits("cafe"), 1);
+        assert_eq!(max_edits("plumbing"), 2);
+        assert_eq!(term_match("spa", "spy", false), 0.0);
+    }
+
+    #[test]
+    fn term_match_ranks_exact_then_prefix_then_edits() {
+        assert_eq!(term_match("bakery", "bakery", false), 1.0);
+        assert_eq!(term_match("bakry", "bakery", false), 0.6);
+        assert_eq!(term_match("plumbng", "plumbing", true), 0.6);
+        assert_eq!(term_match("plamming", "plumbing", false), 0.35);
+        // Two edits need a query term of eight characters or more.
+        assert_eq!(term_match("plmbng", "plumbing", false), 0.0);
+        assert_eq!(term_match("bakery", "brewery", false), 0.0);
+    }
+
+    #[test]
+    fn only_the_last_term_matches_as_a_prefix() {
+        assert_eq!(term_match("plum", "plumbing", true), 0.8);
+        assert_eq!(term_match("plum", "plumbing", false), 0.0);
+        // Two characters are too few to guess the word from.
+        assert_eq!(term_match("pl", "plumbing", true), 0.0);
+    }
+
+    #[test]
+    fn fuzzy_pattern_lists_every_one_edit_variant() {
+        assert_eq!(fuzzy_pattern("spa"), "spa");
+
+        let pattern = fuzzy_pattern("cafe");
+        let alternatives: Vec<&str> = pattern.split('|').collect();
+        // The term, then per character a substitution and a deletion, per
+        // pair a transposition, and per gap an insertion.
+        assert_eq!(alternatives.len(), 1 + 4 * 2 + 3 + 5);
+        assert_eq!(alternatives[0], "cafe");
+        for variant in [
+            ".afe", "afe", "acfe", "ca.e", "cae", "caef", ".cafe", "caf.e", "cafe.",
+        ] {
+            assert!(alternatives.contains(&variant), "missing {}", variant);
+        }
+        assert!(pattern
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '|'));
+    }
+
+    #[test]
+    fn fuzzy_pattern_counts_characters_not_bytes() {
+        let pattern = fuzzy_pattern("café");
+        assert!(pattern.split('|').any(|variant| variant == "caf."));
+        assert!(pattern.split('|').any(|variant| variant == "caé"));
+    }
+}
@@ -0,0 +1,238 @@
+// search/mongo.rs
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    Client, Database,
+};
+
+use super::embedded::EmbeddedIndex;
+use super::{
+    fuzzy_pattern, tokenize, SearchDocument, SearchHit, SearchIndex, SearchKind, SearchRequest,
+    SearchResults,
+};
+
+/// Candidates per collection fetched for the typo-tolerant fallback.
+const FUZZY_CANDIDATES: i64 = 500;
+/// Query terms beyond this are ignored by the fallback to keep the regex small.
+const FUZZY_MAX_TERMS: usize = 5;
+
+/// Queries the weighted text indexes on each collection. Text indexes only
+/// match whole (stemmed) words, so a query with no matches at all is retried
+/// with one-edit regexes and ranked in process like the embedded index.
+pub struct MongoTextIndex {
+    db: Database,
+}
+
+impl MongoTextIndex {
+    pub fn new(client: &Client) -> MongoTextIndex {
+        MongoTextIndex {
+            db: client.database("cucura-ccdb"),
+        }
+    }
+
+    async fn text_search(&self, request: &SearchRequest) -> mongodb::error::Result<SearchResults> {
+        let filter = doc! { "$text": { "$search": &request.query }, "hidden": { "$ne": true } };
+        let mut results = SearchResults::default();
+        for kind in SearchKind::ALL {
+            let count = self
+                .db
+                .collection::<Document>(kind.collection())
+                .count_documents(filter.clone())
+                .await?;
+            results.facets.insert(kind, count);
+        }
+
+        // Each collection's top `skip + limit` is enough to page the merged
+        // list. Ties are broken by title here as well, so the cut-off does
+        // not change between pages.
+        let mut hits = Vec::new();
+        for kind in request
+            .kinds
+            .iter()
+            .copied()
+            .filter(|kind| results.facets[kind] > 0)
+        {
+            let title = kind.fields()[0].0;
+            let mut cursor = self
+                .db
+                .collection::<Document>(kind.collection())
+                .find(filter.clone())
+                .projection(doc! { "score": { "$meta": "textScore" } })
+                .sort(doc! { "score": { "$meta": "textScore" }, title: 1, "_id": 1 })
+                .limit(request.skip as i64 + request.limit)
+                .await?;
+            let mut kind_hits = Vec::new();
+            while let Some(document) = cursor.next().await {
+                let mut document = document?;
+                let score = match document.remove("score") {
+                    Some(Bson::Double(score)) => score,
+                    _ => 0.0,
+                };
+                if let Some(document) = SearchDocument::from_document(kind, document) {
+                    kind_hits.push(SearchHit {
+                        kind,
+                        id: document.id,
+                        title: document.title,
+                        subtitle: document.subtitle,
+                        score,
+                    });
+                }
+            }
+            normalise(&mut kind_hits);
+            hits.extend(kind_hits);
+        }
+        hits.sort_by(SearchHit::rank);
+
+        results.total = request.kinds.iter().map(|kind| results.facets[kind]).sum();
+        results.results = hits
+            .into_iter()
+            .skip(request.skip as usize)
+            .take(request.limit as usize)
+            .collect();
+        Ok(results)
+    }
+
+    async fn fuzzy_search(&self, request: &SearchRequest) -> mongodb::error::Result<SearchResults> {
+        let patterns: Vec<String> = tokenize(&request.query)
+            .take(FUZZY_MAX_TERMS)
+            .map(|term| fuzzy_pattern(&term))
+            .collect();
+        if patterns.is_empty() {
+            return Ok(SearchResults {
+                facets: SearchKind::ALL.into_iter().map(|kind| (kind, 0)).collect(),
+                ..SearchResults::default()
+            });
+        }
+        let pattern = format!("({})", patterns.join("|"));
+
+        let mut candidates = Vec::new();
+        for kind in SearchKind::ALL {
+            let conditions: Vec<Document> = kind
+                .fields()
+                .iter()
+                .map(|(field, _)| doc! { *field: { "$regex": &pattern, "$options": "i" } })
+                .collect();
+            let mut cursor = self
+                .db
+                .collection::<Document>(kind.collection())
+                .find(doc! { "$or": conditions, "hidden": { "$ne": true } })
+                .limit(FUZZY_CANDIDATES)
+                .await?;
+            while let Some(document) = cursor.next().await {
+                let document = document?;
+                let Ok(object_id) = document.get_object_id("_id") else {
+                    continue;
+                };
+                if let Some(search_document) = SearchDocument::from_document(kind, document) {
+                    candidates.push(((kind, object_id), search_document));
+                }
+            }
+        }
+
+        Ok(EmbeddedIndex::from_documents(candidates).query(request))
+    }
+}
+
+/// Scales one collection's text scores so its best hit scores 1. Raw scores
+/// grow with the collection's field weights and so cannot be compared
+/// across collections.
+fn normalise(hits: &mut [SearchHit]) {
+    let best = hits.iter().map(|hit| hit.score).fold(0.0, f64::max);
+    if best > 0.0 {
+        for hit in hits {
+            hit.score /= best;
+        }
+    }
+}
+
+impl SearchIndex for MongoTextIndex {
+    fn search<'a>(
+        &'a self,
+        request: &'a SearchRequest,
+    ) -> BoxFuture<'a, mongodb::error::Result<SearchResults>> {
+        Box::pin(async move {
+            let results = self.text_search(request).await?;
+            if results.facets.values().all(|count| *count == 0) {
+                return self.fuzzy_search(request).await;
+            }
+            Ok(results)
+        })
+    }
+}
+
+/// Weights for the text index of `kind`, matching [`SearchKind::fields`].
+pub fn text_index_keys(kind: SearchKind) -> (Document, Document) {
+    let mut keys = Document::new();
+    let mut weights = Document::new();
+    for (field, weight) in kind.fields() {
+        keys.insert(*field, "text");
+        weights.insert(*field, *weight);
+    }
+    (keys, weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn hit(kind: SearchKind, title: &str, score: f64) -> SearchHit {
+        SearchHit {
+            kind,
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            subtitle: None,
+            score,
+        }
+    }
+
+    #[test]
+    fn scores_are_relative_to_the_collection_best() {
+        let mut businesses = [
+            hit(SearchKind::Business, "Sunrise Bakery", 16.5),
+            hit(SearchKind::Business, "Corner Shop", 5.5),
+        ];
+        let mut trainings = [hit(SearchKind::Training, "Bakery basics", 11.0)];
+        normalise(&mut businesses);
+        normalise(&mut trainings);
+        assert_eq!(businesses[0].score, 1.0);
+        assert!((businesses[1].score - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(trainings[0].score, 1.0);
+
+        let mut nothing = [hit(SearchKind::Profile, "zero", 0.0)];
+        normalise(&mut nothing);
+        assert_eq!(nothing[0].score, 0.0);
+    }
+
+    #[test]
+    fn merged_hits_rank_by_score_then_title() {
+        let mut hits = [
+            hit(SearchKind::Training, "Bakery basics", 1.0),
+            hit(SearchKind::Business, "Corner Shop", 0.4),
+            hit(SearchKind::Business, "Sunrise Bakery", 1.0),
+            hit(SearchKind::Profile, "baker_bob", 0.4),
+        ];
+        hits.sort_by(SearchHit::rank);
+        let titles: Vec<&str> = hits.iter().map(|hit| hit.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            [
+                "Bakery basics",
+                "Sunrise Bakery",
+                "Corner Shop",
+                "baker_bob"
+            ]
+        );
+
+        let same = hit(SearchKind::Business, "Twin", 1.0);
+        let twin = SearchHit {
+            id: Uuid::nil(),
+            ..same.clone()
+        };
+        let mut twins = [same, twin];
+        twins.sort_by(SearchHit::rank);
+        assert_eq!(twins[0].id, Uuid::nil());
+    }
+}
@@ -0,0 +1,462 @@
+// search/suggest.rs
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    Client, Database,
+};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::sync::SyncTarget;
+use super::{DocumentKey, SearchDocument, SearchKind};
+
+/// Prefix keys visited per query; short prefixes on a large directory stop
+/// here rather than scoring every entry.
+const MAX_SCANNED_KEYS: usize = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionKind {
+    Business,
+    Profile,
+    Industry,
+    City,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    #[serde(rename = "type")]
+    pub kind: SuggestionKind,
+    pub text: String,
+    /// Business or profile id; industries and cities have none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Target {
+    Document(DocumentKey),
+    Industry(String),
+    City(String),
+}
+
+struct Listed {
+    id: Uuid,
+    text: String,
+    industry: Option<String>,
+    city: Option<String>,
+}
+
+/// An industry or city, suggested once however many businesses share it.
+struct Facet {
+    text: String,
+    businesses: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    documents: HashMap<DocumentKey, Listed>,
+    facets: HashMap<Target, Facet>,
+    /// Normalized text from each word start to the end, so "plu" finds
+    /// "Acme Plumbing". The flag marks keys that start at the first word.
+    prefixes: BTreeMap<String, HashMap<Target, bool>>,
+}
+
+impl Inner {
+    fn index_text(&mut self, text: &str, target: &Target) {
+        let normalized = normalize(text);
+        for (position, suffix) in word_suffixes(&normalized).enumerate() {
+            self.prefixes
+                .entry(suffix.to_string())
+                .or_default()
+                .insert(target.clone(), position == 0);
+        }
+    }
+
+    fn unindex_text(&mut self, text: &str, target: &Target) {
+        let normalized = normalize(text);
+        for suffix in word_suffixes(&normalized) {
+            if let Some(targets) = self.prefixes.get_mut(suffix) {
+                targets.remove(target);
+                if targets.is_empty() {
+                    self.prefixes.remove(suffix);
+                }
+            }
+        }
+    }
+
+    fn add_facet(&mut self, target: Target, text: &str) {
+        match self.facets.get_mut(&target) {
+            Some(facet) => facet.businesses += 1,
+            None => {
+                self.index_text(text, &target);
+                self.facets.insert(
+                    target,
+                    Facet {
+                        text: text.to_string(),
+                        businesses: 1,
+                    },
+                );
+            }
+        }
+    }
+
+    fn remove_facet(&mut self, target: Target) {
+        let Some(facet) = self.facets.get_mut(&target) else {
+            return;
+        };
+        facet.businesses -= 1;
+        if facet.businesses == 0 {
+            let facet = self.facets.remove(&target).unwrap();
+            self.unindex_text(&facet.text, &target);
+        }
+    }
+
+    fn insert(&mut self, key: DocumentKey, document: &SearchDocument) {
+        self.remove(&key);
+        let listed = match key.0 {
+            SearchKind::Business => Listed {
+                id: document.id,
+                text: document.title.clone(),
+                industry: document.field("industry").map(str::to_string),
+                city: document.field("city").map(str::to_string),
+            },
+            SearchKind::Profile => Listed {
+                id: document.id,
+                text: document.title.clone(),
+                industry: None,
+                city: None,
+            },
+            SearchKind::Training => return,
+        };
+        self.index_text(&listed.text, &Target::Document(key));
+        if let Some(industry) = &listed.industry {
+            self.add_facet(Target::Industry(normalize(industry)), industry);
+        }
+        if let Some(city) = &listed.city {
+            self.add_facet(Target::City(normalize(city)), city);
+        }
+        self.documents.insert(key, listed);
+    }
+
+    fn remove(&mut self, key: &DocumentKey) {
+        let Some(listed) = self.documents.remove(key) else {
+            return;
+        };
+        self.unindex_text(&listed.text, &Target::Document(*key));
+        if let Some(industry) = &listed.industry {
+            self.remove_facet(Target::Industry(normalize(industry)));
+        }
+        if let Some(city) = &listed.city {
+            self.remove_facet(Target::City(normalize(city)));
+        }
+    }
+}
+
+/// As-you-type suggestions for business names, usernames, industries and
+/// cities, served from memory and kept current by [`super::sync`].
+/// Popular entries (reviews for businesses, views for profiles, listed
+/// businesses for industries and cities) rank first among equal matches.
+#[derive(Default)]
+pub struct SuggestIndex {
+    inner: RwLock<Inner>,
+    /// Review counts and profile views by business or profile id, refreshed
+    /// periodically rather than on every review or view.
+    popularity: RwLock<HashMap<Uuid, u64>>,
+}
+
+impl SuggestIndex {
+    pub fn suggest(&self, query: &str, limit: usize) -> Vec<Suggestion> {
+        let needle = normalize(query);
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let inner = self.inner.read().unwrap();
+        let popularity = self.popularity.read().unwrap();
+
+        let mut best: HashMap<&Target, f64> = HashMap::new();
+        for (key, targets) in inner
+            .prefixes
+            .range(needle.clone()..)
+            .take_while(|(key, _)| key.starts_with(&needle))
+            .take(MAX_SCANNED_KEYS)
+        {
+            for (target, starts_text) in targets {
+                let mut quality = if *starts_text { 2.0 } else { 1.0 };
+                if *key == needle {
+                    quality += 1.0;
+                }
+                let weight = match target {
+                    Target::Document(key) => popularity
+                        .get(&inner.documents[key].id)
+                        .copied()
+                        .unwrap_or(0),
+                    facet => inner.facets[facet].businesses,
+                };
+                let score = quality * (1.0 + (weight as f64).ln_1p());
+                let current = best.entry(target).or_default();
+                *current = current.max(score);
+            }
+        }
+
+        let mut suggestions: Vec<(f64, Suggestion)> = best
+            .into_iter()
+            .map(|(target, score)| {
+                let suggestion = match target {
+                    Target::Document(key) => Suggestion {
+                        kind: match key.0 {
+                            SearchKind::Profile => SuggestionKind::Profile,
+                            _ => SuggestionKind::Business,
+                        },
+                        text: inner.documents[key].text.clone(),
+                        id: Some(inner.documents[key].id),
+                    },
+                    Target::Industry(_) => Suggestion {
+                        kind: SuggestionKind::Industry,
+                        text: inner.facets[target].text.clone(),
+                        id: None,
+                    },
+                    Target::City(_) => Suggestion {
+                        kind: SuggestionKind::City,
+                        text: inner.facets[target].text.clone(),
+                        id: None,
+                    },
+                };
+                (score, suggestion)
+            })
+            .collect();
+        suggestions.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .total_cmp(a_score)
+                .then_with(|| a.text.len().cmp(&b.text.len()))
+                .then_with(|| a.text.cmp(&b.text))
+        });
+        suggestions
+            .into_iter()
+            .take(limit)
+            .map(|(_, suggestion)| suggestion)
+            .collect()
+    }
+
+    async fn refresh_popularity(&self, db: &Database) -> mongodb::error::Result<()> {
+        #[derive(Deserialize)]
+        struct Count {
+            #[serde(rename = "_id")]
+            id: Uuid,
+            count: i64,
+        }
+
+        let mut popularity = HashMap::new();
+        let mut reviews = db
+            .collection::<Document>("reviews")
+            .aggregate([doc! { "$group": { "_id": "$business_id", "count": { "$sum": 1 } } }])
+            .with_type::<Count>()
+            .await?;
+        while let Some(count) = reviews.next().await {
+            let count = count?;
+            popularity.insert(count.id, count.count.max(0) as u64);
+        }
+        let mut views = db
+            .collection::<Document>("profile_views")
+            .aggregate([doc! { "$project": { "_id": "$profile_id", "count": "$views" } }])
+            .with_type::<Count>()
+            .await?;
+        while let Some(count) = views.next().await {
+            let count = count?;
+            popularity.insert(count.id, count.count.max(0) as u64);
+        }
+
+        *self.popularity.write().unwrap() = popularity;
+        Ok(())
+    }
+}
+
+impl SyncTarget for SuggestIndex {
+    fn replace_all(&self, documents: &[(DocumentKey, SearchDocument)]) {
+        let mut inner = Inner::default();
+        for (key, document) in documents {
+            inner.insert(*key, document);
+        }
+        *self.inner.write().unwrap() = inner;
+    }
+
+    fn upsert(&self, key: DocumentKey, document: &SearchDocument) {
+        self.inner.write().unwrap().insert(key, document);
+    }
+
+    fn remove(&self, key: &DocumentKey) {
+        self.inner.write().unwrap().remove(key);
+    }
+}
+
+/// Recomputes popularity every `interval`.
+pub fn spawn_popularity_refresh(client: Client, index: Arc<SuggestIndex>, interval: Duration) {
+    actix_web::rt::spawn(async move {
+        let db = client.database("cucura-ccdb");
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = index.refresh_popularity(&db).await {
+                warn!(error = %e, "Failed to refresh suggestion popularity");
+            }
+        }
+    });
+}
+
+/// Lowercased words joined by single spaces. Unlike [`super::tokenize`],
+/// single characters are kept: they are the first keystroke of a query.
+fn normalize(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `"acme plumbing ltd"`, `"plumbing ltd"`, `"ltd"`.
+fn word_suffixes(normalized: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(0)
+        .chain(normalized.match_indices(' ').map(|(index, _)| index + 1))
+        .map(|start| &normalized[start..])
+        .filter(|suffix| !suffix.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::oid::ObjectId;
+
+    use super::*;
+
+    fn business(name: &str, industry: &str, city: &str) -> (DocumentKey, SearchDocument) {
+        let document = SearchDocument {
+            kind: SearchKind::Business,
+            id: Uuid::new_v4(),
+            title: name.to_string(),
+            subtitle: None,
+            fields: vec![
+                ("name", name.to_string()),
+                ("industry", industry.to_string()),
+                ("city", city.to_string()),
+            ],
+        };
+        ((SearchKind::Business, ObjectId::new()), document)
+    }
+
+    fn texts(index: &SuggestIndex, query: &str) -> Vec<String> {
+        index
+            .suggest(query, 10)
+            .into_iter()
+            .map(|suggestion| suggestion.text)
+            .collect()
+    }
+
+    #[test]
+    fn normalizes_and_splits_at_word_starts() {
+        assert_eq!(normalize("  Acme-Plumbing, LTD "), "acme plumbing ltd");
+        assert_eq!(
+            word_suffixes("acme plumbing ltd").collect::<Vec<_>>(),
+            ["acme plumbing ltd", "plumbing ltd", "ltd"]
+        );
+        assert_eq!(word_suffixes("").count(), 0);
+    }
+
+    #[test]
+    fn matches_any_word_start() {
+        let index = SuggestIndex::default();
+        index.replace_all(&[business("Acme Plumbing", "Construction", "Nairobi")]);
+        assert_eq!(texts(&index, "plu"), ["Acme Plumbing"]);
+        assert_eq!(texts(&index, "ACME p"), ["Acme Plumbing"]);
+        assert_eq!(texts(&index, "nai"), ["Nairobi"]);
+        assert!(texts(&index, "umb").is_empty());
+        assert!(texts(&index, " - ").is_empty());
+    }
+
+    #[test]
+    fn ranks_first_word_and_exact_matches_higher() {
+        let index = SuggestIndex::default();
+        index.replace_all(&[
+            business("Best Bakery", "Food", "Mombasa"),
+            business("Bakery Supplies", "Wholesale", "Kisumu"),
+            business("Bakery", "Food", "Kisumu"),
+        ]);
+        assert_eq!(
+            texts(&index, "bak"),
+            ["Bakery", "Bakery Supplies", "Best Bakery"]
+        );
+        // A whole-word match counts as much as starting the name.
+        assert_eq!(
+            texts(&index, "bakery"),
+            ["Bakery", "Best Bakery", "Bakery Supplies"]
+        );
+    }
+
+    #[test]
+    fn popularity_breaks_ties() {
+        let index = SuggestIndex::default();
+        let quiet = business("Sunrise Cafe", "Food", "Nakuru");
+        let busy = business("Sunset Cafe", "Food", "Nakuru");
+        index.popularity.write().unwrap().insert(busy.1.id, 40);
+        index.replace_all(&[quiet, busy]);
+        assert_eq!(texts(&index, "sun"), ["Sunset Cafe", "Sunrise Cafe"]);
+    }
+
+    #[test]
+    fn facets_are_suggested_once_while_any_business_has_them() {
+        let index = SuggestIndex::default();
+        let (first_key, first) = business("Acme", "Plumbing", "Nairobi");
+        let (second_key, second) = business("Bolt", "Plumbing", "Nairobi");
+        index.upsert(first_key, &first);
+        index.upsert(second_key, &second);
+        let suggestions = index.suggest("plumbing", 10);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].kind, SuggestionKind::Industry);
+        assert_eq!(suggestions[0].id, None);
+
+        index.remove(&first_key);
+        assert_eq!(texts(&index, "plumbing"), ["Plumbing"]);
+        index.remove(&second_key);
+        assert!(texts(&index, "plumbing").is_empty());
+        assert!(texts(&index, "nairobi").is_empty());
+    }
+
+    #[test]
+    fn upsert_replaces_the_previous_version() {
+        let index = SuggestIndex::default();
+        let (key, mut document) = business("Old Name", "Retail", "Eldoret");
+        index.upsert(key, &document);
+        document.title = "New Name".to_string();
+        document.fields = vec![
+            ("name", "New Name".to_string()),
+            ("industry", "Retail".to_string()),
+        ];
+        index.upsert(key, &document);
+        assert!(texts(&index, "old").is_empty());
+        assert_eq!(texts(&index, "new"), ["New Name"]);
+        assert!(texts(&index, "eldoret").is_empty());
+    }
+
+    #[test]
+    fn skips_trainings_and_respects_the_limit() {
+        let index = SuggestIndex::default();
+        let training = SearchDocument {
+            kind: SearchKind::Training,
+            id: Uuid::new_v4(),
+            title: "Bookkeeping basics".to_string(),
+            subtitle: None,
+            fields: Vec::new(),
+        };
+        index.upsert((SearchKind::Training, ObjectId::new()), &training);
+        assert!(texts(&index, "book").is_empty());
+
+        let businesses: Vec<_> = (0..5)
+            .map(|i| business(&format!("Shop {}", i), "Retail", "Thika"))
+            .collect();
+        index.replace_all(&businesses);
+        assert_eq!(index.suggest("shop", 3).len(), 3);
+    }
+}
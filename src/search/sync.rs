@@ -0,0 +1,146 @@
+// search/sync.rs
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    change_stream::event::{ChangeStreamEvent, OperationType},
+    options::FullDocumentType,
+    Client, Database,
+};
+use tracing::{info, warn};
+
+use super::{DocumentKey, SearchDocument, SearchKind};
+
+/// An in-memory structure derived from the searched collections.
+pub trait SyncTarget: Send + Sync {
+    fn replace_all(&self, documents: &[(DocumentKey, SearchDocument)]);
+    fn upsert(&self, key: DocumentKey, document: &SearchDocument);
+    fn remove(&self, key: &DocumentKey);
+}
+
+/// Keeps `targets` in step with the searched collections: a full load, then
+/// change stream events. Change streams need a replica set; against a
+/// standalone server the targets are instead reloaded every `refresh`.
+pub fn spawn(client: Client, targets: Vec<Arc<dyn SyncTarget>>, refresh: Duration) {
+    actix_web::rt::spawn(async move {
+        let db = client.database("cucura-ccdb");
+        let collections: Vec<&str> = SearchKind::ALL.iter().map(|k| k.collection()).collect();
+        let mut warned = false;
+        loop {
+            // Opened before the load so no write falls between the two.
+            let stream = db
+                .watch()
+                .pipeline([doc! { "$match": { "ns.coll": { "$in": &collections } } }])
+                .full_document(FullDocumentType::UpdateLookup)
+                .await;
+
+            match load_all(&db).await {
+                Ok(documents) => {
+                    info!(documents = documents.len(), "Search index loaded");
+                    for target in &targets {
+                        target.replace_all(&documents);
+                    }
+                }
+                Err(e) => warn!(error = %e, "Failed to load search index"),
+            }
+
+            match stream {
+                Ok(mut stream) => {
+                    warned = false;
+                    while let Some(event) = stream.next().await {
+                        match event {
+                            Ok(event) => {
+                                if !apply(&targets, event) {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                warn!(error = %e, "Search index change stream failed");
+                                tokio::time::sleep(refresh).await;
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    if !warned {
+                        warn!(
+                            error = %e,
+                            refresh_secs = refresh.as_secs(),
+                            "Change streams unavailable, reloading the search index periodically"
+                        );
+                        warned = true;
+                    }
+                    tokio::time::sleep(refresh).await;
+                }
+            }
+        }
+    });
+}
+
+async fn load_all(db: &Database) -> mongodb::error::Result<Vec<(DocumentKey, SearchDocument)>> {
+    let mut documents = Vec::new();
+    for kind in SearchKind::ALL {
+        let mut cursor = db
+            .collection::<Document>(kind.collection())
+            .find(doc! {})
+            .await?;
+        while let Some(document) = cursor.next().await {
+            let document = document?;
+            let Ok(object_id) = document.get_object_id("_id") else {
+                continue;
+            };
+            if let Some(search_document) = SearchDocument::from_document(kind, document) {
+                documents.push(((kind, object_id), search_document));
+            }
+        }
+    }
+    Ok(documents)
+}
+
+/// Applies one change event; `false` when the stream has to be reopened and
+/// the index reloaded (collection dropped or renamed).
+fn apply(targets: &[Arc<dyn SyncTarget>], event: ChangeStreamEvent<Document>) -> bool {
+    let Some(kind) = event
+        .ns
+        .as_ref()
+        .and_then(|ns| ns.coll.as_deref())
+        .and_then(SearchKind::from_collection)
+    else {
+        return !matches!(
+            event.operation_type,
+            OperationType::Invalidate | OperationType::DropDatabase
+        );
+    };
+    let Some(object_id) = event
+        .document_key
+        .as_ref()
+        .and_then(|key| key.get_object_id("_id").ok())
+    else {
+        return !matches!(
+            event.operation_type,
+            OperationType::Drop | OperationType::Rename
+        );
+    };
+    let key = (kind, object_id);
+
+    match (event.operation_type, event.full_document) {
+        (
+            OperationType::Insert | OperationType::Update | OperationType::Replace,
+            Some(document),
+        ) => match SearchDocument::from_document(kind, document) {
+            Some(document) => targets
+                .iter()
+                .for_each(|target| target.upsert(key, &document)),
+            None => targets.iter().for_each(|target| target.remove(&key)),
+        },
+        // Updated, then deleted before the lookup ran.
+        (OperationType::Update, None) | (OperationType::Delete, _) => {
+            targets.iter().for_each(|target| target.remove(&key))
+        }
+        _ => {}
+    }
+    true
+}
@@ -0,0 +1,276 @@
+// taxonomy.rs
+use std::collections::{HashMap, HashSet};
+
+use futures::StreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    Database,
+};
+use serde::Deserialize;
+
+use crate::models::category::Category;
+use crate::search::{term_match, tokenize};
+
+/// Words that say nothing about the industry.
+const STOPWORDS: &[&str] = &["and", "of", "the", "for", "in", "ltd", "limited", "company"];
+
+/// Longer industry texts are descriptions, not labels; mapping them would
+/// mostly be guessing.
+const MAX_MATCHED_TERMS: usize = 6;
+
+/// Average per-term match quality below which a text stays unmapped.
+const MIN_MATCH_SCORE: f32 = 0.6;
+
+/// Every category, ordered by code (so parents come before their children).
+pub async fn load(db: &Database) -> mongodb::error::Result<Vec<Category>> {
+    let mut cursor = db
+        .collection::<Category>("categories")
+        .find(doc! {})
+        .sort(doc! { "code": 1 })
+        .await?;
+    let mut categories = Vec::new();
+    while let Some(category) = cursor.next().await {
+        categories.push(category?);
+    }
+    Ok(categories)
+}
+
+/// Codes are short runs of uppercase letters and digits, e.g. `G47`.
+pub fn valid_code(code: &str) -> bool {
+    (1..=10).contains(&code.len())
+        && code
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Checks where a new code fits: it must extend its parent's code, and no
+/// other code may be a prefix of it or extend it, or prefix matching would
+/// pull it into the wrong subtree.
+pub fn check_new_code(
+    code: &str,
+    parent: Option<&Category>,
+    categories: &[Category],
+) -> Result<(), String> {
+    if !valid_code(code) {
+        return Err("code must be 1-10 uppercase letters or digits".to_string());
+    }
+    if let Some(parent) = parent {
+        if code.len() <= parent.code.len() || !code.starts_with(&parent.code) {
+            return Err(format!(
+                "code must extend its parent's code {}",
+                parent.code
+            ));
+        }
+    }
+    for existing in categories {
+        let ancestor = parent.is_some_and(|parent| parent.code.starts_with(&existing.code));
+        if existing.code.starts_with(code) || (code.starts_with(&existing.code) && !ancestor) {
+            return Err(format!(
+                "code {} overlaps existing code {}",
+                code, existing.code
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Validates and de-duplicates business category codes, keeping their order.
+/// Returns the first unknown code as the error.
+pub fn resolve(codes: &[String], categories: &[Category]) -> Result<Vec<String>, String> {
+    let mut resolved: Vec<String> = Vec::new();
+    for code in codes {
+        let code = code.trim().to_uppercase();
+        if !categories.iter().any(|category| category.code == code) {
+            return Err(code);
+        }
+        if !resolved.contains(&code) {
+            resolved.push(code);
+        }
+    }
+    Ok(resolved)
+}
+
+/// A business's categories: the given codes if all are known, otherwise
+/// the best match for its free-text industry (possibly none).
+pub fn categorize(
+    codes: &[String],
+    industry: Option<&str>,
+    categories: &[Category],
+) -> Result<Vec<String>, String> {
+    if !codes.is_empty() {
+        return resolve(codes, categories);
+    }
+    Ok(industry
+        .and_then(|industry| best_match(industry, categories))
+        .map(|category| vec![category.code.clone()])
+        .unwrap_or_default())
+}
+
+pub fn find<'a>(code: &str, categories: &'a [Category]) -> Option<&'a Category> {
+    categories.iter().find(|category| category.code == code)
+}
+
+/// The category a free-text industry most likely means, matching its words
+/// against category names and keywords with the search typo tolerance.
+/// Broader categories win ties, as the safer guess.
+pub fn best_match<'a>(text: &str, categories: &'a [Category]) -> Option<&'a Category> {
+    let text = text.trim();
+    if let Some(category) = categories.iter().find(|category| {
+        category.code.eq_ignore_ascii_case(text) || category.name.eq_ignore_ascii_case(text)
+    }) {
+        return Some(category);
+    }
+
+    let terms: Vec<String> = tokenize(text)
+        .filter(|term| !STOPWORDS.contains(&term.as_str()))
+        .collect();
+    if terms.is_empty() || terms.len() > MAX_MATCHED_TERMS {
+        return None;
+    }
+    let mut best: Option<(&Category, f32)> = None;
+    for category in categories {
+        // A category also answers to its ancestors' words: "dairy farming"
+        // is dairy (A01) within farming (A).
+        let vocabulary: Vec<String> = categories
+            .iter()
+            .filter(|ancestor| category.code.starts_with(&ancestor.code))
+            .flat_map(|ancestor| {
+                tokenize(&ancestor.name).chain(
+                    ancestor
+                        .keywords
+                        .iter()
+                        .flat_map(|keyword| tokenize(keyword)),
+                )
+            })
+            .collect();
+        let score = terms
+            .iter()
+            .map(|term| {
+                vocabulary
+                    .iter()
+                    .map(|word| term_match(term, word, true))
+                    .fold(0.0, f32::max)
+            })
+            .sum::<f32>()
+            / terms.len() as f32;
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((category, score));
+        }
+    }
+    best.filter(|(_, score)| *score >= MIN_MATCH_SCORE)
+        .map(|(category, _)| category)
+}
+
+/// Businesses per category, counting each business once under every
+/// category its codes fall within.
+pub async fn business_counts(
+    db: &Database,
+    categories: &[Category],
+) -> mongodb::error::Result<HashMap<String, u64>> {
+    #[derive(Deserialize)]
+    struct CodeSet {
+        #[serde(rename = "_id")]
+        codes: Vec<String>,
+        count: i64,
+    }
+
+    let mut cursor = db
+        .collection::<Document>("businesses")
+        .aggregate([
+            doc! { "$match": { "category_codes.0": { "$exists": true } } },
+            doc! { "$group": { "_id": "$category_codes", "count": { "$sum": 1 } } },
+        ])
+        .with_type::<CodeSet>()
+        .await?;
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    while let Some(set) = cursor.next().await {
+        let set = set?;
+        let within: HashSet<&str> = categories
+            .iter()
+            .filter(|category| {
+                set.codes
+                    .iter()
+                    .any(|code| code.starts_with(&category.code))
+            })
+            .map(|category| category.code.as_str())
+            .collect();
+        for code in within {
+            *counts.entry(code.to_string()).or_default() += set.count.max(0) as u64;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::category::bundled_taxonomy;
+
+    fn matched(text: &str) -> Option<String> {
+        best_match(text, &bundled_taxonomy()).map(|category| category.code.clone())
+    }
+
+    #[test]
+    fn best_match_takes_codes_and_names_as_they_are() {
+        assert_eq!(matched("g47").as_deref(), Some("G47"));
+        assert_eq!(matched(" Construction ").as_deref(), Some("F"));
+        assert_eq!(matched("Retail trade").as_deref(), Some("G47"));
+    }
+
+    #[test]
+    fn best_match_uses_keywords_ancestors_and_typos() {
+        assert_eq!(matched("Bakery").as_deref(), Some("C10"));
+        assert_eq!(matched("dairy farming").as_deref(), Some("A01"));
+        assert_eq!(matched("plumbng and roofing").as_deref(), Some("F43"));
+        assert_eq!(matched("Matatu company ltd").as_deref(), Some("H49"));
+    }
+
+    #[test]
+    fn best_match_prefers_the_broader_category_on_a_tie() {
+        assert_eq!(matched("building contractor").as_deref(), Some("F"));
+    }
+
+    #[test]
+    fn best_match_leaves_unclear_text_unmapped() {
+        assert_eq!(matched(""), None);
+        assert_eq!(matched("the and of"), None);
+        assert_eq!(matched("xyzzy quux"), None);
+        assert_eq!(
+            matched("we bake bread and sell it at the market every morning to our loyal customers"),
+            None
+        );
+    }
+
+    #[test]
+    fn categorize_prefers_given_codes() {
+        let categories = bundled_taxonomy();
+        let codes = vec!["g47".to_string(), "G47".to_string(), "A01".to_string()];
+        assert_eq!(
+            categorize(&codes, Some("Bakery"), &categories),
+            Ok(vec!["G47".to_string(), "A01".to_string()])
+        );
+        assert_eq!(
+            categorize(&["Z99".to_string()], None, &categories),
+            Err("Z99".to_string())
+        );
+        assert_eq!(
+            categorize(&[], Some("Bakery"), &categories),
+            Ok(vec!["C10".to_string()])
+        );
+        assert_eq!(categorize(&[], None, &categories), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn new_codes_must_extend_their_parent_without_overlap() {
+        let categories = bundled_taxonomy();
+        let parent = find("G", &categories);
+        assert_eq!(check_new_code("G48", parent, &categories), Ok(()));
+        assert!(check_new_code("g48", parent, &categories).is_err());
+        assert!(check_new_code("H48", parent, &categories).is_err());
+        assert!(check_new_code("G", parent, &categories).is_err());
+        // G470 would fall inside G47 when matching by prefix.
+        assert!(check_new_code("G470", parent, &categories).is_err());
+        assert!(check_new_code("A0", None, &categories).is_err());
+        assert_eq!(check_new_code("Z", None, &categories), Ok(()));
+    }
+}
@@ -0,0 +1,86 @@
+// telemetry.rs
+use std::env;
+
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Keeps the OTLP pipeline alive; dropping it flushes pending spans.
+pub struct TelemetryGuard {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush OTLP spans: {}", e);
+            }
+        }
+    }
+}
+
+/// JSON logs on stdout (`LOG_FORMAT=pretty` for local development), filtered by
+/// `RUST_LOG`. Records from the `log` crate (actix, the Mongo driver) are bridged
+/// in. With the `otlp` feature and `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are
+/// also exported to that collector.
+pub fn init() -> TelemetryGuard {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info,cucura_backend=debug"));
+    let fmt_layer = if env::var("LOG_FORMAT").is_ok_and(|format| format == "pretty") {
+        tracing_subscriber::fmt::layer().pretty().boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed()
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer);
+
+    #[cfg(feature = "otlp")]
+    {
+        let provider = otlp_provider();
+        let otel_layer = provider.as_ref().map(|provider| {
+            use opentelemetry::trace::TracerProvider as _;
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("cucura-backend"))
+        });
+        registry.with(otel_layer).init();
+        TelemetryGuard { provider }
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        TelemetryGuard {}
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_provider() -> Option<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+
+    // The exporter reads the endpoint (e.g. http://localhost:4318) from the env itself.
+    env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+    let exporter = match SpanExporter::builder().with_http().build() {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!(
+                "Failed to build OTLP exporter, spans will not be exported: {}",
+                e
+            );
+            return None;
+        }
+    };
+    Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(env!("CARGO_PKG_NAME"))
+                    .build(),
+            )
+            .build(),
+    )
+}
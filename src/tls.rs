@@ -0,0 +1,144 @@
+// tls.rs
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::crypto::ring;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use tracing::{info, warn};
+
+/// Native TLS, enabled when both `TLS_CERT_PATH` (PEM chain, leaf first) and
+/// `TLS_KEY_PATH` (PEM private key) are set. HTTP/2 is negotiated over ALPN.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// How often the files are checked for changes (`TLS_RELOAD_INTERVAL_SECS`).
+    pub reload_interval: Duration,
+    /// Plain HTTP port that only redirects to HTTPS (`TLS_REDIRECT_PORT`).
+    pub redirect_port: Option<u16>,
+    /// HTTPS port clients are redirected to (`TLS_PUBLIC_PORT`), for when a
+    /// load balancer maps it to something other than the listener's port.
+    pub public_port: Option<u16>,
+}
+
+impl TlsConfig {
+    pub fn from_env() -> Option<TlsConfig> {
+        let cert_path = env::var("TLS_CERT_PATH").ok()?;
+        let key_path = env::var("TLS_KEY_PATH").ok()?;
+        let reload_secs = env::var("TLS_RELOAD_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(30);
+        let port_from_env = |key: &str| {
+            env::var(key).ok().map(|value| {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{} must be a port, got {}", key, value))
+            })
+        };
+        Some(TlsConfig {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            reload_interval: Duration::from_secs(reload_secs),
+            redirect_port: port_from_env("TLS_REDIRECT_PORT"),
+            public_port: port_from_env("TLS_PUBLIC_PORT"),
+        })
+    }
+
+    /// Loads the certificate once up front so a bad path fails startup rather
+    /// than the first handshake.
+    pub fn server_config(&self) -> io::Result<(ServerConfig, Arc<ReloadingCertResolver>)> {
+        let resolver = Arc::new(ReloadingCertResolver {
+            current: RwLock::new(Arc::new(load_certified_key(
+                &self.cert_path,
+                &self.key_path,
+            )?)),
+            config: self.clone(),
+        });
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver.clone());
+        Ok((config, resolver))
+    }
+}
+
+/// Serves whichever certificate was loaded last, so renewed certificates are
+/// picked up by new connections without a restart.
+#[derive(Debug)]
+pub struct ReloadingCertResolver {
+    config: TlsConfig,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+impl ReloadingCertResolver {
+    /// Polls the cert and key modification times and reloads when either
+    /// changes. A pair that fails to load (e.g. the key was renewed but the
+    /// chain not yet written) keeps the previous certificate and is retried
+    /// on the next tick.
+    pub async fn watch(self: Arc<Self>) {
+        let mut loaded = self.modified();
+        let mut interval = tokio::time::interval(self.config.reload_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let modified = self.modified();
+            if modified == loaded {
+                continue;
+            }
+            match load_certified_key(&self.config.cert_path, &self.config.key_path) {
+                Ok(key) => {
+                    *self.current.write().unwrap() = Arc::new(key);
+                    loaded = modified;
+                    info!(cert_path = %self.config.cert_path.display(), "TLS certificate reloaded");
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to reload TLS certificate, keeping the current one")
+                }
+            }
+        }
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        Some((
+            modified(&self.config.cert_path)?,
+            modified(&self.config.key_path)?,
+        ))
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(invalid_data(format!(
+            "no certificates in {}",
+            cert_path.display()
+        )));
+    }
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or_else(|| invalid_data(format!("no private key in {}", key_path.display())))?;
+    let signing_key = ring::sign::any_supported_type(&key).map_err(invalid_data)?;
+
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    certified_key.keys_match().map_err(invalid_data)?;
+    Ok(certified_key)
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}